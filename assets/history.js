@@ -0,0 +1,263 @@
+// Drives the change history at /history. Entries are read from the API, so
+// that a server started with --token only shows them to people who have it.
+
+"use strict";
+
+(function () {
+  const TOKEN_KEY = "rojo-token";
+
+  const statusElement = document.getElementById("history-status");
+  const listElement = document.getElementById("history-list");
+  const olderElement = document.getElementById("history-older");
+
+  // The index of the oldest entry shown so far, which older entries are read
+  // from.
+  let oldest = null;
+
+  class UnauthorizedError extends Error {}
+
+  async function api(path) {
+    const headers = {};
+    const token = sessionStorage.getItem(TOKEN_KEY);
+
+    if (token) {
+      headers["Authorization"] = "Bearer " + token;
+    }
+
+    const response = await fetch("/api" + path, { headers });
+
+    if (response.status === 401) {
+      throw new UnauthorizedError();
+    }
+
+    const body = await response.json();
+
+    if (!response.ok) {
+      throw new Error(body.details || response.statusText);
+    }
+
+    return body;
+  }
+
+  function run(task) {
+    task().catch((err) => {
+      if (err instanceof UnauthorizedError) {
+        sessionStorage.removeItem(TOKEN_KEY);
+        askForToken();
+      } else {
+        setStatus("Error: " + err.message);
+      }
+    });
+  }
+
+  function setStatus(text) {
+    statusElement.textContent = text;
+  }
+
+  function askForToken() {
+    setStatus("This server requires the token shown when rojo serve started.");
+
+    const form = document.createElement("form");
+    form.className = "token-form";
+
+    const input = document.createElement("input");
+    input.type = "password";
+    input.placeholder = "Token";
+
+    const button = document.createElement("button");
+    button.type = "submit";
+    button.className = "button";
+    button.textContent = "Connect";
+
+    form.append(input, button);
+    form.addEventListener("submit", (event) => {
+      event.preventDefault();
+      sessionStorage.setItem(TOKEN_KEY, input.value.trim());
+      form.remove();
+      run(load);
+    });
+
+    statusElement.after(form);
+    input.focus();
+  }
+
+  async function load() {
+    setStatus("Loading...");
+
+    const path = oldest === null ? "/history" : `/history?before=${oldest}`;
+    const history = await api(path);
+
+    for (const entry of history.entries) {
+      listElement.appendChild(renderEntry(entry, history.sharedStrings || {}));
+      oldest = entry.index;
+    }
+
+    if (listElement.children.length === 0) {
+      setStatus("Nothing has changed since the server started.");
+    } else {
+      setStatus("");
+    }
+
+    olderElement.hidden = !history.more;
+  }
+
+  function renderEntry(entry, sharedStrings) {
+    const changes = document.createElement("ul");
+    changes.className = "history-changes";
+
+    for (const instance of entry.added) {
+      changes.appendChild(item("Added ", renderInstance(instance)));
+    }
+
+    for (const id of entry.removed) {
+      changes.appendChild(item("Removed instance ", code(id)));
+    }
+
+    for (const update of entry.updated) {
+      const updated = item("Updated ", renderInstance(update));
+
+      if (update.metadataChanged) {
+        const note = document.createElement("p");
+        note.className = "history-note";
+        note.textContent = "Rojo metadata changed";
+        updated.appendChild(note);
+      }
+
+      const table = document.createElement("table");
+      table.className = "property-table";
+
+      for (const change of update.changes) {
+        const row = document.createElement("tr");
+        const name = document.createElement("td");
+        name.textContent = change.property;
+
+        row.append(
+          name,
+          valueCell("diff-before", change.before, sharedStrings),
+          valueCell("diff-after", change.after, sharedStrings),
+        );
+        table.appendChild(row);
+      }
+
+      updated.appendChild(table);
+      changes.appendChild(updated);
+    }
+
+    for (const diagnostic of entry.reportedDiagnostics || []) {
+      const reported = item(`Problem: ${diagnostic.path}: ${diagnostic.message}`);
+      reported.className = "change-error";
+      changes.appendChild(reported);
+    }
+
+    for (const path of entry.clearedDiagnostics || []) {
+      changes.appendChild(item("Problem fixed: ", code(path)));
+    }
+
+    const counts = `+${entry.added.length} -${entry.removed.length} ~${entry.updated.length}`;
+
+    const summary = document.createElement("summary");
+    summary.append(
+      span("history-time", new Date(entry.timestamp).toLocaleString()),
+      span("history-cause", describeCause(entry.cause)),
+      span("history-counts", counts),
+    );
+
+    const details = document.createElement("details");
+    details.append(summary, changes);
+
+    const element = document.createElement("li");
+    element.className = "history-entry";
+    element.appendChild(details);
+    return element;
+  }
+
+  function describeCause(cause) {
+    if (cause == null) {
+      return "Unknown cause";
+    }
+
+    switch (cause.type) {
+      case "fileCreated":
+        return "Created " + cause.path;
+      case "fileChanged":
+        return "Changed " + cause.path;
+      case "fileRemoved":
+        return "Removed " + cause.path;
+      case "fileRescanned":
+        return "Rescanned " + cause.path;
+      case "clientWrite":
+        return "Written by a client";
+      case "resync":
+        return "Resynced";
+      default:
+        return "Unknown cause";
+    }
+  }
+
+  function renderInstance(instance) {
+    const element = document.createElement("span");
+
+    if (instance.name != null && instance.className != null) {
+      const name = document.createElement("strong");
+      name.textContent = instance.name;
+      element.append(name, span("tree-class", instance.className));
+    } else {
+      element.append(code(instance.id), span("history-note", " (since removed)"));
+    }
+
+    return element;
+  }
+
+  function valueCell(className, value, sharedStrings) {
+    const pre = document.createElement("pre");
+    pre.className = className;
+    pre.textContent = formatValue(value, sharedStrings);
+
+    const cell = document.createElement("td");
+    cell.appendChild(pre);
+    return cell;
+  }
+
+  // Values are sent tagged with their type, like `{ "Vector3": [1, 2, 3] }`.
+  // SharedStrings only carry a hash, and their contents are sent separately.
+  function formatValue(value, sharedStrings) {
+    if (value == null) {
+      return "(none)";
+    }
+
+    if (value.String !== undefined) {
+      return value.String;
+    }
+
+    if (value.SharedString !== undefined) {
+      const contents = sharedStrings[value.SharedString];
+      const size = contents === undefined ? "?" : atob(contents).length;
+      return `SharedString (${size} bytes)`;
+    }
+
+    return JSON.stringify(value);
+  }
+
+  function item(...content) {
+    const element = document.createElement("li");
+    element.append(...content);
+    return element;
+  }
+
+  function code(text) {
+    const element = document.createElement("code");
+    element.textContent = text;
+    return element;
+  }
+
+  function span(className, text) {
+    const element = document.createElement("span");
+    element.className = className;
+    element.textContent = text;
+    return element;
+  }
+
+  olderElement.addEventListener("click", () => run(load));
+
+  run(load);
+})();
@@ -0,0 +1,593 @@
+// Drives the instance browser at /show-instances. Instances are read from the
+// API as they're expanded instead of all at once, so that large places stay
+// usable, and are kept up to date by subscribing to changes.
+
+"use strict";
+
+(function () {
+  const MAX_RECENT_CHANGES = 50;
+  const HIGHLIGHT_MILLIS = 2000;
+  const SEARCH_DELAY_MILLIS = 250;
+  const TOKEN_KEY = "rojo-token";
+
+  const treeElement = document.getElementById("instance-tree");
+  const detailsElement = document.getElementById("instance-details");
+  const searchElement = document.getElementById("instance-search");
+  const statusElement = document.getElementById("instance-status");
+  const changesElement = document.getElementById("recent-changes");
+
+  const state = {
+    sessionId: null,
+    projectName: null,
+    rootId: null,
+    messageCursor: 0,
+
+    // Every instance read so far, by ID. Instances whose children haven't
+    // been read yet still list their children's IDs.
+    instances: new Map(),
+    expanded: new Set(),
+    selected: null,
+
+    // Instances that changed recently, which are shown highlighted.
+    highlighted: new Set(),
+
+    // The results of the current search, or null when not searching.
+    search: null,
+  };
+
+  class UnauthorizedError extends Error {}
+
+  async function api(path) {
+    const headers = {};
+    const token = sessionStorage.getItem(TOKEN_KEY);
+
+    if (token) {
+      headers["Authorization"] = "Bearer " + token;
+    }
+
+    const response = await fetch("/api" + path, { headers });
+
+    if (response.status === 401) {
+      throw new UnauthorizedError();
+    }
+
+    const body = await response.json();
+
+    if (!response.ok) {
+      throw new Error(body.details || response.statusText);
+    }
+
+    return body;
+  }
+
+  function run(task) {
+    task().catch((err) => {
+      if (err instanceof UnauthorizedError) {
+        sessionStorage.removeItem(TOKEN_KEY);
+        askForToken();
+      } else {
+        setStatus("Error: " + err.message);
+      }
+    });
+  }
+
+  function setStatus(text) {
+    statusElement.textContent = text;
+  }
+
+  function askForToken() {
+    setStatus("This server requires the token shown when rojo serve started.");
+
+    const form = document.createElement("form");
+    form.className = "token-form";
+
+    const input = document.createElement("input");
+    input.type = "password";
+    input.placeholder = "Token";
+
+    const button = document.createElement("button");
+    button.type = "submit";
+    button.className = "button";
+    button.textContent = "Connect";
+
+    form.append(input, button);
+    form.addEventListener("submit", (event) => {
+      event.preventDefault();
+      sessionStorage.setItem(TOKEN_KEY, input.value.trim());
+      form.remove();
+      run(start);
+    });
+
+    statusElement.after(form);
+    input.focus();
+  }
+
+  async function start() {
+    setStatus("Connecting...");
+
+    const info = await api("/rojo");
+    const read = await api(`/read/${info.rootInstanceId}?depth=1`);
+
+    state.sessionId = info.sessionId;
+    state.projectName = info.projectName;
+    state.rootId = info.rootInstanceId;
+    state.messageCursor = read.messageCursor;
+    state.instances.clear();
+    state.expanded.clear();
+    state.expanded.add(state.rootId);
+
+    addInstances(read.instances);
+    render();
+    setStatus("Connected to " + state.projectName);
+
+    subscribe();
+  }
+
+  async function subscribe() {
+    for (;;) {
+      let response;
+
+      try {
+        response = await api(`/subscribe/${state.messageCursor}`);
+      } catch (err) {
+        setStatus("Lost connection to the Rojo server: " + err.message);
+        return;
+      }
+
+      if (response.sessionId !== state.sessionId) {
+        setStatus("The Rojo server was restarted. Reload the page to reconnect.");
+        return;
+      }
+
+      if (response.sessionEnding) {
+        setStatus("The Rojo server was shut down.");
+        return;
+      }
+
+      state.messageCursor = response.messageCursor;
+
+      const touched = new Set();
+      for (const message of response.messages) {
+        applyMessage(message, touched);
+      }
+
+      render();
+
+      if (state.selected !== null && touched.has(state.selected)) {
+        run(() => showDetails(state.selected));
+      }
+    }
+  }
+
+  function addInstances(instances) {
+    for (const [id, instance] of Object.entries(instances)) {
+      state.instances.set(id, instance);
+    }
+  }
+
+  function applyMessage(message, touched) {
+    const changes = [];
+
+    for (const id of message.removed) {
+      const instance = state.instances.get(id);
+
+      if (instance !== undefined) {
+        changes.push({ kind: "removed", text: describe(instance) });
+        removeInstance(id);
+      }
+    }
+
+    for (const [id, instance] of Object.entries(message.added)) {
+      state.instances.set(id, instance);
+
+      const parent = state.instances.get(instance.Parent);
+      if (parent !== undefined && !parent.Children.includes(id)) {
+        parent.Children.push(id);
+      }
+
+      // Descendants of added instances are sent too, but only the topmost
+      // one is worth listing.
+      if (!(instance.Parent in message.added)) {
+        changes.push({ kind: "added", text: describe(instance) });
+      }
+
+      highlight(id);
+      touched.add(id);
+    }
+
+    for (const update of message.updated) {
+      const instance = state.instances.get(update.id);
+
+      // Changes to instances that haven't been read can't be shown, and will
+      // be there when they are read.
+      if (instance === undefined) {
+        continue;
+      }
+
+      const diffs = [];
+
+      if (update.changedName != null) {
+        diffs.push(["Name", instance.Name, update.changedName]);
+        instance.Name = update.changedName;
+      }
+
+      if (update.changedClassName != null) {
+        diffs.push(["ClassName", instance.ClassName, update.changedClassName]);
+        instance.ClassName = update.changedClassName;
+      }
+
+      for (const [key, value] of Object.entries(update.setProperties || {})) {
+        diffs.push([key, formatValue(instance.Properties[key]), formatValue(value)]);
+        instance.Properties[key] = value;
+      }
+
+      for (const key of update.removedProperties || []) {
+        diffs.push([key, formatValue(instance.Properties[key]), formatValue(null)]);
+        delete instance.Properties[key];
+      }
+
+      changes.push({ kind: "updated", text: describe(instance), diffs });
+      highlight(update.id);
+      touched.add(update.id);
+    }
+
+    for (const diagnostic of message.reportedDiagnostics || []) {
+      changes.push({ kind: "error", text: `${diagnostic.path}: ${diagnostic.message}` });
+    }
+
+    for (const path of message.clearedDiagnostics || []) {
+      changes.push({ kind: "fixed", text: `${path} was fixed` });
+    }
+
+    addRecentChanges(changes);
+  }
+
+  function removeInstance(id) {
+    const instance = state.instances.get(id);
+
+    if (instance === undefined) {
+      return;
+    }
+
+    for (const childId of instance.Children) {
+      removeInstance(childId);
+    }
+
+    const parent = state.instances.get(instance.Parent);
+    if (parent !== undefined) {
+      parent.Children = parent.Children.filter((childId) => childId !== id);
+    }
+
+    state.instances.delete(id);
+    state.expanded.delete(id);
+
+    if (state.selected === id) {
+      state.selected = null;
+      detailsElement.replaceChildren();
+    }
+  }
+
+  function highlight(id) {
+    state.highlighted.add(id);
+
+    setTimeout(() => {
+      state.highlighted.delete(id);
+      render();
+    }, HIGHLIGHT_MILLIS);
+  }
+
+  function describe(instance) {
+    if (instance.Name === instance.ClassName) {
+      return instance.Name;
+    }
+
+    return `${instance.Name} (${instance.ClassName})`;
+  }
+
+  function addRecentChanges(changes) {
+    const time = new Date().toLocaleTimeString();
+
+    for (const change of changes) {
+      const item = document.createElement("li");
+      item.className = "change change-" + change.kind;
+
+      const title = document.createElement("div");
+      title.textContent = `${time} ${change.kind}: ${change.text}`;
+      item.appendChild(title);
+
+      if (change.diffs !== undefined && change.diffs.length > 0) {
+        const list = document.createElement("ul");
+        list.className = "change-diffs";
+
+        for (const [label, before, after] of change.diffs) {
+          const diff = document.createElement("li");
+          diff.append(label + ": ", span("diff-before", before), " → ", span("diff-after", after));
+          list.appendChild(diff);
+        }
+
+        item.appendChild(list);
+      }
+
+      changesElement.prepend(item);
+    }
+
+    while (changesElement.children.length > MAX_RECENT_CHANGES) {
+      changesElement.lastElementChild.remove();
+    }
+  }
+
+  function span(className, text) {
+    const element = document.createElement("span");
+    element.className = className;
+    element.textContent = text;
+    return element;
+  }
+
+  function render() {
+    const root = state.instances.get(state.rootId);
+
+    if (root === undefined) {
+      treeElement.replaceChildren();
+    } else {
+      treeElement.replaceChildren(renderInstance(root));
+    }
+  }
+
+  function isExpanded(id) {
+    if (state.search !== null && state.search.ancestors.has(id)) {
+      return true;
+    }
+
+    return state.expanded.has(id);
+  }
+
+  function renderInstance(instance) {
+    const id = instance.Id;
+    const item = document.createElement("div");
+    item.className = "tree-item";
+
+    const row = document.createElement("div");
+    row.className = "tree-row";
+    row.classList.toggle("selected", state.selected === id);
+    row.classList.toggle("changed", state.highlighted.has(id));
+    row.classList.toggle("match", state.search !== null && state.search.matches.has(id));
+
+    const toggle = document.createElement("button");
+    toggle.className = "tree-toggle";
+
+    const expanded = isExpanded(id);
+
+    if (instance.Children.length > 0) {
+      toggle.textContent = expanded ? "▾" : "▸";
+      toggle.addEventListener("click", () => run(() => toggleExpanded(id)));
+    } else {
+      toggle.disabled = true;
+    }
+
+    const name = span("tree-name", instance.Name);
+    name.addEventListener("click", () => run(() => select(id)));
+
+    row.append(toggle, name);
+
+    if (instance.ClassName !== instance.Name) {
+      row.append(span("tree-class", instance.ClassName));
+    }
+
+    item.appendChild(row);
+
+    if (expanded) {
+      const children = document.createElement("div");
+      children.className = "tree-children";
+
+      // While searching, ancestors of matches only show the children that
+      // lead to matches, unless they were expanded by hand.
+      const onlyVisible =
+        state.search !== null && state.search.ancestors.has(id) && !state.expanded.has(id);
+
+      for (const childId of instance.Children) {
+        const child = state.instances.get(childId);
+
+        if (child === undefined || (onlyVisible && !state.search.visible.has(childId))) {
+          continue;
+        }
+
+        children.appendChild(renderInstance(child));
+      }
+
+      item.appendChild(children);
+    }
+
+    return item;
+  }
+
+  async function toggleExpanded(id) {
+    const instance = state.instances.get(id);
+
+    if (state.expanded.has(id) || (state.search !== null && state.search.ancestors.has(id))) {
+      state.expanded.delete(id);
+
+      if (state.search !== null) {
+        state.search.ancestors.delete(id);
+      }
+
+      render();
+      return;
+    }
+
+    state.expanded.add(id);
+
+    if (instance.Children.some((childId) => !state.instances.has(childId))) {
+      const read = await api(`/read/${id}?depth=1`);
+      addInstances(read.instances);
+    }
+
+    render();
+  }
+
+  async function select(id) {
+    state.selected = id;
+    render();
+
+    await showDetails(id);
+  }
+
+  async function showDetails(id) {
+    const read = await api(`/read/${id}?depth=0&sources=true`);
+
+    if (state.selected !== id) {
+      return;
+    }
+
+    const instance = read.instances[id];
+
+    if (instance === undefined) {
+      detailsElement.replaceChildren();
+      return;
+    }
+
+    const heading = document.createElement("h1");
+    heading.textContent = describe(instance);
+
+    const metadata = instance.Metadata || {};
+    const sections = [heading, field("ID", instance.Id)];
+
+    if (metadata.instigatingSource !== undefined) {
+      sections.push(field("Instigating source", formatSource(metadata.instigatingSource)));
+    }
+
+    if (metadata.relevantPaths !== undefined) {
+      const list = document.createElement("ul");
+      list.className = "path-list";
+
+      for (const path of metadata.relevantPaths) {
+        const item = document.createElement("li");
+        item.textContent = path;
+        list.appendChild(item);
+      }
+
+      sections.push(field("Relevant paths", list));
+    }
+
+    sections.push(field("Ignores unknown instances", String(metadata.ignoreUnknownInstances)));
+
+    const table = document.createElement("table");
+    table.className = "property-table";
+
+    for (const key of Object.keys(instance.Properties).sort()) {
+      const value = instance.Properties[key];
+      const row = document.createElement("tr");
+
+      for (const text of [key, valueType(value), formatValue(value)]) {
+        const cell = document.createElement("td");
+        cell.textContent = text;
+        row.appendChild(cell);
+      }
+
+      table.appendChild(row);
+    }
+
+    sections.push(field("Properties", table));
+
+    detailsElement.replaceChildren(...sections);
+  }
+
+  function field(label, content) {
+    const element = document.createElement("div");
+    element.className = "details-field";
+    element.append(span("details-label", label + ": "), content);
+    return element;
+  }
+
+  function formatSource(source) {
+    if (source.path !== undefined) {
+      return source.path;
+    }
+
+    const node = source.projectNode;
+    return `${node.name} in ${node.projectPath}`;
+  }
+
+  function valueType(value) {
+    return value == null ? "" : Object.keys(value)[0];
+  }
+
+  // Values are sent tagged with their type, like `{ "Vector3": [1, 2, 3] }`.
+  function formatValue(value) {
+    if (value == null) {
+      return "(none)";
+    }
+
+    return formatInner(Object.values(value)[0], false);
+  }
+
+  function formatInner(inner, nested) {
+    if (Array.isArray(inner)) {
+      const joined = inner.map((item) => formatInner(item, true)).join(", ");
+      return nested ? `(${joined})` : joined;
+    }
+
+    if (inner !== null && typeof inner === "object") {
+      return Object.entries(inner)
+        .map(([key, item]) => `${key}: ${formatInner(item, true)}`)
+        .join("; ");
+    }
+
+    return String(inner);
+  }
+
+  let searchTimer = null;
+
+  searchElement.addEventListener("input", () => {
+    clearTimeout(searchTimer);
+    searchTimer = setTimeout(() => run(search), SEARCH_DELAY_MILLIS);
+  });
+
+  async function search() {
+    const query = searchElement.value.trim();
+
+    if (query === "") {
+      state.search = null;
+      render();
+      setStatus("Connected to " + state.projectName);
+      return;
+    }
+
+    const response = await api(`/search/${encodeURIComponent(query)}`);
+
+    // Another search may have started while this one was in flight.
+    if (searchElement.value.trim() !== query) {
+      return;
+    }
+
+    addInstances(response.instances);
+
+    const matches = new Set(response.matches);
+    const ancestors = new Set();
+
+    for (const id of matches) {
+      let current = state.instances.get(id);
+
+      while (current !== undefined && state.instances.has(current.Parent)) {
+        ancestors.add(current.Parent);
+        current = state.instances.get(current.Parent);
+      }
+    }
+
+    state.search = {
+      matches,
+      ancestors,
+      visible: new Set([...matches, ...ancestors]),
+    };
+
+    render();
+
+    let status = `${matches.size} matching instances`;
+    if (response.truncated) {
+      status += " (only the first ones are shown)";
+    }
+
+    setStatus(status);
+  }
+
+  run(start);
+})();
@@ -37,6 +37,9 @@ fn place_setup<P: AsRef<Path>>(input_path: P) -> (TempDir, BuildCommand) {
         project: input,
         watch: false,
         output,
+        rev: None,
+        overlay: Vec::new(),
+        watcher_options: Default::default(),
     };
 
     (dir, options)
@@ -0,0 +1,313 @@
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+use crate::{InMemoryFs, Metadata, ReadDir, VfsBackend, VfsEvent, VfsSnapshot};
+
+/// Read-only `VfsBackend` that serves the contents of a zip or tar archive.
+///
+/// The archive is mounted at its own path, so an archive at `bundle.zip`
+/// containing `default.project.json` is read as if it were a directory, through
+/// `bundle.zip/default.project.json`. Paths outside of the archive are reported
+/// as not found.
+///
+/// The whole archive is read into memory when the backend is created. Entries
+/// with absolute paths or paths that contain `..` are skipped. Only available
+/// with the `archive` feature.
+pub struct ArchiveBackend {
+    inner: InMemoryFs,
+}
+
+impl ArchiveBackend {
+    /// Reads the archive at the given path. The format is picked from the file
+    /// extension; see [`is_archive`](#method.is_archive).
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let kind = ArchiveKind::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a zip or tar archive", path.display()),
+            )
+        })?;
+
+        let file = fs_err::File::open(path)?;
+        let mut root = VfsSnapshot::empty_dir();
+
+        match kind {
+            ArchiveKind::Zip => read_zip(file, &mut root)?,
+            ArchiveKind::Tar => read_tar(file, &mut root)?,
+            ArchiveKind::TarGz => read_tar(GzDecoder::new(file), &mut root)?,
+        }
+
+        let mut inner = InMemoryFs::new();
+        inner.load_snapshot(path, root)?;
+
+        Ok(Self { inner })
+    }
+
+    /// Tells whether the given path has the extension of an archive that
+    /// `ArchiveBackend` can read: `.zip`, `.tar`, `.tar.gz`, or `.tgz`.
+    pub fn is_archive<P: AsRef<Path>>(path: P) -> bool {
+        ArchiveKind::from_path(path.as_ref()).is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?.to_lowercase();
+
+        if file_name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if file_name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+fn read_zip<R: Read + io::Seek>(reader: R, root: &mut VfsSnapshot) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(archive_error)?;
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).map_err(archive_error)?;
+
+        let path = match file.enclosed_name() {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let snapshot = if file.is_dir() {
+            VfsSnapshot::empty_dir()
+        } else {
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+
+            if file.is_symlink() {
+                let target = String::from_utf8(contents).map_err(invalid_data)?;
+                VfsSnapshot::symlink(target)
+            } else {
+                VfsSnapshot::file(contents)
+            }
+        };
+
+        root.insert(&path, snapshot)?;
+    }
+
+    Ok(())
+}
+
+fn read_tar<R: Read>(reader: R, root: &mut VfsSnapshot) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+
+        let is_enclosed = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+        if !is_enclosed {
+            continue;
+        }
+
+        let entry_type = entry.header().entry_type();
+
+        let snapshot = if entry_type.is_dir() {
+            VfsSnapshot::empty_dir()
+        } else if entry_type.is_symlink() {
+            match entry.link_name()? {
+                Some(target) => VfsSnapshot::symlink(target.into_owned()),
+                None => continue,
+            }
+        } else if entry_type.is_file() {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            VfsSnapshot::file(contents)
+        } else {
+            // Hard links, devices, and other special entries have no
+            // equivalent in a VfsSnapshot.
+            continue;
+        };
+
+        root.insert(&path, snapshot)?;
+    }
+
+    Ok(())
+}
+
+impl VfsBackend for ArchiveBackend {
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+
+    fn write(&mut self, _path: &Path, _data: &[u8]) -> io::Result<()> {
+        read_only()
+    }
+
+    fn read_dir(&mut self, path: &Path) -> io::Result<ReadDir> {
+        self.inner.read_dir(path)
+    }
+
+    fn metadata(&mut self, path: &Path) -> io::Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+        self.inner.canonicalize(path)
+    }
+
+    fn remove_file(&mut self, _path: &Path) -> io::Result<()> {
+        read_only()
+    }
+
+    fn remove_dir_all(&mut self, _path: &Path) -> io::Result<()> {
+        read_only()
+    }
+
+    fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent> {
+        // An archive is only read once, so there's nothing to report.
+        crossbeam_channel::never()
+    }
+
+    fn watch(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn unwatch(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn archive_error(err: zip::result::ZipError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn read_only<T>() -> io::Result<T> {
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        "ArchiveBackend is read-only",
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use crate::conformance;
+
+    fn write_zip(path: &Path) {
+        fn add<W: Write + io::Seek>(
+            writer: &mut zip::ZipWriter<W>,
+            path: &str,
+            snapshot: VfsSnapshot,
+        ) {
+            let options = zip::write::SimpleFileOptions::default();
+
+            match snapshot {
+                VfsSnapshot::File { contents } => {
+                    writer.start_file(path, options).unwrap();
+                    writer.write_all(&contents).unwrap();
+                }
+                VfsSnapshot::Dir { children } => {
+                    if !path.is_empty() {
+                        writer.add_directory(path, options).unwrap();
+                    }
+
+                    for (name, child) in children {
+                        let child_path = if path.is_empty() {
+                            name
+                        } else {
+                            format!("{}/{}", path, name)
+                        };
+
+                        add(writer, &child_path, child);
+                    }
+                }
+                VfsSnapshot::Symlink { target } => {
+                    let target = target.to_str().expect("symlink target must be UTF-8");
+                    writer.add_symlink(path, target, options).unwrap();
+                }
+            }
+        }
+
+        let mut writer = zip::ZipWriter::new(fs_err::File::create(path).unwrap());
+        add(&mut writer, "", conformance::fixture());
+        writer.finish().unwrap();
+    }
+
+    fn write_tar_gz(path: &Path) {
+        let dir = tempfile::tempdir().unwrap();
+        let contents = dir.path().join("contents");
+        conformance::write_fixture_to_disk(&contents).unwrap();
+
+        let encoder = GzEncoder::new(fs_err::File::create(path).unwrap(), Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        builder.append_dir_all(".", &contents).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn conformance_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("fixture.zip");
+        write_zip(&root);
+
+        conformance::check_read(&mut ArchiveBackend::new(&root).unwrap(), &root);
+    }
+
+    #[test]
+    fn conformance_tar_gz() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("fixture.tar.gz");
+        write_tar_gz(&root);
+
+        conformance::check_read(&mut ArchiveBackend::new(&root).unwrap(), &root);
+    }
+
+    #[test]
+    fn read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("fixture.zip");
+        write_zip(&root);
+
+        let mut backend = ArchiveBackend::new(&root).unwrap();
+
+        assert!(backend.write(&root.join("a.txt"), b"nope").is_err());
+        assert!(backend.remove_file(&root.join("a.txt")).is_err());
+        assert!(backend.remove_dir_all(&root.join("b")).is_err());
+    }
+
+    #[test]
+    fn is_archive() {
+        assert!(ArchiveBackend::is_archive("bundle.zip"));
+        assert!(ArchiveBackend::is_archive("bundle.tar"));
+        assert!(ArchiveBackend::is_archive("bundle.tar.gz"));
+        assert!(ArchiveBackend::is_archive("bundle.TGZ"));
+        assert!(!ArchiveBackend::is_archive("default.project.json"));
+        assert!(!ArchiveBackend::is_archive("src"));
+    }
+}
@@ -0,0 +1,347 @@
+//! A conformance test suite that any [`VfsBackend`](../trait.VfsBackend.html)
+//! can run to check that it behaves the way `Vfs` and Rojo expect.
+//!
+//! Backends are checked against a fixed tree of files, given by
+//! [`fixture`](fn.fixture.html). Load the fixture into a fresh backend at some
+//! root path, then pass the backend and root to
+//! [`check_read`](fn.check_read.html). Backends that support writing should
+//! also pass a fresh backend to [`check_write`](fn.check_write.html), and
+//! backends that watch for changes to [`check_events`](fn.check_events.html).
+//!
+//! ```ignore
+//! #[test]
+//! fn conformance() {
+//!     let mut backend = MyBackend::from_snapshot("/root", memofs::conformance::fixture());
+//!     memofs::conformance::check_read(&mut backend, Path::new("/root"));
+//! }
+//! ```
+//!
+//! Checks panic with a description of the problem when they fail, which makes
+//! them suitable for calling directly from `#[test]` functions.
+//!
+//! This module is only available with the `conformance` feature enabled.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Receiver;
+
+use crate::{VfsBackend, VfsEvent, VfsSnapshot};
+
+/// How long `check_events` waits for each event before failing.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The tree of files that backends are checked against:
+///
+/// ```text
+/// a.txt            "contents of a"
+/// b/
+///     c.txt        "contents of c"
+///     d/
+///         e.txt    "contents of e"
+/// ```
+///
+/// The fixture has no empty directories, since some backends, like git, can't
+/// represent them.
+pub fn fixture() -> VfsSnapshot {
+    VfsSnapshot::dir(vec![
+        ("a.txt", VfsSnapshot::file("contents of a")),
+        (
+            "b",
+            VfsSnapshot::dir(vec![
+                ("c.txt", VfsSnapshot::file("contents of c")),
+                (
+                    "d",
+                    VfsSnapshot::dir(vec![("e.txt", VfsSnapshot::file("contents of e"))]),
+                ),
+            ]),
+        ),
+    ])
+}
+
+/// Writes the [`fixture`](fn.fixture.html) to the real filesystem at the given
+/// path, which must not exist yet. Useful for backends that are populated from
+/// disk.
+pub fn write_fixture_to_disk(root: &Path) -> io::Result<()> {
+    write_snapshot_to_disk(root, fixture())
+}
+
+fn write_snapshot_to_disk(path: &Path, snapshot: VfsSnapshot) -> io::Result<()> {
+    match snapshot {
+        VfsSnapshot::File { contents } => fs_err::write(path, contents),
+        VfsSnapshot::Dir { children } => {
+            fs_err::create_dir(path)?;
+
+            for (name, child) in children {
+                write_snapshot_to_disk(&path.join(name), child)?;
+            }
+
+            Ok(())
+        }
+        VfsSnapshot::Symlink { .. } => Err(io::Error::new(
+            io::ErrorKind::Other,
+            "symlinks are not supported by write_fixture_to_disk",
+        )),
+    }
+}
+
+/// Checks all of the read-only operations of a backend containing the
+/// [`fixture`](fn.fixture.html) at `root`.
+///
+/// The backend is not modified.
+pub fn check_read<B: VfsBackend + ?Sized>(backend: &mut B, root: &Path) {
+    let a = root.join("a.txt");
+    let b = root.join("b");
+    let c = b.join("c.txt");
+    let d = b.join("d");
+    let e = d.join("e.txt");
+    let missing = root.join("missing");
+
+    // read
+    assert_eq!(
+        backend.read(&a).expect("read of a file failed"),
+        b"contents of a",
+        "read returned the wrong contents for {}",
+        a.display()
+    );
+    assert_eq!(
+        backend.read(&c).expect("read of a nested file failed"),
+        b"contents of c",
+        "read returned the wrong contents for {}",
+        c.display()
+    );
+    assert!(backend.read(&b).is_err(), "read of a directory should fail");
+    assert_not_found(backend.read(&missing), "read");
+
+    // read_dir
+    assert_eq!(
+        entries(backend, root),
+        vec![a.clone(), b.clone()].into_iter().collect(),
+        "read_dir returned the wrong entries for {}",
+        root.display()
+    );
+    assert_eq!(
+        entries(backend, &b),
+        vec![c.clone(), d.clone()].into_iter().collect(),
+        "read_dir returned the wrong entries for {}",
+        b.display()
+    );
+    assert_eq!(
+        entries(backend, &d),
+        vec![e].into_iter().collect(),
+        "read_dir returned the wrong entries for {}",
+        d.display()
+    );
+    assert!(
+        backend.read_dir(&a).is_err(),
+        "read_dir of a file should fail"
+    );
+    assert_not_found(backend.read_dir(&missing), "read_dir");
+
+    // metadata
+    let meta = backend.metadata(&a).expect("metadata of a file failed");
+    assert!(meta.is_file() && !meta.is_dir(), "a.txt should be a file");
+    assert!(!meta.is_symlink(), "a.txt should not be a symlink");
+
+    let meta = backend
+        .metadata(&b)
+        .expect("metadata of a directory failed");
+    assert!(meta.is_dir() && !meta.is_file(), "b should be a directory");
+    assert!(!meta.is_symlink(), "b should not be a symlink");
+
+    assert_not_found(backend.metadata(&missing), "metadata");
+
+    // canonicalize
+    let canonical = backend
+        .canonicalize(&c)
+        .expect("canonicalize of a file failed");
+    assert_eq!(
+        backend
+            .read(&canonical)
+            .expect("read of a canonicalized path failed"),
+        b"contents of c",
+        "canonicalized path {} should refer to the same file",
+        canonical.display()
+    );
+    assert_not_found(backend.canonicalize(&missing), "canonicalize");
+
+    // watch and unwatch
+    for path in &[root.to_path_buf(), a.clone(), b.clone(), c.clone()] {
+        backend
+            .watch(path)
+            .unwrap_or_else(|err| panic!("watch of {} failed: {}", path.display(), err));
+    }
+    for path in &[root.to_path_buf(), a, b, c] {
+        backend
+            .unwatch(path)
+            .unwrap_or_else(|err| panic!("unwatch of {} failed: {}", path.display(), err));
+    }
+}
+
+/// Checks all of the mutating operations of a backend containing the
+/// [`fixture`](fn.fixture.html) at `root`.
+///
+/// The backend is modified, so it should not be reused for other checks.
+pub fn check_write<B: VfsBackend + ?Sized>(backend: &mut B, root: &Path) {
+    let a = root.join("a.txt");
+    let b = root.join("b");
+    let c = b.join("c.txt");
+    let new = root.join("new.txt");
+
+    // write
+    backend
+        .write(&new, b"new contents")
+        .expect("write of a new file failed");
+    assert_eq!(
+        backend.read(&new).expect("read of a new file failed"),
+        b"new contents",
+        "written file should be readable"
+    );
+    assert!(
+        entries(backend, root).contains(&new),
+        "written file should be listed by read_dir"
+    );
+
+    backend
+        .write(&a, b"changed")
+        .expect("write of an existing file failed");
+    assert_eq!(
+        backend
+            .read(&a)
+            .expect("read of an overwritten file failed"),
+        b"changed",
+        "overwritten file should have its new contents"
+    );
+
+    // remove_file
+    assert!(
+        backend.remove_file(&b).is_err(),
+        "remove_file of a directory should fail"
+    );
+    backend.remove_file(&a).expect("remove_file failed");
+    assert_not_found(backend.read(&a), "read after remove_file");
+
+    // remove_dir_all
+    assert!(
+        backend.remove_dir_all(&new).is_err(),
+        "remove_dir_all of a file should fail"
+    );
+    backend.remove_dir_all(&b).expect("remove_dir_all failed");
+    assert_not_found(backend.metadata(&b), "metadata after remove_dir_all");
+    assert_not_found(backend.read(&c), "read of a child after remove_dir_all");
+}
+
+/// Checks that a backend containing the [`fixture`](fn.fixture.html) at `root`
+/// reports changes the way [`VfsBackend`](../trait.VfsBackend.html) documents.
+///
+/// Paths are watched the way `Vfs` would after reading them, then changed
+/// through the backend. Since backends have no way to rename, `rename` is
+/// called to move a file from its first path to its second, like
+/// `std::fs::rename`. Extra events are allowed, since events may be spurious.
+///
+/// The backend is modified, so it should not be reused for other checks.
+pub fn check_events<B, R>(backend: &mut B, root: &Path, mut rename: R)
+where
+    B: VfsBackend + ?Sized,
+    R: FnMut(&Path, &Path) -> io::Result<()>,
+{
+    let a = root.join("a.txt");
+    let b = root.join("b");
+    let c = b.join("c.txt");
+    let d = b.join("d");
+    let new = root.join("new.txt");
+    let renamed = root.join("renamed.txt");
+
+    let receiver = backend.event_receiver();
+
+    for path in &[root.to_path_buf(), a.clone(), b.clone(), c.clone()] {
+        backend
+            .watch(path)
+            .unwrap_or_else(|err| panic!("watch of {} failed: {}", path.display(), err));
+    }
+
+    backend.write(&a, b"changed").expect("write failed");
+    expect_event(&receiver, VfsEvent::Write(a), "writing a watched file");
+
+    backend
+        .write(&new, b"new")
+        .expect("write of a new file failed");
+    expect_event(
+        &receiver,
+        VfsEvent::Create(new.clone()),
+        "creating a file in a watched directory",
+    );
+
+    backend.remove_file(&c).expect("remove_file failed");
+    expect_event(&receiver, VfsEvent::Remove(c), "removing a watched file");
+
+    backend.remove_dir_all(&d).expect("remove_dir_all failed");
+    expect_event(
+        &receiver,
+        VfsEvent::Remove(d),
+        "removing a directory in a watched directory",
+    );
+
+    rename(&new, &renamed)
+        .unwrap_or_else(|err| panic!("rename of {} failed: {}", new.display(), err));
+    expect_event(&receiver, VfsEvent::Remove(new), "renaming a file");
+    expect_event(&receiver, VfsEvent::Create(renamed), "renaming a file");
+}
+
+/// Waits for `expected`, which must be a `Create`, `Write`, or `Remove`
+/// event, skipping any other events.
+fn expect_event(receiver: &Receiver<VfsEvent>, expected: VfsEvent, action: &str) {
+    let deadline = Instant::now() + EVENT_TIMEOUT;
+    let mut skipped = Vec::new();
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let event = receiver.recv_timeout(remaining).unwrap_or_else(|_| {
+            panic!(
+                "{} should send {:?}, but only these events arrived: {:?}",
+                action, expected, skipped
+            )
+        });
+
+        let matches = match (&event, &expected) {
+            (VfsEvent::Create(path), VfsEvent::Create(expected))
+            | (VfsEvent::Write(path), VfsEvent::Write(expected))
+            | (VfsEvent::Remove(path), VfsEvent::Remove(expected)) => path == expected,
+            _ => false,
+        };
+
+        if matches {
+            return;
+        }
+
+        skipped.push(event);
+    }
+}
+
+fn entries<B: VfsBackend + ?Sized>(backend: &mut B, path: &Path) -> BTreeSet<PathBuf> {
+    backend
+        .read_dir(path)
+        .unwrap_or_else(|err| panic!("read_dir of {} failed: {}", path.display(), err))
+        .map(|entry| {
+            entry
+                .unwrap_or_else(|err| panic!("read_dir of {} failed: {}", path.display(), err))
+                .path()
+                .to_path_buf()
+        })
+        .collect()
+}
+
+fn assert_not_found<T>(result: io::Result<T>, operation: &str) {
+    match result {
+        Ok(_) => panic!("{} of a missing path should fail", operation),
+        Err(err) => assert_eq!(
+            err.kind(),
+            io::ErrorKind::NotFound,
+            "{} of a missing path should fail with NotFound, got: {}",
+            operation,
+            err
+        ),
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use gix::objs::tree::EntryKind;
+use gix::ObjectId;
+
+use crate::symlink;
+use crate::{DirEntry, Metadata, ReadDir, VfsBackend, VfsEvent};
+
+/// Read-only `VfsBackend` that serves the files of a single git commit,
+/// without touching the working copy.
+///
+/// The commit's tree is mounted at the repository's working directory, so
+/// paths look exactly as they would if the commit were checked out. Paths
+/// outside of the working directory are reported as not found.
+///
+/// Objects are read through [gitoxide](https://github.com/Byron/gitoxide), a
+/// pure Rust implementation of git. Only available with the `git` feature.
+pub struct GitBackend {
+    repo: gix::Repository,
+    entries: HashMap<PathBuf, Entry>,
+}
+
+#[derive(Debug)]
+enum Entry {
+    File { id: ObjectId },
+    Dir { children: BTreeSet<OsString> },
+    Symlink { target: PathBuf },
+}
+
+impl GitBackend {
+    /// Opens the repository containing `path` and mounts the tree of `rev`,
+    /// which can be any revision understood by `git rev-parse`, like a branch,
+    /// tag, or commit hash.
+    ///
+    /// `path` doesn't need to exist in the working copy. The nearest ancestor
+    /// that does exist is used to find the repository.
+    pub fn new<P: AsRef<Path>>(path: P, rev: &str) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let search_path = path
+            .ancestors()
+            .find(|ancestor| ancestor.is_dir())
+            .unwrap_or(path);
+
+        let repo = gix::discover(search_path).map_err(git_error)?;
+
+        let mount_path = repo
+            .workdir()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "git repository containing {} has no working directory",
+                        path.display()
+                    ),
+                )
+            })?
+            .to_path_buf();
+
+        let tree_id = repo
+            .rev_parse_single(rev)
+            .map_err(git_error)?
+            .object()
+            .map_err(git_error)?
+            .peel_to_tree()
+            .map_err(git_error)?
+            .id;
+
+        let mut entries = HashMap::new();
+        index_tree(&repo, tree_id, mount_path, &mut entries)?;
+
+        Ok(Self { repo, entries })
+    }
+
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        symlink::resolve(path, |path| self.read_link(path))
+    }
+
+    fn resolve_parent(&self, path: &Path) -> io::Result<PathBuf> {
+        symlink::resolve_parent(path, |path| self.read_link(path))
+    }
+
+    fn read_link(&self, path: &Path) -> Option<PathBuf> {
+        match self.entries.get(path) {
+            Some(Entry::Symlink { target }) => Some(target.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Walks the tree with the given ID, recording every entry in it under
+/// `path`. Blobs are not read until they're needed.
+fn index_tree(
+    repo: &gix::Repository,
+    tree_id: ObjectId,
+    path: PathBuf,
+    entries: &mut HashMap<PathBuf, Entry>,
+) -> io::Result<()> {
+    let tree = repo.find_tree(tree_id).map_err(git_error)?;
+    let mut children = BTreeSet::new();
+
+    for entry in tree.iter() {
+        let entry = entry.map_err(git_error)?;
+        let name = gix::path::try_from_bstr(entry.filename())
+            .map_err(git_error)?
+            .into_owned();
+        let child_path = path.join(&name);
+
+        match entry.kind() {
+            EntryKind::Tree => {
+                index_tree(repo, entry.object_id(), child_path, entries)?;
+            }
+            EntryKind::Blob | EntryKind::BlobExecutable => {
+                entries.insert(
+                    child_path,
+                    Entry::File {
+                        id: entry.object_id(),
+                    },
+                );
+            }
+            EntryKind::Link => {
+                let blob = repo.find_blob(entry.object_id()).map_err(git_error)?;
+                let target = gix::path::try_from_byte_slice(&blob.data)
+                    .map_err(git_error)?
+                    .to_path_buf();
+
+                entries.insert(child_path, Entry::Symlink { target });
+            }
+            // Submodules point to commits in other repositories, which we
+            // can't read.
+            EntryKind::Commit => continue,
+        }
+
+        children.insert(name.into_os_string());
+    }
+
+    entries.insert(path, Entry::Dir { children });
+
+    Ok(())
+}
+
+impl VfsBackend for GitBackend {
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        let resolved = self.resolve(path)?;
+
+        match self.entries.get(&resolved) {
+            Some(Entry::File { id }) => {
+                let blob = self.repo.find_blob(*id).map_err(git_error)?;
+                Ok(blob.data.clone())
+            }
+            Some(Entry::Dir { .. }) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "path {} was a directory, but must be a file",
+                    path.display()
+                ),
+            )),
+            _ => not_found(path),
+        }
+    }
+
+    fn write(&mut self, _path: &Path, _data: &[u8]) -> io::Result<()> {
+        read_only()
+    }
+
+    fn read_dir(&mut self, path: &Path) -> io::Result<ReadDir> {
+        let resolved = self.resolve(path)?;
+
+        match self.entries.get(&resolved) {
+            Some(Entry::Dir { children }) => {
+                let entries: Vec<_> = children
+                    .iter()
+                    .map(|name| Ok(DirEntry::new(path.join(name))))
+                    .collect();
+
+                Ok(ReadDir::new(entries))
+            }
+            Some(Entry::File { .. }) => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "path {} was a file, but must be a directory",
+                    path.display()
+                ),
+            )),
+            _ => not_found(path),
+        }
+    }
+
+    fn metadata(&mut self, path: &Path) -> io::Result<Metadata> {
+        let link_path = self.resolve_parent(path)?;
+        let is_symlink = matches!(self.entries.get(&link_path), Some(Entry::Symlink { .. }));
+        let resolved = self.resolve(path)?;
+
+        match self.entries.get(&resolved) {
+            Some(Entry::File { .. }) => Ok(Metadata::file().symlink(is_symlink)),
+            Some(Entry::Dir { .. }) => Ok(Metadata::dir().symlink(is_symlink)),
+            _ => not_found(path),
+        }
+    }
+
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let resolved = self.resolve(path)?;
+
+        if self.entries.contains_key(&resolved) {
+            Ok(resolved)
+        } else {
+            not_found(path)
+        }
+    }
+
+    fn remove_file(&mut self, _path: &Path) -> io::Result<()> {
+        read_only()
+    }
+
+    fn remove_dir_all(&mut self, _path: &Path) -> io::Result<()> {
+        read_only()
+    }
+
+    fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent> {
+        // A commit never changes, so there's nothing to report.
+        crossbeam_channel::never()
+    }
+
+    fn watch(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn unwatch(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn git_error<E>(err: E) -> io::Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+fn read_only<T>() -> io::Result<T> {
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        "GitBackend is read-only",
+    ))
+}
+
+fn not_found<T>(path: &Path) -> io::Result<T> {
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("path {} not found", path.display()),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::process::Command;
+
+    use crate::conformance;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_AUTHOR_NAME", "memofs")
+            .env("GIT_AUTHOR_EMAIL", "memofs@example.com")
+            .env("GIT_COMMITTER_NAME", "memofs")
+            .env("GIT_COMMITTER_EMAIL", "memofs@example.com")
+            .status()
+            .expect("couldn't run git");
+
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    /// Creates a repository whose first commit contains the conformance
+    /// fixture under `root`, returning the directory and the root path.
+    fn fixture_repo() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        conformance::write_fixture_to_disk(&root).unwrap();
+
+        git(dir.path(), &["init", "--quiet"]);
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "--quiet", "-m", "Fixture"]);
+
+        (dir, root)
+    }
+
+    #[test]
+    fn conformance_read() {
+        let (_dir, root) = fixture_repo();
+
+        // Remove the fixture from the working copy to make sure everything is
+        // read from the commit.
+        fs_err::remove_dir_all(&root).unwrap();
+
+        let mut backend = GitBackend::new(&root, "HEAD").unwrap();
+        conformance::check_read(&mut backend, &root);
+    }
+
+    #[test]
+    fn older_revision() {
+        let (dir, root) = fixture_repo();
+
+        fs_err::write(root.join("a.txt"), "changed").unwrap();
+        git(dir.path(), &["commit", "--quiet", "-am", "Change a"]);
+
+        let mut old = GitBackend::new(&root, "HEAD~1").unwrap();
+        assert_eq!(old.read(&root.join("a.txt")).unwrap(), b"contents of a");
+
+        let mut new = GitBackend::new(&root, "HEAD").unwrap();
+        assert_eq!(new.read(&root.join("a.txt")).unwrap(), b"changed");
+    }
+
+    #[test]
+    fn read_only() {
+        let (_dir, root) = fixture_repo();
+        let mut backend = GitBackend::new(&root, "HEAD").unwrap();
+
+        assert!(backend.write(&root.join("a.txt"), b"nope").is_err());
+        assert!(backend.remove_file(&root.join("a.txt")).is_err());
+        assert!(backend.remove_dir_all(&root.join("b")).is_err());
+    }
+}
@@ -1,10 +1,11 @@
-use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 use crossbeam_channel::{Receiver, Sender};
 
+use crate::symlink;
 use crate::{DirEntry, Metadata, ReadDir, VfsBackend, VfsEvent, VfsSnapshot};
 
 /// In-memory filesystem that can be used as a VFS backend.
@@ -12,6 +13,10 @@ use crate::{DirEntry, Metadata, ReadDir, VfsBackend, VfsEvent, VfsSnapshot};
 /// Internally reference counted to enable giving a copy to
 /// [`Vfs`](struct.Vfs.html) and keeping the original to mutate the filesystem's
 /// state with.
+///
+/// Changes made through it are reported as events for paths that are being
+/// watched, the same way a backend watching the real filesystem would.
+/// [`raise_event`](#method.raise_event) can send any other event.
 #[derive(Debug, Clone)]
 pub struct InMemoryFs {
     inner: Arc<Mutex<InMemoryFsInner>>,
@@ -48,6 +53,30 @@ impl InMemoryFs {
         let inner = self.inner.lock().unwrap();
         inner.event_sender.send(event).unwrap();
     }
+
+    /// Moves a file or directory to a new path, replacing anything that was
+    /// there, like `std::fs::rename`. Watchers see the old path removed and
+    /// the new one created.
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> io::Result<()> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let mut inner = self.inner.lock().unwrap();
+        let from_link = inner.resolve_parent(from)?;
+        let to_link = inner.resolve_parent(to)?;
+
+        let snapshot = match inner.snapshot(&from_link) {
+            Some(snapshot) => snapshot,
+            None => return not_found(from),
+        };
+
+        inner.remove(from_link);
+        inner.remove(to_link.clone());
+        inner.load_snapshot(to_link, snapshot)?;
+
+        inner.notify_removed(from);
+        inner.notify_created(to);
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -55,6 +84,9 @@ struct InMemoryFsInner {
     entries: HashMap<PathBuf, Entry>,
     orphans: BTreeSet<PathBuf>,
 
+    /// Paths passed to `watch`, in the form they were given.
+    watched: HashSet<PathBuf>,
+
     event_receiver: Receiver<VfsEvent>,
     event_sender: Sender<VfsEvent>,
 }
@@ -66,6 +98,7 @@ impl InMemoryFsInner {
         Self {
             entries: HashMap::new(),
             orphans: BTreeSet::new(),
+            watched: HashSet::new(),
             event_receiver,
             event_sender,
         }
@@ -103,11 +136,78 @@ impl InMemoryFsInner {
                     self.load_snapshot(full_path, child)?;
                 }
             }
+            VfsSnapshot::Symlink { target } => {
+                self.entries.insert(path, Entry::Symlink { target });
+            }
         }
 
         Ok(())
     }
 
+    /// Resolves every symbolic link in the given path, including the last
+    /// component.
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        symlink::resolve(path, |path| self.read_link(path))
+    }
+
+    /// Resolves every symbolic link in the given path except for the last
+    /// component, which is useful for operating on links themselves.
+    fn resolve_parent(&self, path: &Path) -> io::Result<PathBuf> {
+        symlink::resolve_parent(path, |path| self.read_link(path))
+    }
+
+    fn read_link(&self, path: &Path) -> Option<PathBuf> {
+        match self.entries.get(path) {
+            Some(Entry::Symlink { target }) => Some(target.clone()),
+            _ => None,
+        }
+    }
+
+    /// Reads the entry at `path` and everything inside of it back out as a
+    /// snapshot.
+    fn snapshot(&self, path: &Path) -> Option<VfsSnapshot> {
+        match self.entries.get(path)? {
+            Entry::File { contents } => Some(VfsSnapshot::file(contents.clone())),
+            Entry::Dir { children } => {
+                let children = children.iter().filter_map(|child| {
+                    let name = child.file_name()?.to_string_lossy().into_owned();
+                    Some((name, self.snapshot(child)?))
+                });
+
+                Some(VfsSnapshot::dir(children.collect::<Vec<_>>()))
+            }
+            Entry::Symlink { target } => Some(VfsSnapshot::symlink(target.clone())),
+        }
+    }
+
+    fn parent_watched(&self, path: &Path) -> bool {
+        path.parent()
+            .is_some_and(|parent| self.watched.contains(parent))
+    }
+
+    fn send(&self, event: VfsEvent) {
+        // Nobody listening isn't a problem, since events are only a hint.
+        let _ = self.event_sender.send(event);
+    }
+
+    fn notify_created(&self, path: &Path) {
+        if self.parent_watched(path) {
+            self.send(VfsEvent::Create(path.to_path_buf()));
+        }
+    }
+
+    fn notify_written(&self, path: &Path) {
+        if self.watched.contains(path) {
+            self.send(VfsEvent::Write(path.to_path_buf()));
+        }
+    }
+
+    fn notify_removed(&self, path: &Path) {
+        if self.watched.contains(path) || self.parent_watched(path) {
+            self.send(VfsEvent::Remove(path.to_path_buf()));
+        }
+    }
+
     fn remove(&mut self, root_path: PathBuf) {
         self.orphans.remove(&root_path);
 
@@ -127,55 +227,75 @@ enum Entry {
     File { contents: Vec<u8> },
 
     Dir { children: BTreeSet<PathBuf> },
+
+    Symlink { target: PathBuf },
 }
 
 impl VfsBackend for InMemoryFs {
     fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
         let inner = self.inner.lock().unwrap();
+        let resolved = inner.resolve(path)?;
 
-        match inner.entries.get(path) {
+        match inner.entries.get(&resolved) {
             Some(Entry::File { contents }) => Ok(contents.clone()),
             Some(Entry::Dir { .. }) => must_be_file(path),
-            None => not_found(path),
+            _ => not_found(path),
         }
     }
 
     fn write(&mut self, path: &Path, data: &[u8]) -> io::Result<()> {
         let mut inner = self.inner.lock().unwrap();
+        let resolved = inner.resolve(path)?;
+        let existed = inner.entries.contains_key(&resolved);
 
         inner.load_snapshot(
-            path.to_path_buf(),
+            resolved,
             VfsSnapshot::File {
                 contents: data.to_owned(),
             },
-        )
+        )?;
+
+        if existed {
+            inner.notify_written(path);
+        } else {
+            inner.notify_created(path);
+        }
+
+        Ok(())
     }
 
     fn read_dir(&mut self, path: &Path) -> io::Result<ReadDir> {
         let inner = self.inner.lock().unwrap();
+        let resolved = inner.resolve(path)?;
 
-        match inner.entries.get(path) {
+        match inner.entries.get(&resolved) {
             Some(Entry::Dir { children }) => {
-                let iter = children
-                    .clone()
-                    .into_iter()
-                    .map(|path| Ok(DirEntry { path }));
+                // Children are stored by their canonical paths, but should be
+                // reported relative to the path the directory was read through.
+                let path = path.to_path_buf();
+                let iter = children.clone().into_iter().map(move |child| {
+                    Ok(DirEntry {
+                        path: path.join(child.file_name().unwrap()),
+                    })
+                });
 
                 Ok(ReadDir {
                     inner: Box::new(iter),
                 })
             }
             Some(Entry::File { .. }) => must_be_dir(path),
-            None => not_found(path),
+            _ => not_found(path),
         }
     }
 
     fn remove_file(&mut self, path: &Path) -> io::Result<()> {
         let mut inner = self.inner.lock().unwrap();
+        let link_path = inner.resolve_parent(path)?;
 
-        match inner.entries.get(path) {
-            Some(Entry::File { .. }) => {
-                inner.remove(path.to_owned());
+        match inner.entries.get(&link_path) {
+            Some(Entry::File { .. }) | Some(Entry::Symlink { .. }) => {
+                inner.remove(link_path);
+                inner.notify_removed(path);
                 Ok(())
             }
             Some(Entry::Dir { .. }) => must_be_file(path),
@@ -185,10 +305,12 @@ impl VfsBackend for InMemoryFs {
 
     fn remove_dir_all(&mut self, path: &Path) -> io::Result<()> {
         let mut inner = self.inner.lock().unwrap();
+        let link_path = inner.resolve_parent(path)?;
 
-        match inner.entries.get(path) {
-            Some(Entry::Dir { .. }) => {
-                inner.remove(path.to_owned());
+        match inner.entries.get(&link_path) {
+            Some(Entry::Dir { .. }) | Some(Entry::Symlink { .. }) => {
+                inner.remove(link_path);
+                inner.notify_removed(path);
                 Ok(())
             }
             Some(Entry::File { .. }) => must_be_dir(path),
@@ -198,11 +320,31 @@ impl VfsBackend for InMemoryFs {
 
     fn metadata(&mut self, path: &Path) -> io::Result<Metadata> {
         let inner = self.inner.lock().unwrap();
+        let link_path = inner.resolve_parent(path)?;
+        let is_symlink = matches!(inner.entries.get(&link_path), Some(Entry::Symlink { .. }));
+        let resolved = inner.resolve(path)?;
+
+        match inner.entries.get(&resolved) {
+            Some(Entry::File { .. }) => Ok(Metadata {
+                is_file: true,
+                is_symlink,
+            }),
+            Some(Entry::Dir { .. }) => Ok(Metadata {
+                is_file: false,
+                is_symlink,
+            }),
+            _ => not_found(path),
+        }
+    }
 
-        match inner.entries.get(path) {
-            Some(Entry::File { .. }) => Ok(Metadata { is_file: true }),
-            Some(Entry::Dir { .. }) => Ok(Metadata { is_file: false }),
-            None => not_found(path),
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let inner = self.inner.lock().unwrap();
+        let resolved = inner.resolve(path)?;
+
+        if inner.entries.contains_key(&resolved) {
+            Ok(resolved)
+        } else {
+            not_found(path)
         }
     }
 
@@ -212,11 +354,15 @@ impl VfsBackend for InMemoryFs {
         inner.event_receiver.clone()
     }
 
-    fn watch(&mut self, _path: &Path) -> io::Result<()> {
+    fn watch(&mut self, path: &Path) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.watched.insert(path.to_path_buf());
         Ok(())
     }
 
-    fn unwatch(&mut self, _path: &Path) -> io::Result<()> {
+    fn unwatch(&mut self, path: &Path) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.watched.remove(path);
         Ok(())
     }
 }
@@ -247,3 +393,45 @@ fn not_found<T>(path: &Path) -> io::Result<T> {
         format!("path {} not found", path.display()),
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::conformance;
+
+    fn fixture_fs() -> InMemoryFs {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/root", conformance::fixture()).unwrap();
+        imfs
+    }
+
+    #[test]
+    fn conformance_read() {
+        conformance::check_read(&mut fixture_fs(), Path::new("/root"));
+    }
+
+    #[test]
+    fn conformance_write() {
+        conformance::check_write(&mut fixture_fs(), Path::new("/root"));
+    }
+
+    #[test]
+    fn conformance_events() {
+        let mut imfs = fixture_fs();
+        let mut handle = imfs.clone();
+
+        conformance::check_events(&mut imfs, Path::new("/root"), |from, to| {
+            handle.rename(from, to)
+        });
+    }
+
+    #[test]
+    fn conformance_through_symlink() {
+        let mut imfs = fixture_fs();
+        imfs.load_snapshot("/link", VfsSnapshot::symlink("/root"))
+            .unwrap();
+
+        conformance::check_read(&mut imfs, Path::new("/link"));
+    }
+}
@@ -8,39 +8,58 @@ memofs is currently an unstable minimum viable library. Its primary consumer is
 ## Current Features
 * API similar to `std::fs`
 * Configurable backends
-    * `StdBackend`, which uses `std::fs` and the `notify` crate
+    * `StdBackend`, which uses `std::fs` and the `notify` crate, or polling
     * `NoopBackend`, which always throws errors
     * `InMemoryFs`, a simple in-memory filesystem useful for testing
+    * `GitBackend`, which reads a git commit without checking it out (requires
+      the `git` feature)
+    * `ArchiveBackend`, which reads a zip or tar archive (requires the
+      `archive` feature)
+    * `OverlayBackend`, which layers one backend on top of another
+    * Custom backends, by implementing the `VfsBackend` trait
+* A conformance test suite for backends, enabled with the `conformance` feature
 
 ## Future Features
 * Hash-based hierarchical memoization keys (hence the name)
 * Configurable caching (write-through, write-around, write-back)
 */
 
+#[cfg(any(test, feature = "conformance"))]
+pub mod conformance;
+
+#[cfg(feature = "archive")]
+mod archive_backend;
+#[cfg(feature = "git")]
+mod git_backend;
 mod in_memory_fs;
 mod noop_backend;
+mod overlay_backend;
+mod poll_watcher;
 mod snapshot;
 mod std_backend;
+mod symlink;
 
 use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, MutexGuard};
 
+#[cfg(feature = "archive")]
+pub use archive_backend::ArchiveBackend;
+#[cfg(feature = "git")]
+pub use git_backend::GitBackend;
 pub use in_memory_fs::InMemoryFs;
 pub use noop_backend::NoopBackend;
+pub use overlay_backend::OverlayBackend;
 pub use snapshot::VfsSnapshot;
-pub use std_backend::StdBackend;
-
-mod sealed {
-    use super::*;
-
-    /// Sealing trait for VfsBackend.
-    pub trait Sealed {}
+pub use std_backend::{StdBackend, DEFAULT_DEBOUNCE};
 
-    impl Sealed for NoopBackend {}
-    impl Sealed for StdBackend {}
-    impl Sealed for InMemoryFs {}
-}
+/// The `log` target that every filesystem event `StdBackend` receives is
+/// logged to at the trace level, both as reported by the watcher and after
+/// being translated into [`VfsEvent`](enum.VfsEvent.html)s.
+///
+/// Enabling it, like with `RUST_LOG=memofs::events=trace`, helps diagnose
+/// changes that never show up.
+pub const EVENT_LOG_TARGET: &str = "memofs::events";
 
 /// Trait that transforms `io::Result<T>` into `io::Result<Option<T>>`.
 ///
@@ -66,12 +85,50 @@ impl<T> IoResultExt<T> for io::Result<T> {
 
 /// Backend that can be used to create a `Vfs`.
 ///
-/// This trait is sealed and cannot not be implemented outside this crate.
-pub trait VfsBackend: sealed::Sealed + Send + 'static {
+/// Backends are responsible for all filesystem access and for reporting
+/// changes. `Vfs` holds its backend behind a lock, so methods are never called
+/// concurrently.
+///
+/// Errors for paths that don't exist should use `io::ErrorKind::NotFound`.
+/// Rojo treats those errors as "this file doesn't exist" rather than as a
+/// failure. Read-only backends should return an error from the mutating
+/// methods.
+///
+/// The [`conformance`](conformance/index.html) module, enabled by the
+/// `conformance` feature, contains tests that any backend can run to check
+/// that it follows these rules.
+///
+/// ## Events
+/// When watching is enabled, `Vfs` calls `watch` on every path it successfully
+/// reads with `read` or `read_dir`. From then on, the backend should send
+/// [`VfsEvent`](enum.VfsEvent.html)s through the receiver returned by
+/// `event_receiver`:
+///
+/// * `Create` when a file or directory appears as a direct child of a watched
+///   directory.
+/// * `Write` when the contents of a watched file change.
+/// * `Remove` when a watched path, or a direct child of a watched directory, is
+///   removed.
+/// * `Rescan` when changes under a watched path may have been missed, like
+///   when the platform's event queue overflows.
+/// * `Error` when watching fails. Backends should follow it with a `Rescan` of
+///   any paths that might have missed changes because of it.
+///
+/// Renames should be reported as a `Remove` of the old path followed by a
+/// `Create` of the new one. Paths in events must be in the same form they were
+/// passed to `watch`, even if the backend resolves them to something else
+/// internally. Events may be coalesced or spurious; consumers re-read the
+/// affected paths instead of trusting the event kind.
+///
+/// `unwatch` is called when a path is removed and may be called for paths that
+/// were never watched. Backends that never change can return `Ok(())` from
+/// `watch` and `unwatch` and a receiver from `crossbeam_channel::never`.
+pub trait VfsBackend: Send + 'static {
     fn read(&mut self, path: &Path) -> io::Result<Vec<u8>>;
     fn write(&mut self, path: &Path, data: &[u8]) -> io::Result<()>;
     fn read_dir(&mut self, path: &Path) -> io::Result<ReadDir>;
     fn metadata(&mut self, path: &Path) -> io::Result<Metadata>;
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf>;
     fn remove_file(&mut self, path: &Path) -> io::Result<()>;
     fn remove_dir_all(&mut self, path: &Path) -> io::Result<()>;
 
@@ -80,14 +137,63 @@ pub trait VfsBackend: sealed::Sealed + Send + 'static {
     fn unwatch(&mut self, path: &Path) -> io::Result<()>;
 }
 
+impl<B: VfsBackend + ?Sized> VfsBackend for Box<B> {
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn write(&mut self, path: &Path, data: &[u8]) -> io::Result<()> {
+        (**self).write(path, data)
+    }
+
+    fn read_dir(&mut self, path: &Path) -> io::Result<ReadDir> {
+        (**self).read_dir(path)
+    }
+
+    fn metadata(&mut self, path: &Path) -> io::Result<Metadata> {
+        (**self).metadata(path)
+    }
+
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+        (**self).canonicalize(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> io::Result<()> {
+        (**self).remove_file(path)
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        (**self).remove_dir_all(path)
+    }
+
+    fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent> {
+        (**self).event_receiver()
+    }
+
+    fn watch(&mut self, path: &Path) -> io::Result<()> {
+        (**self).watch(path)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> io::Result<()> {
+        (**self).unwatch(path)
+    }
+}
+
 /// Vfs equivalent to [`std::fs::DirEntry`][std::fs::DirEntry].
 ///
 /// [std::fs::DirEntry]: https://doc.rust-lang.org/stable/std/fs/struct.DirEntry.html
+#[derive(Debug)]
 pub struct DirEntry {
     pub(crate) path: PathBuf,
 }
 
 impl DirEntry {
+    /// Creates an entry for the given path, which should be the path of the
+    /// directory that was read joined with the entry's name.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
@@ -100,6 +206,19 @@ pub struct ReadDir {
     pub(crate) inner: Box<dyn Iterator<Item = io::Result<DirEntry>>>,
 }
 
+impl ReadDir {
+    /// Creates a `ReadDir` from any iterator of entries.
+    pub fn new<I>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = io::Result<DirEntry>>,
+        I::IntoIter: 'static,
+    {
+        Self {
+            inner: Box::new(entries.into_iter()),
+        }
+    }
+}
+
 impl Iterator for ReadDir {
     type Item = io::Result<DirEntry>;
 
@@ -110,13 +229,38 @@ impl Iterator for ReadDir {
 
 /// Vfs equivalent to [`std::fs::Metadata`][std::fs::Metadata].
 ///
+/// Like `std::fs::metadata`, symbolic links are followed. Whether the given
+/// path was itself a symbolic link is available via `is_symlink`.
+///
 /// [std::fs::Metadata]: https://doc.rust-lang.org/stable/std/fs/struct.Metadata.html
 #[derive(Debug)]
 pub struct Metadata {
     pub(crate) is_file: bool,
+    pub(crate) is_symlink: bool,
 }
 
 impl Metadata {
+    /// Creates metadata describing a file.
+    pub fn file() -> Self {
+        Self {
+            is_file: true,
+            is_symlink: false,
+        }
+    }
+
+    /// Creates metadata describing a directory.
+    pub fn dir() -> Self {
+        Self {
+            is_file: false,
+            is_symlink: false,
+        }
+    }
+
+    /// Marks whether the queried path was a symbolic link.
+    pub fn symlink(self, is_symlink: bool) -> Self {
+        Self { is_symlink, ..self }
+    }
+
     pub fn is_file(&self) -> bool {
         self.is_file
     }
@@ -124,16 +268,50 @@ impl Metadata {
     pub fn is_dir(&self) -> bool {
         !self.is_file
     }
+
+    /// Whether the path this metadata was queried for is a symbolic link. The
+    /// rest of the metadata describes the target of the link.
+    pub fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
 }
 
 /// Represents an event that a filesystem can raise that might need to be
 /// handled.
+///
+/// See [`VfsBackend`](trait.VfsBackend.html) for when backends should raise
+/// each kind of event.
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum VfsEvent {
     Create(PathBuf),
     Write(PathBuf),
     Remove(PathBuf),
+
+    /// Changes to the given path or anything inside of it may have been
+    /// missed, so everything read from there should be read again.
+    Rescan(PathBuf),
+
+    /// The backend ran into a problem while watching for changes, possibly at
+    /// a specific path.
+    Error {
+        path: Option<PathBuf>,
+        message: String,
+    },
+}
+
+/// Counts of the reads a [`Vfs`](struct.Vfs.html) has passed on to its
+/// backend, returned by `Vfs::stats`. Failed reads aren't counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VfsStats {
+    /// How many files have been read.
+    pub files_read: u64,
+
+    /// The total size of every file read, in bytes.
+    pub bytes_read: u64,
+
+    /// How many directories have been listed.
+    pub dirs_read: u64,
 }
 
 /// Contains implementation details of the Vfs, wrapped by `Vfs` and `VfsLock`,
@@ -141,6 +319,7 @@ pub enum VfsEvent {
 struct VfsInner {
     backend: Box<dyn VfsBackend>,
     watch_enabled: bool,
+    stats: VfsStats,
 }
 
 impl VfsInner {
@@ -148,6 +327,9 @@ impl VfsInner {
         let path = path.as_ref();
         let contents = self.backend.read(path)?;
 
+        self.stats.files_read += 1;
+        self.stats.bytes_read += contents.len() as u64;
+
         if self.watch_enabled {
             self.backend.watch(path)?;
         }
@@ -165,6 +347,8 @@ impl VfsInner {
         let path = path.as_ref();
         let dir = self.backend.read_dir(path)?;
 
+        self.stats.dirs_read += 1;
+
         if self.watch_enabled {
             self.backend.watch(path)?;
         }
@@ -189,6 +373,11 @@ impl VfsInner {
         self.backend.metadata(path)
     }
 
+    fn canonicalize<P: AsRef<Path>>(&mut self, path: P) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        self.backend.canonicalize(path)
+    }
+
     fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent> {
         self.backend.event_receiver()
     }
@@ -221,10 +410,14 @@ impl Vfs {
     }
 
     /// Creates a new `Vfs` with the given backend.
+    ///
+    /// Any [`VfsBackend`](trait.VfsBackend.html) can be used, including
+    /// backends chosen at runtime as a `Box<dyn VfsBackend>`.
     pub fn new<B: VfsBackend>(backend: B) -> Self {
         let lock = VfsInner {
             backend: Box::new(backend),
             watch_enabled: true,
+            stats: VfsStats::default(),
         };
 
         Self {
@@ -249,6 +442,11 @@ impl Vfs {
         inner.watch_enabled = enabled;
     }
 
+    /// Returns how many reads this `Vfs` has made from its backend so far.
+    pub fn stats(&self) -> VfsStats {
+        self.inner.lock().unwrap().stats
+    }
+
     /// Read a file from the VFS, or the underlying backend if it isn't
     /// resident.
     ///
@@ -317,6 +515,18 @@ impl Vfs {
         self.inner.lock().unwrap().metadata(path)
     }
 
+    /// Resolve all symbolic links in the given path, returning the canonical
+    /// path it refers to.
+    ///
+    /// Roughly equivalent to [`std::fs::canonicalize`][std::fs::canonicalize].
+    ///
+    /// [std::fs::canonicalize]: https://doc.rust-lang.org/stable/std/fs/fn.canonicalize.html
+    #[inline]
+    pub fn canonicalize<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        self.inner.lock().unwrap().canonicalize(path)
+    }
+
     /// Retrieve a handle to the event receiver for this `Vfs`.
     #[inline]
     pub fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent> {
@@ -419,6 +629,18 @@ impl VfsLock<'_> {
         self.inner.metadata(path)
     }
 
+    /// Resolve all symbolic links in the given path, returning the canonical
+    /// path it refers to.
+    ///
+    /// Roughly equivalent to [`std::fs::canonicalize`][std::fs::canonicalize].
+    ///
+    /// [std::fs::canonicalize]: https://doc.rust-lang.org/stable/std/fs/fn.canonicalize.html
+    #[inline]
+    pub fn canonicalize<P: AsRef<Path>>(&mut self, path: P) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        self.inner.canonicalize(path)
+    }
+
     /// Retrieve a handle to the event receiver for this `Vfs`.
     #[inline]
     pub fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent> {
@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crossbeam_channel::{select, Receiver};
+
+use crate::{DirEntry, IoResultExt, Metadata, ReadDir, VfsBackend, VfsEvent};
+
+/// `VfsBackend` that layers one backend on top of another, like an
+/// [`InMemoryFs`](struct.InMemoryFs.html) of generated files on top of a
+/// [`StdBackend`](struct.StdBackend.html).
+///
+/// Both layers share the same paths. Reads are served by the upper layer when
+/// it has the path and fall through to the lower layer otherwise, and directory
+/// listings contain the entries of both layers. Events from either layer are
+/// reported.
+///
+/// Writes go to the upper layer if it already has the path, and to the lower
+/// layer otherwise, so files that only exist on disk stay editable. Removals
+/// apply to every layer that has the path.
+pub struct OverlayBackend<U, L> {
+    upper: U,
+    lower: L,
+    event_receiver: Receiver<VfsEvent>,
+}
+
+impl<U: VfsBackend, L: VfsBackend> OverlayBackend<U, L> {
+    /// Creates a new `OverlayBackend` with `upper` shadowing `lower`.
+    pub fn new(upper: U, lower: L) -> Self {
+        let upper_events = upper.event_receiver();
+        let lower_events = lower.event_receiver();
+        let (tx, rx) = crossbeam_channel::unbounded();
+
+        thread::spawn(move || {
+            let mut upper_events = Some(upper_events);
+            let mut lower_events = Some(lower_events);
+            let never = crossbeam_channel::never();
+
+            // Layers stop reporting events by disconnecting their receivers,
+            // which are swapped out for `never` so that the other layer can
+            // keep going.
+            while upper_events.is_some() || lower_events.is_some() {
+                select! {
+                    recv(upper_events.as_ref().unwrap_or(&never)) -> event => match event {
+                        Ok(event) => tx.send(event)?,
+                        Err(_) => upper_events = None,
+                    },
+                    recv(lower_events.as_ref().unwrap_or(&never)) -> event => match event {
+                        Ok(event) => tx.send(event)?,
+                        Err(_) => lower_events = None,
+                    },
+                }
+            }
+
+            Result::<(), crossbeam_channel::SendError<VfsEvent>>::Ok(())
+        });
+
+        Self {
+            upper,
+            lower,
+            event_receiver: rx,
+        }
+    }
+
+    /// Tells whether the upper layer has anything at the given path.
+    fn upper_has(&mut self, path: &Path) -> io::Result<bool> {
+        Ok(self.upper.metadata(path).with_not_found()?.is_some())
+    }
+
+    fn lower_has(&mut self, path: &Path) -> io::Result<bool> {
+        Ok(self.lower.metadata(path).with_not_found()?.is_some())
+    }
+}
+
+impl<U: VfsBackend, L: VfsBackend> VfsBackend for OverlayBackend<U, L> {
+    fn read(&mut self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.upper.read(path).with_not_found()? {
+            Some(contents) => Ok(contents),
+            None => self.lower.read(path),
+        }
+    }
+
+    fn write(&mut self, path: &Path, data: &[u8]) -> io::Result<()> {
+        if self.upper_has(path)? {
+            self.upper.write(path, data)
+        } else {
+            self.lower.write(path, data)
+        }
+    }
+
+    fn read_dir(&mut self, path: &Path) -> io::Result<ReadDir> {
+        let upper = match self.upper.read_dir(path).with_not_found()? {
+            Some(upper) => upper,
+            None => return self.lower.read_dir(path),
+        };
+
+        let mut entries = BTreeMap::new();
+
+        // A directory in the upper layer shadows anything that isn't a
+        // directory in the lower layer.
+        let lower_is_dir = match self.lower.metadata(path).with_not_found()? {
+            Some(metadata) => metadata.is_dir(),
+            None => false,
+        };
+
+        if lower_is_dir {
+            for entry in self.lower.read_dir(path)? {
+                let path = entry?.path().to_path_buf();
+                entries.insert(path.file_name().map(|name| name.to_owned()), path);
+            }
+        }
+
+        for entry in upper {
+            let path = entry?.path().to_path_buf();
+            entries.insert(path.file_name().map(|name| name.to_owned()), path);
+        }
+
+        Ok(ReadDir::new(
+            entries.into_values().map(|path| Ok(DirEntry::new(path))),
+        ))
+    }
+
+    fn metadata(&mut self, path: &Path) -> io::Result<Metadata> {
+        match self.upper.metadata(path).with_not_found()? {
+            Some(metadata) => Ok(metadata),
+            None => self.lower.metadata(path),
+        }
+    }
+
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+        // Directories usually exist in both layers, in which case the lower
+        // layer knows where they really are.
+        if self.lower_has(path)? {
+            self.lower.canonicalize(path)
+        } else {
+            self.upper.canonicalize(path)
+        }
+    }
+
+    fn remove_file(&mut self, path: &Path) -> io::Result<()> {
+        let in_upper = self.upper_has(path)?;
+        let in_lower = self.lower_has(path)?;
+
+        if in_upper {
+            self.upper.remove_file(path)?;
+        }
+
+        if in_lower || !in_upper {
+            self.lower.remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove_dir_all(&mut self, path: &Path) -> io::Result<()> {
+        let in_upper = self.upper_has(path)?;
+        let in_lower = self.lower_has(path)?;
+
+        if in_upper {
+            self.upper.remove_dir_all(path)?;
+        }
+
+        if in_lower || !in_upper {
+            self.lower.remove_dir_all(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn event_receiver(&self) -> Receiver<VfsEvent> {
+        self.event_receiver.clone()
+    }
+
+    fn watch(&mut self, path: &Path) -> io::Result<()> {
+        if self.upper_has(path)? {
+            self.upper.watch(path)?;
+        }
+
+        // Paths that only exist in the upper layer can't be watched in the
+        // lower layer, since they aren't there.
+        if self.lower_has(path)? {
+            self.lower.watch(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn unwatch(&mut self, path: &Path) -> io::Result<()> {
+        let upper = self.upper.unwatch(path);
+        let lower = self.lower.unwatch(path);
+
+        upper.and(lower)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::{conformance, InMemoryFs, VfsSnapshot};
+
+    /// Splits the conformance fixture between two layers, with a file that
+    /// only exists in the lower layer shadowed by the upper one.
+    fn split_fixture() -> (InMemoryFs, InMemoryFs) {
+        let mut upper = InMemoryFs::new();
+        upper
+            .load_snapshot(
+                "/root",
+                VfsSnapshot::dir(vec![
+                    ("a.txt", VfsSnapshot::file("contents of a")),
+                    (
+                        "b",
+                        VfsSnapshot::dir(vec![(
+                            "d",
+                            VfsSnapshot::dir(vec![("e.txt", VfsSnapshot::file("contents of e"))]),
+                        )]),
+                    ),
+                ]),
+            )
+            .unwrap();
+
+        let mut lower = InMemoryFs::new();
+        lower
+            .load_snapshot(
+                "/root",
+                VfsSnapshot::dir(vec![
+                    ("a.txt", VfsSnapshot::file("shadowed")),
+                    (
+                        "b",
+                        VfsSnapshot::dir(vec![("c.txt", VfsSnapshot::file("contents of c"))]),
+                    ),
+                ]),
+            )
+            .unwrap();
+
+        (upper, lower)
+    }
+
+    #[test]
+    fn conformance_read() {
+        let (upper, lower) = split_fixture();
+
+        conformance::check_read(&mut OverlayBackend::new(upper, lower), Path::new("/root"));
+    }
+
+    #[test]
+    fn conformance_write() {
+        let (upper, lower) = split_fixture();
+
+        conformance::check_write(&mut OverlayBackend::new(upper, lower), Path::new("/root"));
+    }
+
+    #[test]
+    fn writes_go_to_owning_layer() {
+        let (upper, lower) = split_fixture();
+        let mut backend = OverlayBackend::new(upper.clone(), lower.clone());
+
+        backend.write(Path::new("/root/a.txt"), b"new a").unwrap();
+        backend.write(Path::new("/root/b/c.txt"), b"new c").unwrap();
+
+        let mut upper = upper;
+        let mut lower = lower;
+
+        assert_eq!(upper.read(Path::new("/root/a.txt")).unwrap(), b"new a");
+        assert_eq!(lower.read(Path::new("/root/a.txt")).unwrap(), b"shadowed");
+        assert_eq!(lower.read(Path::new("/root/b/c.txt")).unwrap(), b"new c");
+        assert!(upper.read(Path::new("/root/b/c.txt")).is_err());
+    }
+
+    #[test]
+    fn events_from_both_layers() {
+        let (mut upper, mut lower) = split_fixture();
+        let backend = OverlayBackend::new(upper.clone(), lower.clone());
+        let receiver = backend.event_receiver();
+
+        upper.raise_event(VfsEvent::Write(PathBuf::from("/root/a.txt")));
+        lower.raise_event(VfsEvent::Write(PathBuf::from("/root/b/c.txt")));
+
+        let mut paths = Vec::new();
+        for _ in 0..2 {
+            match receiver.recv_timeout(Duration::from_secs(5)).unwrap() {
+                VfsEvent::Write(path) => paths.push(path),
+                event => panic!("unexpected event {:?}", event),
+            }
+        }
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/root/a.txt"), PathBuf::from("/root/b/c.txt")]
+        );
+    }
+}
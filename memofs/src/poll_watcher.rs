@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crossbeam_channel::RecvTimeoutError;
+use notify::DebouncedEvent;
+
+/// Watches paths by scanning them at a fixed interval and comparing the
+/// modification time and size of every file, for filesystems that never
+/// deliver native change events, like some network drives and container
+/// volumes.
+///
+/// Watches behave like notify's non-recursive watches: watching a directory
+/// reports changes to its direct children. Changes are reported as
+/// `DebouncedEvent`s so that they can be handled exactly like notify's.
+pub(crate) struct PollWatcher {
+    watched: Arc<Mutex<HashMap<PathBuf, PathState>>>,
+
+    /// Dropped along with the watcher to stop the polling thread.
+    _stop: crossbeam_channel::Sender<()>,
+}
+
+impl PollWatcher {
+    pub fn new(tx: mpsc::Sender<DebouncedEvent>, interval: Duration) -> Self {
+        let watched = Arc::new(Mutex::new(HashMap::new()));
+        let thread_watched = Arc::clone(&watched);
+        let (stop_tx, stop_rx) = crossbeam_channel::bounded::<()>(0);
+
+        thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                for (path, change) in poll(&thread_watched) {
+                    let event = match change {
+                        Change::Create => DebouncedEvent::Create(path),
+                        Change::Write => DebouncedEvent::Write(path),
+                        Change::Remove => DebouncedEvent::Remove(path),
+                    };
+
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Self {
+            watched,
+            _stop: stop_tx,
+        }
+    }
+
+    pub fn watch(&mut self, path: &Path) -> io::Result<()> {
+        let state = PathState::scan(path);
+
+        if state == PathState::Missing {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("cannot watch missing path {}", path.display()),
+            ));
+        }
+
+        self.watched
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), state);
+
+        Ok(())
+    }
+
+    pub fn unwatch(&mut self, path: &Path) -> io::Result<()> {
+        self.watched.lock().unwrap().remove(path);
+        Ok(())
+    }
+}
+
+/// Rescans every watched path, returning the changes since the last scan
+/// without duplicates.
+fn poll(watched: &Mutex<HashMap<PathBuf, PathState>>) -> Vec<(PathBuf, Change)> {
+    let paths: Vec<PathBuf> = watched.lock().unwrap().keys().cloned().collect();
+    let mut changes = Vec::new();
+
+    for path in paths {
+        // Scanning can be slow, so it's done without holding the lock.
+        let new_state = PathState::scan(&path);
+
+        let mut watched = watched.lock().unwrap();
+        if let Some(old_state) = watched.get_mut(&path) {
+            old_state.diff(&new_state, &path, &mut changes);
+            *old_state = new_state;
+        }
+    }
+
+    changes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Create,
+    Write,
+    Remove,
+}
+
+fn push_change(changes: &mut Vec<(PathBuf, Change)>, path: PathBuf, change: Change) {
+    let change = (path, change);
+
+    if !changes.contains(&change) {
+        changes.push(change);
+    }
+}
+
+/// What a watched path looked like the last time it was scanned.
+#[derive(Debug, PartialEq)]
+enum PathState {
+    Missing,
+    File(Stamp),
+    Dir(BTreeMap<PathBuf, Stamp>),
+}
+
+/// The parts of a file's metadata that are compared to find changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    is_dir: bool,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl Stamp {
+    fn read(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+
+        Some(Self {
+            is_dir: metadata.is_dir(),
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+impl PathState {
+    fn scan(path: &Path) -> Self {
+        let stamp = match Stamp::read(path) {
+            Some(stamp) => stamp,
+            None => return PathState::Missing,
+        };
+
+        if !stamp.is_dir {
+            return PathState::File(stamp);
+        }
+
+        let mut children = BTreeMap::new();
+
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                let child = entry.path();
+
+                if let Some(stamp) = Stamp::read(&child) {
+                    children.insert(child, stamp);
+                }
+            }
+        }
+
+        PathState::Dir(children)
+    }
+
+    fn diff(&self, new: &PathState, path: &Path, changes: &mut Vec<(PathBuf, Change)>) {
+        match (self, new) {
+            (PathState::Missing, PathState::Missing) => {}
+            (PathState::Missing, _) => push_change(changes, path.to_path_buf(), Change::Create),
+            (_, PathState::Missing) => push_change(changes, path.to_path_buf(), Change::Remove),
+            (PathState::File(old), PathState::File(new)) => {
+                if old != new {
+                    push_change(changes, path.to_path_buf(), Change::Write);
+                }
+            }
+            (PathState::Dir(old), PathState::Dir(new)) => {
+                for (child, old_stamp) in old {
+                    match new.get(child) {
+                        None => push_change(changes, child.clone(), Change::Remove),
+                        Some(new_stamp) if new_stamp.is_dir != old_stamp.is_dir => {
+                            push_change(changes, child.clone(), Change::Remove);
+                            push_change(changes, child.clone(), Change::Create);
+                        }
+                        // A directory's own metadata changes whenever its
+                        // contents do, which is reported by watching it
+                        // instead.
+                        Some(new_stamp) if !new_stamp.is_dir && new_stamp != old_stamp => {
+                            push_change(changes, child.clone(), Change::Write);
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                for child in new.keys() {
+                    if !old.contains_key(child) {
+                        push_change(changes, child.clone(), Change::Create);
+                    }
+                }
+            }
+            // A file was replaced by a directory or the other way around.
+            _ => {
+                push_change(changes, path.to_path_buf(), Change::Remove);
+                push_change(changes, path.to_path_buf(), Change::Create);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn watched(paths: &[&Path]) -> Mutex<HashMap<PathBuf, PathState>> {
+        let watched = paths
+            .iter()
+            .map(|path| (path.to_path_buf(), PathState::scan(path)))
+            .collect();
+
+        Mutex::new(watched)
+    }
+
+    #[test]
+    fn create_write_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let file = root.join("a.txt");
+        fs_err::write(&file, "a").unwrap();
+
+        let watched = watched(&[root, &file]);
+        assert_eq!(poll(&watched), vec![]);
+
+        let created = root.join("b.txt");
+        fs_err::write(&created, "b").unwrap();
+        assert_eq!(poll(&watched), vec![(created.clone(), Change::Create)]);
+
+        // Changing the size is detected even if the modification time has too
+        // coarse a resolution to change.
+        fs_err::write(&file, "changed").unwrap();
+        assert_eq!(poll(&watched), vec![(file.clone(), Change::Write)]);
+
+        fs_err::remove_file(&file).unwrap();
+        assert_eq!(poll(&watched), vec![(file, Change::Remove)]);
+
+        fs_err::remove_file(&created).unwrap();
+        assert_eq!(poll(&watched), vec![(created, Change::Remove)]);
+    }
+
+    #[test]
+    fn nested_changes_not_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let nested = root.join("nested");
+        fs_err::create_dir(&nested).unwrap();
+
+        let watched = watched(&[root]);
+        fs_err::write(nested.join("a.txt"), "a").unwrap();
+
+        assert_eq!(poll(&watched), vec![]);
+    }
+
+    #[test]
+    fn events_sent() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = PollWatcher::new(tx, Duration::from_millis(10));
+        watcher.watch(root).unwrap();
+
+        let file = root.join("a.txt");
+        fs_err::write(&file, "a").unwrap();
+
+        match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            DebouncedEvent::Create(path) => assert_eq!(path, file),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn watch_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tx, _rx) = mpsc::channel();
+        let mut watcher = PollWatcher::new(tx, Duration::from_millis(10));
+
+        assert!(watcher.watch(&dir.path().join("missing")).is_err());
+    }
+}
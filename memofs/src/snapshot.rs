@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
 
 /// A slice of a tree of files. Can be loaded into an
 /// [`InMemoryFs`](struct.InMemoryFs.html).
@@ -13,6 +15,10 @@ pub enum VfsSnapshot {
     Dir {
         children: BTreeMap<String, VfsSnapshot>,
     },
+
+    Symlink {
+        target: PathBuf,
+    },
 }
 
 impl VfsSnapshot {
@@ -31,6 +37,14 @@ impl VfsSnapshot {
         }
     }
 
+    /// A symbolic link pointing to `target`. Relative targets are resolved
+    /// relative to the directory containing the link.
+    pub fn symlink<P: Into<PathBuf>>(target: P) -> Self {
+        Self::Symlink {
+            target: target.into(),
+        }
+    }
+
     pub fn empty_file() -> Self {
         Self::File {
             contents: Vec::new(),
@@ -42,4 +56,116 @@ impl VfsSnapshot {
             children: BTreeMap::new(),
         }
     }
+
+    /// Inserts `snapshot` into this directory snapshot at the given relative
+    /// path, creating any parent directories that don't exist yet.
+    ///
+    /// Inserting a directory where a directory already exists keeps the
+    /// existing one, along with its children. Returns an error if this
+    /// snapshot or any parent on the path is not a directory.
+    pub fn insert<P: AsRef<Path>>(&mut self, path: P, snapshot: VfsSnapshot) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut names = Vec::new();
+
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => {
+                    let name = name.to_str().ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("path {} is not valid UTF-8", path.display()),
+                        )
+                    })?;
+
+                    names.push(name.to_owned());
+                }
+                Component::CurDir => {}
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("path {} must be relative", path.display()),
+                    ))
+                }
+            }
+        }
+
+        let not_dir = || {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("a parent of {} is not a directory", path.display()),
+            )
+        };
+
+        let mut current = self;
+
+        for name in names.iter().take(names.len().saturating_sub(1)) {
+            current = match current {
+                VfsSnapshot::Dir { children } => children
+                    .entry(name.clone())
+                    .or_insert_with(VfsSnapshot::empty_dir),
+                _ => return Err(not_dir()),
+            };
+        }
+
+        let children = match current {
+            VfsSnapshot::Dir { children } => children,
+            _ => return Err(not_dir()),
+        };
+
+        let last = match names.last() {
+            Some(last) => last,
+            None => return Ok(()),
+        };
+
+        if let VfsSnapshot::Dir { .. } = snapshot {
+            if let Some(VfsSnapshot::Dir { .. }) = children.get(last) {
+                return Ok(());
+            }
+        }
+
+        children.insert(last.clone(), snapshot);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_creates_parents() {
+        let mut root = VfsSnapshot::empty_dir();
+        root.insert("a/b/c.txt", VfsSnapshot::file("c")).unwrap();
+
+        // Directories listed after their contents shouldn't replace them.
+        root.insert("a/b", VfsSnapshot::empty_dir()).unwrap();
+
+        match root {
+            VfsSnapshot::Dir { children } => match &children["a"] {
+                VfsSnapshot::Dir { children } => match &children["b"] {
+                    VfsSnapshot::Dir { children } => assert!(children.contains_key("c.txt")),
+                    _ => panic!("b should be a directory"),
+                },
+                _ => panic!("a should be a directory"),
+            },
+            _ => panic!("root should be a directory"),
+        }
+    }
+
+    #[test]
+    fn insert_rejects_escaping_paths() {
+        let mut root = VfsSnapshot::empty_dir();
+
+        assert!(root.insert("../a.txt", VfsSnapshot::file("a")).is_err());
+        assert!(root.insert("/a.txt", VfsSnapshot::file("a")).is_err());
+    }
+
+    #[test]
+    fn insert_into_file() {
+        let mut root = VfsSnapshot::empty_dir();
+        root.insert("a", VfsSnapshot::file("a")).unwrap();
+
+        assert!(root.insert("a/b.txt", VfsSnapshot::file("b")).is_err());
+    }
 }
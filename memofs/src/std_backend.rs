@@ -1,44 +1,100 @@
+use std::collections::{BTreeSet, HashMap};
 use std::io;
-use std::path::Path;
-use std::sync::mpsc;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
 use crossbeam_channel::Receiver;
 use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
 
-use crate::{DirEntry, Metadata, ReadDir, VfsBackend, VfsEvent};
+use crate::poll_watcher::PollWatcher;
+use crate::{DirEntry, Metadata, ReadDir, VfsBackend, VfsEvent, EVENT_LOG_TARGET};
 
 /// `VfsBackend` that uses `std::fs` and the `notify` crate.
+///
+/// Changes are found through the operating system's native file watching by
+/// default. Filesystems that don't support it, like some network drives and
+/// container volumes, can be watched by polling instead with
+/// [`new_polling`](#method.new_polling).
 pub struct StdBackend {
-    watcher: RecommendedWatcher,
+    watcher: StdWatcher,
     watcher_receiver: Receiver<VfsEvent>,
+    watched_paths: Arc<Mutex<WatchedPaths>>,
 }
 
+enum StdWatcher {
+    Native(RecommendedWatcher),
+    Poll(PollWatcher),
+}
+
+impl StdWatcher {
+    fn watch(&mut self, path: &Path) -> io::Result<()> {
+        match self {
+            StdWatcher::Native(watcher) => watcher
+                .watch(path, RecursiveMode::NonRecursive)
+                .map_err(|inner| io::Error::new(io::ErrorKind::Other, inner)),
+            StdWatcher::Poll(watcher) => watcher.watch(path),
+        }
+    }
+
+    fn unwatch(&mut self, path: &Path) -> io::Result<()> {
+        match self {
+            StdWatcher::Native(watcher) => watcher
+                .unwatch(path)
+                .map_err(|inner| io::Error::new(io::ErrorKind::Other, inner)),
+            StdWatcher::Poll(watcher) => watcher.unwatch(path),
+        }
+    }
+}
+
+/// How long the native watcher waits for changes to a file to settle before
+/// reporting them, unless told otherwise.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(50);
+
 impl StdBackend {
     pub fn new() -> StdBackend {
+        Self::new_with_debounce(DEFAULT_DEBOUNCE)
+    }
+
+    /// Creates a `StdBackend` whose native watcher waits for changes to a file
+    /// to settle for `debounce` before reporting them.
+    ///
+    /// Longer windows help with editors that save by writing a temporary file
+    /// and renaming it over the original, which otherwise shows up as the file
+    /// being removed and created again.
+    pub fn new_with_debounce(debounce: Duration) -> StdBackend {
+        let (notify_tx, notify_rx) = mpsc::channel();
+        let watcher = watcher(notify_tx, debounce).unwrap();
+
+        Self::with_watcher(StdWatcher::Native(watcher), notify_rx)
+    }
+
+    /// Creates a `StdBackend` that finds changes by scanning every watched
+    /// path once per `interval` and comparing the modification time and size
+    /// of files, instead of relying on native file watching.
+    pub fn new_polling(interval: Duration) -> StdBackend {
         let (notify_tx, notify_rx) = mpsc::channel();
-        let watcher = watcher(notify_tx, Duration::from_millis(50)).unwrap();
+        let watcher = PollWatcher::new(notify_tx, interval);
+
+        Self::with_watcher(StdWatcher::Poll(watcher), notify_rx)
+    }
 
+    fn with_watcher(watcher: StdWatcher, notify_rx: mpsc::Receiver<DebouncedEvent>) -> StdBackend {
         let (tx, rx) = crossbeam_channel::unbounded();
 
+        let watched_paths = Arc::new(Mutex::new(WatchedPaths::default()));
+        let thread_watched_paths = Arc::clone(&watched_paths);
+
         thread::spawn(move || {
             for event in notify_rx {
-                match event {
-                    DebouncedEvent::Create(path) => {
-                        tx.send(VfsEvent::Create(path))?;
-                    }
-                    DebouncedEvent::Write(path) => {
-                        tx.send(VfsEvent::Write(path))?;
-                    }
-                    DebouncedEvent::Remove(path) => {
-                        tx.send(VfsEvent::Remove(path))?;
-                    }
-                    DebouncedEvent::Rename(from, to) => {
-                        tx.send(VfsEvent::Remove(from))?;
-                        tx.send(VfsEvent::Create(to))?;
-                    }
-                    _ => {}
+                log::trace!(target: EVENT_LOG_TARGET, "Raw event: {:?}", event);
+
+                let translated = translate(event, &thread_watched_paths.lock().unwrap());
+
+                for event in translated {
+                    log::trace!(target: EVENT_LOG_TARGET, "Translated event: {:?}", event);
+                    tx.send(event)?;
                 }
             }
 
@@ -48,7 +104,58 @@ impl StdBackend {
         Self {
             watcher,
             watcher_receiver: rx,
+            watched_paths,
+        }
+    }
+}
+
+/// Turns an event from notify into the `VfsEvent`s it means for the paths
+/// that were asked to be watched.
+fn translate(event: DebouncedEvent, watched_paths: &WatchedPaths) -> Vec<VfsEvent> {
+    // Watches are registered on canonical paths, so events need to be
+    // translated back to every path that was watched through a symlink before
+    // anything else sees them.
+    let aliases = |path: &Path| watched_paths.aliases(path).into_iter();
+
+    match event {
+        DebouncedEvent::Create(path) => aliases(&path).map(VfsEvent::Create).collect(),
+        DebouncedEvent::Write(path) => aliases(&path).map(VfsEvent::Write).collect(),
+        DebouncedEvent::Remove(path) => aliases(&path).map(VfsEvent::Remove).collect(),
+        DebouncedEvent::Rename(from, to) => aliases(&from)
+            .map(VfsEvent::Remove)
+            .chain(aliases(&to).map(VfsEvent::Create))
+            .collect(),
+
+        // The platform dropped events, usually because its queue overflowed
+        // during a large change like a branch switch. Anything we're watching
+        // could be out of date.
+        DebouncedEvent::Rescan => watched_paths
+            .roots()
+            .into_iter()
+            .map(VfsEvent::Rescan)
+            .collect(),
+
+        // Events for a path that had an error might have been lost, so it's
+        // rescanned along with reporting the error.
+        DebouncedEvent::Error(err, path) => {
+            let paths: Vec<PathBuf> = path.iter().flat_map(|path| aliases(path)).collect();
+
+            let error = VfsEvent::Error {
+                path: paths.first().cloned(),
+                message: err.to_string(),
+            };
+
+            std::iter::once(error)
+                .chain(paths.into_iter().map(VfsEvent::Rescan))
+                .collect()
         }
+
+        // Notices are sent ahead of the debounced events they announce, and
+        // permission changes don't change what we read, so neither needs
+        // handling.
+        DebouncedEvent::NoticeWrite(_)
+        | DebouncedEvent::NoticeRemove(_)
+        | DebouncedEvent::Chmod(_) => Vec::new(),
     }
 }
 
@@ -85,26 +192,265 @@ impl VfsBackend for StdBackend {
     }
 
     fn metadata(&mut self, path: &Path) -> io::Result<Metadata> {
-        let inner = fs_err::metadata(path)?;
+        let link_meta = fs_err::symlink_metadata(path)?;
+        let is_symlink = link_meta.file_type().is_symlink();
+
+        let inner = if is_symlink {
+            fs_err::metadata(path)?
+        } else {
+            link_meta
+        };
 
         Ok(Metadata {
             is_file: inner.is_file(),
+            is_symlink,
         })
     }
 
+    fn canonicalize(&mut self, path: &Path) -> io::Result<PathBuf> {
+        fs_err::canonicalize(path)
+    }
+
     fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent> {
         self.watcher_receiver.clone()
     }
 
     fn watch(&mut self, path: &Path) -> io::Result<()> {
-        self.watcher
-            .watch(path, RecursiveMode::NonRecursive)
-            .map_err(|inner| io::Error::new(io::ErrorKind::Other, inner))
+        // Watching the same file through two different paths confuses most
+        // platform watchers, so we always watch the canonical path and keep
+        // track of which paths it was requested through.
+        let target = fs_err::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+        let mut watched_paths = self.watched_paths.lock().unwrap();
+
+        if let Some(old_target) = watched_paths.remove(path) {
+            if old_target != target && !watched_paths.is_target_watched(&old_target) {
+                let _ = self.watcher.unwatch(&old_target);
+            }
+        }
+
+        self.watcher.watch(&target)?;
+
+        watched_paths.insert(path.to_path_buf(), target);
+
+        Ok(())
     }
 
     fn unwatch(&mut self, path: &Path) -> io::Result<()> {
-        self.watcher
-            .unwatch(path)
-            .map_err(|inner| io::Error::new(io::ErrorKind::Other, inner))
+        let mut watched_paths = self.watched_paths.lock().unwrap();
+
+        let target = match watched_paths.remove(path) {
+            Some(target) => target,
+            None => path.to_path_buf(),
+        };
+
+        // Another path might still be watching the same target through a
+        // symlink, in which case the underlying watch needs to stay.
+        if watched_paths.is_target_watched(&target) {
+            return Ok(());
+        }
+
+        self.watcher.unwatch(&target)
+    }
+}
+
+/// Tracks the relationship between paths that were requested to be watched and
+/// the canonical paths that are actually being watched.
+#[derive(Debug, Default)]
+struct WatchedPaths {
+    /// Maps canonical paths to every path that was watched that resolves to
+    /// it.
+    by_target: HashMap<PathBuf, BTreeSet<PathBuf>>,
+
+    /// Maps watched paths to their canonical paths.
+    by_path: HashMap<PathBuf, PathBuf>,
+}
+
+impl WatchedPaths {
+    fn insert(&mut self, path: PathBuf, target: PathBuf) {
+        self.by_target
+            .entry(target.clone())
+            .or_default()
+            .insert(path.clone());
+        self.by_path.insert(path, target);
+    }
+
+    fn remove(&mut self, path: &Path) -> Option<PathBuf> {
+        let target = self.by_path.remove(path)?;
+
+        if let Some(paths) = self.by_target.get_mut(&target) {
+            paths.remove(path);
+
+            if paths.is_empty() {
+                self.by_target.remove(&target);
+            }
+        }
+
+        Some(target)
+    }
+
+    /// Every watched path that isn't inside of another watched path.
+    fn roots(&self) -> BTreeSet<PathBuf> {
+        self.by_path
+            .keys()
+            .filter(|path| {
+                !path
+                    .ancestors()
+                    .skip(1)
+                    .any(|ancestor| self.by_path.contains_key(ancestor))
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn is_target_watched(&self, target: &Path) -> bool {
+        self.by_target.contains_key(target)
+    }
+
+    /// Translates a path reported by the watcher into every watched path that
+    /// refers to the same file. Paths that aren't under any watch are passed
+    /// through as-is.
+    fn aliases(&self, path: &Path) -> BTreeSet<PathBuf> {
+        let mut aliases = BTreeSet::new();
+
+        for ancestor in path.ancestors() {
+            if let Some(watched) = self.by_target.get(ancestor) {
+                let suffix = path.strip_prefix(ancestor).unwrap();
+
+                for watched_path in watched {
+                    if suffix.as_os_str().is_empty() {
+                        aliases.insert(watched_path.clone());
+                    } else {
+                        aliases.insert(watched_path.join(suffix));
+                    }
+                }
+            }
+        }
+
+        if aliases.is_empty() {
+            aliases.insert(path.to_path_buf());
+        }
+
+        aliases
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::conformance;
+
+    #[test]
+    fn conformance_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        conformance::write_fixture_to_disk(&root).unwrap();
+
+        conformance::check_read(&mut StdBackend::new(), &root);
+    }
+
+    #[test]
+    fn conformance_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        conformance::write_fixture_to_disk(&root).unwrap();
+
+        conformance::check_write(&mut StdBackend::new(), &root);
+    }
+
+    #[test]
+    fn conformance_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        conformance::write_fixture_to_disk(&root).unwrap();
+
+        conformance::check_events(&mut StdBackend::new(), &root, |from, to| {
+            fs_err::rename(from, to)
+        });
+    }
+
+    fn watched_paths(paths: &[&str]) -> WatchedPaths {
+        let mut watched_paths = WatchedPaths::default();
+
+        for path in paths {
+            watched_paths.insert(PathBuf::from(path), PathBuf::from(path));
+        }
+
+        watched_paths
+    }
+
+    #[test]
+    fn rescan_watched_roots() {
+        let watched_paths = watched_paths(&["/a", "/a/b", "/a/b/c.txt", "/d"]);
+        let events = translate(DebouncedEvent::Rescan, &watched_paths);
+
+        let paths: Vec<_> = events
+            .into_iter()
+            .map(|event| match event {
+                VfsEvent::Rescan(path) => path,
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect();
+
+        assert_eq!(paths, vec![PathBuf::from("/a"), PathBuf::from("/d")]);
+    }
+
+    #[test]
+    fn error_rescans_path() {
+        let watched_paths = watched_paths(&["/a"]);
+        let error = notify::Error::Generic("oh no".to_owned());
+        let events = translate(
+            DebouncedEvent::Error(error, Some(PathBuf::from("/a/b.txt"))),
+            &watched_paths,
+        );
+
+        match events.as_slice() {
+            [VfsEvent::Error { path, message }, VfsEvent::Rescan(rescan)] => {
+                assert_eq!(path.as_deref(), Some(Path::new("/a/b.txt")));
+                assert_eq!(message, "oh no");
+                assert_eq!(rescan, Path::new("/a/b.txt"));
+            }
+            events => panic!("unexpected events {:?}", events),
+        }
+    }
+
+    #[test]
+    fn conformance_read_polling() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        conformance::write_fixture_to_disk(&root).unwrap();
+
+        let mut backend = StdBackend::new_polling(Duration::from_millis(10));
+        conformance::check_read(&mut backend, &root);
+    }
+
+    #[test]
+    fn conformance_events_polling() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        conformance::write_fixture_to_disk(&root).unwrap();
+
+        let mut backend = StdBackend::new_polling(Duration::from_millis(10));
+        conformance::check_events(&mut backend, &root, |from, to| fs_err::rename(from, to));
+    }
+
+    #[test]
+    fn polling_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        conformance::write_fixture_to_disk(&root).unwrap();
+
+        let mut backend = StdBackend::new_polling(Duration::from_millis(10));
+        let receiver = backend.event_receiver();
+        backend.watch(&root).unwrap();
+
+        let new = root.join("new.txt");
+        fs_err::write(&new, "new").unwrap();
+
+        match receiver.recv_timeout(Duration::from_secs(5)).unwrap() {
+            VfsEvent::Create(path) => assert_eq!(path, new),
+            event => panic!("unexpected event {:?}", event),
+        }
     }
 }
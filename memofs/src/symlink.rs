@@ -0,0 +1,77 @@
+//! Symbolic link resolution shared by backends that store their own links
+//! instead of relying on the operating system.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Maximum number of symbolic links that will be followed while resolving a
+/// single path, mirroring the limit most operating systems impose.
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+/// Resolves every symbolic link in the given path, including the last
+/// component.
+///
+/// `read_link` is called with each prefix of the path and should return the
+/// target of the link if that prefix is a symbolic link. Relative targets are
+/// resolved relative to the directory containing the link.
+pub(crate) fn resolve<F>(path: &Path, read_link: F) -> io::Result<PathBuf>
+where
+    F: Fn(&Path) -> Option<PathBuf>,
+{
+    resolve_with_depth(path, &read_link, 0)
+}
+
+/// Resolves every symbolic link in the given path except for the last
+/// component, which is useful for operating on links themselves.
+pub(crate) fn resolve_parent<F>(path: &Path, read_link: F) -> io::Result<PathBuf>
+where
+    F: Fn(&Path) -> Option<PathBuf>,
+{
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => Ok(resolve(parent, read_link)?.join(file_name)),
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+fn resolve_with_depth<F>(path: &Path, read_link: &F, depth: usize) -> io::Result<PathBuf>
+where
+    F: Fn(&Path) -> Option<PathBuf>,
+{
+    let mut resolved = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => {
+                resolved.push(other);
+
+                if let Some(target) = read_link(&resolved) {
+                    if depth >= MAX_SYMLINK_DEPTH {
+                        return too_many_links(path);
+                    }
+
+                    // Joining an absolute target replaces the whole path,
+                    // which is the behavior we want.
+                    let link_parent = resolved.parent().unwrap_or_else(|| Path::new(""));
+                    let target = link_parent.join(target);
+                    resolved = resolve_with_depth(&target, read_link, depth + 1)?;
+                }
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+fn too_many_links<T>(path: &Path) -> io::Result<T> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!(
+            "path {} has too many levels of symbolic links",
+            path.display()
+        ),
+    ))
+}
@@ -40,21 +40,27 @@ local function performRequest(requestParams)
 	end)
 end
 
-function Http.get(url)
+function Http.get(url, headers)
 	return performRequest({
 		Url = url,
 		Method = "GET",
+		Headers = headers,
 	})
 end
 
-function Http.post(url, body)
+function Http.post(url, body, headers)
 	return performRequest({
 		Url = url,
 		Method = "POST",
 		Body = body,
+		Headers = headers,
 	})
 end
 
+function Http.urlEncode(source)
+	return HttpService:UrlEncode(source)
+end
+
 function Http.jsonEncode(object)
 	return HttpService:JSONEncode(object)
 end
@@ -350,13 +350,8 @@ types = {
 	},
 
 	SharedString = {
-		fromPod = function(pod)
-			error("SharedString is not supported")
-		end,
-
-		toPod = function(roblox)
-			error("SharedString is not supported")
-		end,
+		fromPod = base64.decode,
+		toPod = base64.encode,
 	},
 
 	String = {
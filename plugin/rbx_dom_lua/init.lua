@@ -36,6 +36,16 @@ local function findCanonicalPropertyDescriptor(className, propertyName)
 	return nil
 end
 
+local function findDefaultValue(className, propertyName)
+	local class = database.Classes[className]
+
+	if class == nil then
+		return nil
+	end
+
+	return class.DefaultProperties[propertyName]
+end
+
 local function readProperty(instance, propertyName)
 	local descriptor = findCanonicalPropertyDescriptor(instance.ClassName, propertyName)
 
@@ -64,6 +74,7 @@ return {
 	readProperty = readProperty,
 	writeProperty = writeProperty,
 	findCanonicalPropertyDescriptor = findCanonicalPropertyDescriptor,
+	findDefaultValue = findDefaultValue,
 	Error = Error,
 	EncodedValue = require(script.EncodedValue),
 }
\ No newline at end of file
@@ -17,7 +17,38 @@ local function hangingPromise()
 	return Promise.new(function() end)
 end
 
+--[[
+	SharedString values are sent as the hash of their contents, and the
+	contents of each are sent once per response in its sharedStrings table.
+	This swaps each hash for its base64 contents, which is how RbxDom expects
+	SharedStrings to be encoded.
+]]
+local function resolveSharedStrings(properties, sharedStrings)
+	for _, value in pairs(properties) do
+		local hash = value.SharedString
+
+		if hash ~= nil then
+			local contents = sharedStrings and sharedStrings[hash]
+
+			if contents ~= nil then
+				value.SharedString = contents
+			else
+				Log.warn("Server sent SharedString {} without its contents", hash)
+			end
+		end
+	end
+end
+
 local function rejectFailedRequests(response)
+	if response.code == 401 then
+		local message = (
+			"The Rojo server requires a token, and the one given was missing or incorrect." ..
+			"\nEnter the token shown when 'rojo serve' started, then connect again."
+		)
+
+		return Promise.reject(message)
+	end
+
 	if response.code >= 400 then
 		local message = string.format("HTTP %s:\n%s", tostring(response.code), response.body)
 
@@ -81,14 +112,61 @@ local function rejectWrongPlaceId(infoResponseBody)
 	return Promise.resolve(infoResponseBody)
 end
 
+local function servesPlace(project, placeId)
+	if project.placeId == placeId then
+		return true
+	end
+
+	if project.expectedPlaceIds ~= nil then
+		for _, id in ipairs(project.expectedPlaceIds) do
+			if id == placeId then
+				return true
+			end
+		end
+	end
+
+	return false
+end
+
+--[[
+	A server can serve several projects, each with its own API under
+	/api/<project name>. This finds the one meant for this place by its placeId
+	or servePlaceIds. Returns nil if there's only one project or none of them
+	match, in which case the first project, served at /api, is used.
+]]
+local function chooseProject(infoResponseBody)
+	if infoResponseBody.projects == nil then
+		return nil
+	end
+
+	for _, project in ipairs(infoResponseBody.projects) do
+		if servesPlace(project, game.PlaceId) then
+			return project
+		end
+	end
+
+	return nil
+end
+
 local ApiContext = {}
 ApiContext.__index = ApiContext
 
-function ApiContext.new(baseUrl)
+function ApiContext.new(baseUrl, token)
 	assert(type(baseUrl) == "string")
+	assert(token == nil or type(token) == "string")
+
+	-- Servers started with --token require it on every API request.
+	local headers
+	if token ~= nil and token ~= "" then
+		headers = {
+			Authorization = "Bearer " .. token,
+		}
+	end
 
 	local self = {
+		__headers = headers,
 		__baseUrl = baseUrl,
+		__apiUrl = ("%s/api"):format(baseUrl),
 		__sessionId = nil,
 		__messageCursor = -1,
 		__connected = true,
@@ -103,6 +181,7 @@ function ApiContext:__fmtDebug(output)
 
 	output:writeLine("Connected: {}", self.__connected)
 	output:writeLine("Base URL: {}", self.__baseUrl)
+	output:writeLine("API URL: {}", self.__apiUrl)
 	output:writeLine("Session ID: {}", self.__sessionId)
 	output:writeLine("Message Cursor: {}", self.__messageCursor)
 
@@ -121,13 +200,28 @@ end
 function ApiContext:connect()
 	local url = ("%s/api/rojo"):format(self.__baseUrl)
 
-	return Http.get(url)
+	return Http.get(url, self.__headers)
 		:andThen(rejectFailedRequests)
 		:andThen(Http.Response.json)
 		:andThen(rejectWrongProtocolVersion)
 		:andThen(function(body)
 			assert(validateApiInfo(body))
 
+			local project = chooseProject(body)
+
+			if project ~= nil then
+				Log.info("Server serves several projects, syncing {}", project.projectName)
+
+				self.__apiUrl = ("%s/api/%s"):format(self.__baseUrl, Http.urlEncode(project.projectName))
+
+				body.sessionId = project.sessionId
+				body.projectName = project.projectName
+				body.expectedPlaceIds = project.expectedPlaceIds
+				body.gameId = project.gameId
+				body.placeId = project.placeId
+				body.rootInstanceId = project.rootInstanceId
+			end
+
 			return body
 		end)
 		:andThen(rejectWrongPlaceId)
@@ -139,9 +233,9 @@ function ApiContext:connect()
 end
 
 function ApiContext:read(ids)
-	local url = ("%s/api/read/%s"):format(self.__baseUrl, table.concat(ids, ","))
+	local url = ("%s/read/%s"):format(self.__apiUrl, table.concat(ids, ","))
 
-	return Http.get(url)
+	return Http.get(url, self.__headers)
 		:andThen(rejectFailedRequests)
 		:andThen(Http.Response.json)
 		:andThen(function(body)
@@ -151,12 +245,16 @@ function ApiContext:read(ids)
 
 			assert(validateApiRead(body))
 
+			for _, instance in pairs(body.instances) do
+				resolveSharedStrings(instance.Properties, body.sharedStrings)
+			end
+
 			return body
 		end)
 end
 
 function ApiContext:write(patch)
-	local url = ("%s/api/write"):format(self.__baseUrl)
+	local url = ("%s/write"):format(self.__apiUrl)
 
 	local updated = {}
 	for _, update in ipairs(patch.updated) do
@@ -165,8 +263,12 @@ function ApiContext:write(patch)
 			changedName = update.changedName,
 		}
 
-		if next(update.changedProperties) ~= nil then
-			fixedUpdate.changedProperties = update.changedProperties
+		if next(update.setProperties) ~= nil then
+			fixedUpdate.setProperties = update.setProperties
+		end
+
+		if update.removedProperties ~= nil and next(update.removedProperties) ~= nil then
+			fixedUpdate.removedProperties = update.removedProperties
 		end
 
 		table.insert(updated, fixedUpdate)
@@ -189,7 +291,7 @@ function ApiContext:write(patch)
 
 	body = Http.jsonEncode(body)
 
-	return Http.post(url, body)
+	return Http.post(url, body, self.__headers)
 		:andThen(rejectFailedRequests)
 		:andThen(Http.Response.json)
 		:andThen(function(body)
@@ -200,10 +302,10 @@ function ApiContext:write(patch)
 end
 
 function ApiContext:retrieveMessages()
-	local url = ("%s/api/subscribe/%s"):format(self.__baseUrl, self.__messageCursor)
+	local url = ("%s/subscribe/%s"):format(self.__apiUrl, self.__messageCursor)
 
 	local function sendRequest()
-		return Http.get(url)
+		return Http.get(url, self.__headers)
 			:catch(function(err)
 				if err.type == Http.Error.Kind.Timeout then
 					if self.__connected then
@@ -227,16 +329,45 @@ function ApiContext:retrieveMessages()
 
 			assert(validateApiSubscribe(body))
 
+			if body.sessionEnding then
+				return Promise.reject("The Rojo server was shut down.")
+			end
+
 			self:setMessageCursor(body.messageCursor)
 
+			for _, message in ipairs(body.messages) do
+				for _, instance in pairs(message.added) do
+					resolveSharedStrings(instance.Properties, body.sharedStrings)
+				end
+
+				for _, update in ipairs(message.updated) do
+					resolveSharedStrings(update.setProperties, body.sharedStrings)
+				end
+			end
+
 			return body.messages
 		end)
 end
 
+function ApiContext:getDiagnostics()
+	local url = ("%s/diagnostics"):format(self.__apiUrl)
+
+	return Http.get(url, self.__headers)
+		:andThen(rejectFailedRequests)
+		:andThen(Http.Response.json)
+		:andThen(function(body)
+			if body.sessionId ~= self.__sessionId then
+				return Promise.reject("Server changed ID")
+			end
+
+			return body.diagnostics
+		end)
+end
+
 function ApiContext:open(id)
-	local url = ("%s/api/open/%s"):format(self.__baseUrl, id)
+	local url = ("%s/open/%s"):format(self.__apiUrl, id)
 
-	return Http.post(url, "")
+	return Http.post(url, "", self.__headers)
 		:andThen(rejectFailedRequests)
 		:andThen(Http.Response.json)
 		:andThen(function(body)
@@ -78,11 +78,42 @@ local function AddressEntry(props)
 	end)
 end
 
+local function TokenEntry(props)
+	return Theme.with(function(theme)
+		return e(BorderedContainer, {
+			transparency = props.transparency,
+			size = UDim2.new(1, 0, 0, 36),
+			layoutOrder = props.layoutOrder,
+		}, {
+			Token = e("TextBox", {
+				Text = props.token or "",
+				Font = Enum.Font.Code,
+				TextSize = 18,
+				TextColor3 = theme.AddressEntry.TextColor,
+				TextXAlignment = Enum.TextXAlignment.Left,
+				TextTransparency = props.transparency,
+				PlaceholderText = "Token (optional)",
+				PlaceholderColor3 = theme.AddressEntry.PlaceholderColor,
+				ClearTextOnFocus = false,
+
+				Size = UDim2.new(1, -HOST_OFFSET, 1, 0),
+				Position = UDim2.new(0, HOST_OFFSET, 0, 0),
+
+				ClipsDescendants = true,
+				BackgroundTransparency = 1,
+
+				[Roact.Ref] = props.tokenRef,
+			}),
+		})
+	end)
+end
+
 local NotConnectedPage = Roact.Component:extend("NotConnectedPage")
 
 function NotConnectedPage:init()
 	self.hostRef = Roact.createRef()
 	self.portRef = Roact.createRef()
+	self.tokenRef = Roact.createRef()
 end
 
 function NotConnectedPage:render()
@@ -99,9 +130,16 @@ function NotConnectedPage:render()
 			layoutOrder = 2,
 		}),
 
+		TokenEntry = e(TokenEntry, {
+			token = self.props.token,
+			tokenRef = self.tokenRef,
+			transparency = self.props.transparency,
+			layoutOrder = 3,
+		}),
+
 		Buttons = e("Frame", {
 			Size = UDim2.new(1, 0, 0, 34),
-			LayoutOrder = 3,
+			LayoutOrder = 4,
 			BackgroundTransparency = 1,
 		}, {
 			Settings = e(TextButton, {
@@ -120,10 +158,12 @@ function NotConnectedPage:render()
 				onClick = function()
 					local hostText = self.hostRef.current.Text
 					local portText = self.portRef.current.Text
+					local tokenText = self.tokenRef.current.Text:match("^%s*(.-)%s*$")
 
 					self.props.onConnect(
 						#hostText > 0 and hostText or Config.defaultHost,
-						#portText > 0 and portText or Config.defaultPort
+						#portText > 0 and portText or Config.defaultPort,
+						tokenText
 					)
 				end,
 			}),
@@ -44,8 +44,14 @@ function App:init()
 end
 
 function App:startSession(host, port, sessionOptions)
-	local baseUrl = ("http://%s:%s"):format(host, port)
-	local apiContext = ApiContext.new(baseUrl)
+	-- Hosts can name a scheme, like https://, for servers that use TLS.
+	local baseUrl
+	if host:find("^https?://") then
+		baseUrl = ("%s:%s"):format(host, port)
+	else
+		baseUrl = ("http://%s:%s"):format(host, port)
+	end
+	local apiContext = ApiContext.new(baseUrl, sessionOptions.token)
 
 	local serveSession = ServeSession.new({
 		apiContext = apiContext,
@@ -138,8 +144,13 @@ function App:render()
 				}, {
 					NotConnectedPage = PluginSettings.with(function(settings)
 						return createPageElement(AppStatus.NotConnected, {
-							onConnect = function(host, port)
+							token = settings:get("serverToken"),
+
+							onConnect = function(host, port, token)
+								settings:set("serverToken", token)
+
 								self:startSession(host, port, {
+									token = token,
 									openScriptsExternally = settings:get("openScriptsExternally"),
 									twoWaySync = settings:get("twoWaySync"),
 								})
@@ -7,7 +7,7 @@ return strict("Config", {
 	codename = "Epiphany",
 	version = {7, 0, 0, "-alpha.4"},
 	expectedServerVersionString = "7.0 or newer",
-	protocolVersion = 4,
+	protocolVersion = 5,
 	defaultHost = "localhost",
 	defaultPort = 34872,
 })
\ No newline at end of file
@@ -161,10 +161,16 @@ function PatchSet.humanSummary(instanceMap, patchSet)
 			table.insert(updatedProperties, "ClassName")
 		end
 
-		for name in pairs(update.changedProperties) do
+		for name in pairs(update.setProperties) do
 			table.insert(updatedProperties, name)
 		end
 
+		if update.removedProperties ~= nil then
+			for _, name in ipairs(update.removedProperties) do
+				table.insert(updatedProperties, name)
+			end
+		end
+
 		local instance = instanceMap.fromIds[update.id]
 		local displayName
 		if instance ~= nil then
@@ -6,6 +6,7 @@
 ]]
 
 local Log = require(script.Parent.Parent.Parent.Log)
+local RbxDom = require(script.Parent.Parent.Parent.RbxDom)
 
 local PatchSet = require(script.Parent.Parent.PatchSet)
 local Types = require(script.Parent.Parent.Types)
@@ -15,6 +16,25 @@ local decodeValue = require(script.Parent.decodeValue)
 local reify = require(script.Parent.reify)
 local setProperty = require(script.Parent.setProperty)
 
+--[[
+	Instances can't be missing properties, so a property removed on the server
+	is put back to its default value instead.
+]]
+local function resetProperty(instanceMap, instance, propertyName)
+	local encodedDefault = RbxDom.findDefaultValue(instance.ClassName, propertyName)
+
+	if encodedDefault == nil then
+		return false
+	end
+
+	local ok, decodedValue = decodeValue(encodedDefault, instanceMap)
+	if not ok then
+		return false
+	end
+
+	return setProperty(instance, propertyName, decodedValue)
+end
+
 local function applyPatch(instanceMap, patch)
 	-- Tracks any portions of the patch that could not be applied to the DOM.
 	local unappliedPatch = PatchSet.newEmpty()
@@ -80,7 +100,8 @@ local function applyPatch(instanceMap, patch)
 		-- Track any part of this update that could not be applied.
 		local unappliedUpdate = {
 			id = update.id,
-			changedProperties = {},
+			setProperties = {},
+			removedProperties = {},
 		}
 		local partiallyApplied = false
 
@@ -104,7 +125,7 @@ local function applyPatch(instanceMap, patch)
 			-- While this may result in some issues, like not preserving the
 			-- "Archived" property, a robust solution is sufficiently
 			-- complicated that we're pushing it off for now.
-			local newProperties = update.changedProperties
+			local newProperties = update.setProperties
 
 			-- If the instance's ClassName changed, we'll kick into reify to
 			-- create this instance. We'll handle moving all of children between
@@ -172,18 +193,27 @@ local function applyPatch(instanceMap, patch)
 			partiallyApplied = true
 		end
 
-		if update.changedProperties ~= nil then
-			for propertyName, propertyValue in pairs(update.changedProperties) do
+		if update.setProperties ~= nil then
+			for propertyName, propertyValue in pairs(update.setProperties) do
 				local ok, decodedValue = decodeValue(propertyValue, instanceMap)
 				if not ok then
-					unappliedUpdate.changedProperties[propertyName] = propertyValue
+					unappliedUpdate.setProperties[propertyName] = propertyValue
 					partiallyApplied = true
 					continue
 				end
 
 				local ok = setProperty(instance, propertyName, decodedValue)
 				if not ok then
-					unappliedUpdate.changedProperties[propertyName] = propertyValue
+					unappliedUpdate.setProperties[propertyName] = propertyValue
+					partiallyApplied = true
+				end
+			end
+		end
+
+		if update.removedProperties ~= nil then
+			for _, propertyName in ipairs(update.removedProperties) do
+				if not resetProperty(instanceMap, instance, propertyName) then
+					table.insert(unappliedUpdate.removedProperties, propertyName)
 					partiallyApplied = true
 				end
 			end
@@ -144,7 +144,7 @@ return function()
 		local patch = PatchSet.newEmpty()
 		table.insert(patch.updated, {
 			id = "VALUE",
-			changedProperties = {
+			setProperties = {
 				Value = {
 					String = "WORLD",
 				},
@@ -156,6 +156,25 @@ return function()
 		expect(value.Value).to.equal("WORLD")
 	end)
 
+	it("should reset removed properties to their defaults", function()
+		local value = Instance.new("StringValue")
+		value.Value = "HELLO"
+
+		local instanceMap = InstanceMap.new()
+		instanceMap:insert("VALUE", value)
+
+		local patch = PatchSet.newEmpty()
+		table.insert(patch.updated, {
+			id = "VALUE",
+			setProperties = {},
+			removedProperties = {"Value"},
+		})
+
+		local unapplied = applyPatch(instanceMap, patch)
+		assert(PatchSet.isEmpty(unapplied), "expected remaining patch to be empty")
+		expect(value.Value).to.equal("")
+	end)
+
 	it("should recreate instances when changedClassName is set, preserving children", function()
 		local root = Instance.new("Folder")
 		root.Name = "Initial Root Name"
@@ -173,7 +192,7 @@ return function()
 			id = "ROOT",
 			changedName = "Updated Root Name",
 			changedClassName = "StringValue",
-			changedProperties = {
+			setProperties = {
 				Value = {
 					String = "I am Root",
 				},
@@ -62,7 +62,7 @@ local function diff(instanceMap, virtualInstances, rootId)
 			changedName = virtualInstance.Name
 		end
 
-		local changedProperties = {}
+		local setProperties = {}
 		for propertyName, virtualValue in pairs(virtualInstance.Properties) do
 			local ok, existingValueOrErr = getProperty(instance, propertyName)
 
@@ -72,7 +72,7 @@ local function diff(instanceMap, virtualInstances, rootId)
 
 				if ok then
 					if existingValue ~= decodedValue then
-						changedProperties[propertyName] = virtualValue
+						setProperties[propertyName] = virtualValue
 					end
 				else
 					Log.warn("Failed to decode property of type {}", virtualValue.Type)
@@ -90,12 +90,12 @@ local function diff(instanceMap, virtualInstances, rootId)
 			end
 		end
 
-		if changedName ~= nil or changedClassName ~= nil or not isEmpty(changedProperties) then
+		if changedName ~= nil or changedClassName ~= nil or not isEmpty(setProperties) then
 			table.insert(patch.updated, {
 				id = id,
 				changedName = changedName,
 				changedClassName = changedClassName,
-				changedProperties = changedProperties,
+				setProperties = setProperties,
 				changedMetadata = nil,
 			})
 		end
@@ -69,7 +69,7 @@ return function()
 		local update = patch.updated[1]
 		expect(update.id).to.equal("ROOT")
 		expect(update.changedName).to.equal("Some Name")
-		assert(isEmpty(update.changedProperties))
+		assert(isEmpty(update.setProperties))
 	end)
 
 	it("should generate a patch with a changed property", function()
@@ -102,9 +102,9 @@ return function()
 		local update = patch.updated[1]
 		expect(update.id).to.equal("ROOT")
 		expect(update.changedName).to.equal(nil)
-		expect(size(update.changedProperties)).to.equal(1)
+		expect(size(update.setProperties)).to.equal(1)
 
-		local patchProperty = update.changedProperties["Value"]
+		local patchProperty = update.setProperties["Value"]
 		expect(patchProperty).to.be.a("table")
 		local ty, value = next(patchProperty)
 		expect(ty).to.equal("String")
@@ -97,7 +97,7 @@ function reifyInner(instanceMap, virtualInstances, id, parentInstance, unapplied
 	if next(unappliedProperties) ~= nil then
 		table.insert(unappliedPatch.updated, {
 			id = id,
-			changedProperties = unappliedProperties,
+			setProperties = unappliedProperties,
 		})
 	end
 
@@ -120,7 +120,7 @@ function applyDeferredRefs(instanceMap, deferredRefs, unappliedPatch)
 		-- have many similar referent properties.
 		for _, existingUpdate in ipairs(unappliedPatch.updated) do
 			if existingUpdate.id == id then
-				existingUpdate.changedProperties[propertyName] = virtualValue
+				existingUpdate.setProperties[propertyName] = virtualValue
 				return
 			end
 		end
@@ -129,7 +129,7 @@ function applyDeferredRefs(instanceMap, deferredRefs, unappliedPatch)
 		-- into our unapplied patch.
 		table.insert(unappliedPatch.updated, {
 			id = id,
-			changedProperties = {
+			setProperties = {
 				[propertyName] = virtualValue,
 			},
 		})
@@ -167,9 +167,9 @@ return function()
 
 		local update = unappliedPatch.updated[1]
 		expect(update.id).to.equal("ROOT")
-		expect(size(update.changedProperties)).to.equal(1)
+		expect(size(update.setProperties)).to.equal(1)
 
-		local property = update.changedProperties["Value"]
+		local property = update.setProperties["Value"]
 		expect(property).to.equal(virtualInstances["ROOT"].Properties.Value)
 	end)
 
@@ -346,6 +346,6 @@ return function()
 
 		local update = unappliedPatch.updated[1]
 		expect(update.id).to.equal("ROOT")
-		expect(update.changedProperties.Value).to.equal(virtualInstances["ROOT"].Properties.Value)
+		expect(update.setProperties.Value).to.equal(virtualInstances["ROOT"].Properties.Value)
 	end)
 end
\ No newline at end of file
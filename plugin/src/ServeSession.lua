@@ -114,6 +114,11 @@ function ServeSession:start()
 			self:__setStatus(Status.Connected, serverInfo.projectName)
 			self:__applyGameAndPlaceId(serverInfo)
 
+			if serverInfo.readOnly and self.__twoWaySync then
+				Log.info("Two-way sync is disabled because the Rojo server is read-only.")
+				self.__twoWaySync = false
+			end
+
 			local rootInstanceId = serverInfo.rootInstanceId
 
 			return self:__initialSync(rootInstanceId)
@@ -195,7 +200,7 @@ function ServeSession:__onInstanceChanged(instance, propertyName)
 
 	local update = {
 		id = instanceId,
-		changedProperties = {},
+		setProperties = {},
 	}
 
 	if propertyName == "Name" then
@@ -216,7 +221,7 @@ function ServeSession:__onInstanceChanged(instance, propertyName)
 			return
 		end
 
-		update.changedProperties[propertyName] = encoded
+		update.setProperties[propertyName] = encoded
 	end
 
 	local patch = {
@@ -265,6 +270,13 @@ function ServeSession:__initialSync(rootInstanceId)
 				Log.warn("Could not apply all changes requested by the Rojo server:\n{}",
 					PatchSet.humanSummary(self.__instanceMap, unappliedPatch))
 			end
+
+			return self.__apiContext:getDiagnostics()
+		end)
+		:andThen(function(diagnostics)
+			for _, diagnostic in ipairs(diagnostics) do
+				Log.warn("{}: {}", diagnostic.path, diagnostic.message)
+			end
 		end)
 end
 
@@ -278,6 +290,12 @@ function ServeSession:__mainSyncLoop()
 					Log.warn("Could not apply all changes requested by the Rojo server:\n{}",
 						PatchSet.humanSummary(self.__instanceMap, unappliedPatch))
 				end
+
+				if message.reportedDiagnostics ~= nil then
+					for _, diagnostic in ipairs(message.reportedDiagnostics) do
+						Log.warn("{}: {}", diagnostic.path, diagnostic.message)
+					end
+				end
 			end
 
 			if self.__status ~= Status.Disconnected then
@@ -28,14 +28,31 @@ local ApiInstanceUpdate = t.interface({
 	id = RbxId,
 	changedName = t.optional(t.string),
 	changedClassName = t.optional(t.string),
-	changedProperties = t.map(t.string, ApiValue),
+	setProperties = t.map(t.string, ApiValue),
+	removedProperties = t.optional(t.array(t.string)),
 	changedMetadata = t.optional(ApiInstanceMetadata),
 })
 
+local ApiDiagnostic = t.interface({
+	path = t.string,
+	message = t.string,
+})
+
 local ApiSubscribeMessage = t.interface({
 	removed = t.array(RbxId),
 	added = t.map(RbxId, ApiInstance),
 	updated = t.array(ApiInstanceUpdate),
+	reportedDiagnostics = t.optional(t.array(ApiDiagnostic)),
+	clearedDiagnostics = t.optional(t.array(t.string)),
+})
+
+local ApiProjectInfo = t.interface({
+	sessionId = t.string,
+	projectName = t.string,
+	expectedPlaceIds = t.optional(t.array(t.number)),
+	gameId = t.optional(t.number),
+	placeId = t.optional(t.number),
+	rootInstanceId = RbxId,
 })
 
 local ApiInfoResponse = t.interface({
@@ -43,19 +60,24 @@ local ApiInfoResponse = t.interface({
 	serverVersion = t.string,
 	protocolVersion = t.number,
 	expectedPlaceIds = t.optional(t.array(t.number)),
+	readOnly = t.optional(t.boolean),
 	rootInstanceId = RbxId,
+	projects = t.optional(t.array(ApiProjectInfo)),
 })
 
 local ApiReadResponse = t.interface({
 	sessionId = t.string,
 	messageCursor = t.number,
 	instances = t.map(RbxId, ApiInstance),
+	sharedStrings = t.optional(t.map(t.string, t.string)),
 })
 
 local ApiSubscribeResponse = t.interface({
 	sessionId = t.string,
 	messageCursor = t.number,
 	messages = t.array(ApiSubscribeMessage),
+	sharedStrings = t.optional(t.map(t.string, t.string)),
+	sessionEnding = t.optional(t.boolean),
 })
 
 local ApiError = t.interface({
@@ -81,10 +103,13 @@ return strict("Types", {
 	ifEnabled = ifEnabled,
 
 	ApiInfoResponse = ApiInfoResponse,
+	ApiProjectInfo = ApiProjectInfo,
 	ApiReadResponse = ApiReadResponse,
 	ApiSubscribeResponse = ApiSubscribeResponse,
 	ApiError = ApiError,
 
+	ApiDiagnostic = ApiDiagnostic,
+
 	ApiInstance = ApiInstance,
 	ApiInstanceUpdate = ApiInstanceUpdate,
 	ApiInstanceMetadata = ApiInstanceMetadata,
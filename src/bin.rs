@@ -51,18 +51,27 @@ fn main() {
 
     let options = Options::from_args();
 
-    let log_filter = match options.global.verbosity {
+    let mut log_filter = match options.global.verbosity {
         0 => "info",
         1 => "info,librojo=debug",
         2 => "info,librojo=trace",
         _ => "trace",
+    }
+    .to_owned();
+
+    // Event traces are only useful if we can tell when each event happened.
+    let timestamp = if options.global.trace_events {
+        log_filter.push_str(",memofs::events=trace,librojo::change_processor=trace");
+        Some(env_logger::fmt::TimestampPrecision::Millis)
+    } else {
+        None
     };
 
     let log_env = env_logger::Env::default().default_filter_or(log_filter);
 
     env_logger::Builder::from_env(log_env)
         .format_module_path(false)
-        .format_timestamp(None)
+        .format_timestamp(timestamp)
         // Indent following lines equal to the log level label, like `[ERROR] `
         .format_indent(Some(8))
         .write_style(options.global.color.into())
@@ -1,6 +1,10 @@
 use std::{
     fs,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
 use crossbeam_channel::{select, Receiver, RecvError, Sender};
@@ -9,13 +13,24 @@ use memofs::{IoResultExt, Vfs, VfsEvent};
 use rbx_dom_weak::types::{Ref, Variant};
 
 use crate::{
+    diagnostics::Diagnostics,
     message_queue::MessageQueue,
+    metrics::{Metrics, Phase},
+    serve_session::ServeSessionError,
     snapshot::{
-        apply_patch_set, compute_patch_set, AppliedPatchSet, InstigatingSource, PatchSet, RojoTree,
+        apply_patch_set, compute_patch_set, AppliedPatchSet, InstanceContext, InstanceSnapshot,
+        InstigatingSource, PatchCause, PatchSet, RojoTree,
     },
     snapshot_middleware::{snapshot_from_vfs, snapshot_project_node},
 };
 
+/// How many of the most recent watcher errors are kept to show to users.
+const MAX_WATCHER_ERRORS: usize = 20;
+
+/// What the ChangeProcessor sends back after being asked to resync: the
+/// changes it made to the tree, or why it couldn't snapshot the project.
+pub type ResyncResult = Result<AppliedPatchSet, ServeSessionError>;
+
 /// Processes file change events, updates the DOM, and sends those updates
 /// through a channel for other stuff to consume.
 ///
@@ -46,18 +61,33 @@ pub struct ChangeProcessor {
 impl ChangeProcessor {
     /// Spin up the ChangeProcessor, connecting it to the given tree, VFS, and
     /// outbound message queue.
+    ///
+    /// Each request received on `resync_receiver` rebuilds the tree from
+    /// `start_path`, and the result is sent back through the request.
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
         tree: Arc<Mutex<RojoTree>>,
         vfs: Arc<Vfs>,
+        start_path: PathBuf,
         message_queue: Arc<MessageQueue<AppliedPatchSet>>,
         tree_mutation_receiver: Receiver<PatchSet>,
+        resync_receiver: Receiver<Sender<ResyncResult>>,
+        watcher_errors: Arc<Mutex<Vec<String>>>,
+        diagnostics: Arc<Mutex<Diagnostics>>,
+        read_only: Arc<AtomicBool>,
+        metrics: Arc<Metrics>,
     ) -> Self {
         let (shutdown_sender, shutdown_receiver) = crossbeam_channel::bounded(1);
         let vfs_receiver = vfs.event_receiver();
         let task = JobThreadContext {
             tree,
             vfs,
+            start_path,
             message_queue,
+            watcher_errors,
+            diagnostics,
+            read_only,
+            metrics,
         };
 
         let job_thread = jod_thread::Builder::new()
@@ -73,8 +103,18 @@ impl ChangeProcessor {
                         recv(tree_mutation_receiver) -> patch_set => {
                             task.handle_tree_event(patch_set?);
                         },
+                        recv(resync_receiver) -> responder => {
+                            task.handle_resync(responder?);
+                        },
                         recv(shutdown_receiver) -> _ => {
                             log::trace!("ChangeProcessor shutdown signal received...");
+
+                            // Clients were told their changes were accepted, so
+                            // write any that are still queued before stopping.
+                            for patch_set in tree_mutation_receiver.try_iter() {
+                                task.handle_tree_event(patch_set);
+                            }
+
                             return Ok(());
                         },
                     }
@@ -108,28 +148,63 @@ struct JobThreadContext {
     /// A handle to the VFS we're managing.
     vfs: Arc<Vfs>,
 
+    /// The path the session was started from, which the whole tree is
+    /// snapshotted from when resyncing.
+    start_path: PathBuf,
+
     /// Whenever changes are applied to the DOM, we should push those changes
     /// into this message queue to inform any connected clients.
     message_queue: Arc<MessageQueue<AppliedPatchSet>>,
+
+    /// The most recent errors reported by the VFS while watching for changes,
+    /// oldest first.
+    watcher_errors: Arc<Mutex<Vec<String>>>,
+
+    /// Problems with files that kept the tree from being updated. Cleared for
+    /// a path once it's snapshotted successfully again.
+    diagnostics: Arc<Mutex<Diagnostics>>,
+
+    /// When set, patches from clients are dropped instead of being applied.
+    read_only: Arc<AtomicBool>,
+
+    /// Where the time spent on each change is recorded.
+    metrics: Arc<Metrics>,
 }
 
 impl JobThreadContext {
     fn handle_vfs_event(&self, event: VfsEvent) {
         log::trace!("Vfs event: {:?}", event);
 
+        if let VfsEvent::Rescan(path) = &event {
+            log::warn!(
+                "Changes to {} may have been missed, reading it again",
+                path.display()
+            );
+        }
+
         // Update the VFS immediately with the event.
         self.vfs
             .commit_event(&event)
             .expect("Error applying VFS change");
 
+        if let VfsEvent::Error { path, message } = event {
+            self.handle_watcher_error(path, message);
+            return;
+        }
+
         // For a given VFS event, we might have many changes to different parts
         // of the tree. Calculate and apply all of these changes.
         let applied_patches = {
             let mut tree = self.tree.lock().unwrap();
+            let mut diagnostics = self.diagnostics.lock().unwrap();
             let mut applied_patches = Vec::new();
+            let cause = patch_cause(&event);
 
             match event {
-                VfsEvent::Create(path) | VfsEvent::Write(path) | VfsEvent::Remove(path) => {
+                VfsEvent::Create(path)
+                | VfsEvent::Write(path)
+                | VfsEvent::Remove(path)
+                | VfsEvent::Rescan(path) => {
                     // Find the nearest ancestor to this path that has
                     // associated instances in the tree. This helps make sure
                     // that we handle additions correctly, especially if we
@@ -153,7 +228,14 @@ impl JobThreadContext {
                     };
 
                     for id in affected_ids {
-                        if let Some(patch) = compute_and_apply_changes(&mut tree, &self.vfs, id) {
+                        if let Some(mut patch) = compute_and_apply_changes(
+                            &mut tree,
+                            &self.vfs,
+                            &mut diagnostics,
+                            &self.metrics,
+                            id,
+                        ) {
+                            patch.cause = cause.clone();
                             applied_patches.push(patch);
                         }
                     }
@@ -169,7 +251,87 @@ impl JobThreadContext {
         self.message_queue.push_messages(&applied_patches);
     }
 
+    fn handle_watcher_error(&self, path: Option<PathBuf>, message: String) {
+        let message = match path {
+            Some(path) => format!("{}: {}", path.display(), message),
+            None => message,
+        };
+
+        log::error!("Error watching for file changes: {}", message);
+
+        {
+            let mut watcher_errors = self.watcher_errors.lock().unwrap();
+            watcher_errors.push(message);
+
+            let excess = watcher_errors.len().saturating_sub(MAX_WATCHER_ERRORS);
+            watcher_errors.drain(..excess);
+        }
+
+        // Nothing in the tree changed, so there's no message to push, but
+        // clients only check for errors when they hear from the message queue.
+        self.message_queue.wake_subscribers();
+    }
+
+    /// Rebuilds the whole tree from the VFS. This happens on the processor's
+    /// thread like every other change, so that messages are always pushed in
+    /// the order their changes were applied to the tree.
+    fn handle_resync(&self, responder: Sender<ResyncResult>) {
+        let result = self.resync();
+
+        if let Ok(applied_patch_set) = &result {
+            if !applied_patch_set.is_empty() {
+                self.message_queue
+                    .push_messages(std::slice::from_ref(applied_patch_set));
+            }
+        }
+
+        // The session may have stopped waiting, which is fine.
+        let _ = responder.send(result);
+    }
+
+    fn resync(&self) -> ResyncResult {
+        log::info!("Resyncing project from {}", self.start_path.display());
+
+        let instance_context = InstanceContext::default();
+        let snapshot = self
+            .metrics
+            .time(Phase::Snapshot, || {
+                snapshot_from_vfs(&instance_context, &self.vfs, &self.start_path)
+            })?
+            .ok_or_else(|| ServeSessionError::EmptySnapshot {
+                path: self.start_path.clone(),
+            })?;
+
+        let mut tree = self.tree.lock().unwrap();
+        let root_id = tree.get_root_id();
+
+        let patch_set = self.metrics.time(Phase::ComputePatch, || {
+            compute_patch_set(&snapshot, &tree, root_id)
+        });
+        let mut applied_patch_set = self
+            .metrics
+            .time(Phase::ApplyPatch, || apply_patch_set(&mut tree, patch_set));
+
+        self.watcher_errors.lock().unwrap().clear();
+
+        let (reported, cleared) = self
+            .diagnostics
+            .lock()
+            .unwrap()
+            .replace_all(snapshot.all_diagnostics());
+        applied_patch_set.reported_diagnostics = reported;
+        applied_patch_set.cleared_diagnostics = cleared;
+        applied_patch_set.cause = Some(PatchCause::Resync);
+
+        Ok(applied_patch_set)
+    }
+
     fn handle_tree_event(&self, patch_set: PatchSet) {
+        if self.read_only.load(Ordering::SeqCst) {
+            log::warn!("Refusing changes from a client because the session is read-only");
+            return;
+        }
+
         log::trace!("Applying PatchSet from client: {:#?}", patch_set);
 
         let applied_patch = {
@@ -250,14 +412,37 @@ impl JobThreadContext {
                 }
             }
 
-            apply_patch_set(&mut tree, patch_set)
+            let mut applied_patch = self
+                .metrics
+                .time(Phase::ApplyPatch, || apply_patch_set(&mut tree, patch_set));
+            applied_patch.cause = Some(PatchCause::ClientWrite);
+            applied_patch
         };
 
         self.message_queue.push_messages(&[applied_patch]);
     }
 }
 
-fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: Ref) -> Option<AppliedPatchSet> {
+/// Describes a VFS event for the history of a session.
+fn patch_cause(event: &VfsEvent) -> Option<PatchCause> {
+    let cause = match event {
+        VfsEvent::Create(path) => PatchCause::FileCreated { path: path.clone() },
+        VfsEvent::Write(path) => PatchCause::FileChanged { path: path.clone() },
+        VfsEvent::Remove(path) => PatchCause::FileRemoved { path: path.clone() },
+        VfsEvent::Rescan(path) => PatchCause::FileRescanned { path: path.clone() },
+        _ => return None,
+    };
+
+    Some(cause)
+}
+
+fn compute_and_apply_changes(
+    tree: &mut RojoTree,
+    vfs: &Vfs,
+    diagnostics: &mut Diagnostics,
+    metrics: &Metrics,
+    id: Ref,
+) -> Option<AppliedPatchSet> {
     let metadata = tree
         .get_metadata(id)
         .expect("metadata missing for instance present in tree");
@@ -274,16 +459,33 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: Ref) -> Option<
         }
     };
 
+    // Problems snapshotting this instance are reported for the file that
+    // created it. Project nodes are snapshotted from their `$path`, so that's
+    // where any problem with them will be.
+    let source_path = match instigating_source {
+        InstigatingSource::Path(path) => path.clone(),
+        InstigatingSource::ProjectNode(project_path, _, project_node, _) => {
+            match &project_node.path {
+                Some(node_path) => project_path.parent().unwrap().join(node_path),
+                None => project_path.clone(),
+            }
+        }
+    };
+
     // How we process a file change event depends on what created this
     // file/folder in the first place.
-    let applied_patch_set = match instigating_source {
+    let (mut applied_patch_set, snapshot) = match instigating_source {
         InstigatingSource::Path(path) => match vfs.metadata(path).with_not_found() {
             Ok(Some(_)) => {
                 // Our instance was previously created from a path and that
                 // path still exists. We can generate a snapshot starting at
                 // that path and use it as the source for our patch.
 
-                let snapshot = match snapshot_from_vfs(&metadata.context, &vfs, &path) {
+                let snapshot_result = metrics.time(Phase::Snapshot, || {
+                    snapshot_from_vfs(&metadata.context, &vfs, &path)
+                });
+
+                let snapshot = match snapshot_result {
                     Ok(Some(snapshot)) => snapshot,
                     Ok(None) => {
                         log::error!(
@@ -295,12 +497,17 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: Ref) -> Option<
                     }
                     Err(err) => {
                         log::error!("Snapshot error: {:?}", err);
-                        return None;
+                        return report_diagnostic(diagnostics, &source_path, format!("{:#}", err));
                     }
                 };
 
-                let patch_set = compute_patch_set(&snapshot, &tree, id);
-                apply_patch_set(tree, patch_set)
+                let patch_set = metrics.time(Phase::ComputePatch, || {
+                    compute_patch_set(&snapshot, &tree, id)
+                });
+                let applied_patch_set =
+                    metrics.time(Phase::ApplyPatch, || apply_patch_set(tree, patch_set));
+
+                (applied_patch_set, Some(snapshot))
             }
             Ok(None) => {
                 // Our instance was previously created from a path, but that
@@ -312,11 +519,14 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: Ref) -> Option<
                 let mut patch_set = PatchSet::new();
                 patch_set.removed_instances.push(id);
 
-                apply_patch_set(tree, patch_set)
+                let applied_patch_set =
+                    metrics.time(Phase::ApplyPatch, || apply_patch_set(tree, patch_set));
+
+                (applied_patch_set, None)
             }
             Err(err) => {
                 log::error!("Error processing filesystem change: {:?}", err);
-                return None;
+                return report_diagnostic(diagnostics, &source_path, err.to_string());
             }
         },
 
@@ -325,14 +535,16 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: Ref) -> Option<
             // there might be information associated with our instance from
             // the project file, we snapshot the entire project node again.
 
-            let snapshot_result = snapshot_project_node(
-                &metadata.context,
-                &project_path,
-                instance_name,
-                project_node,
-                &vfs,
-                parent_class.as_ref().map(|name| name.as_str()),
-            );
+            let snapshot_result = metrics.time(Phase::Snapshot, || {
+                snapshot_project_node(
+                    &metadata.context,
+                    &project_path,
+                    instance_name,
+                    project_node,
+                    &vfs,
+                    parent_class.as_ref().map(|name| name.as_str()),
+                )
+            });
 
             let snapshot = match snapshot_result {
                 Ok(Some(snapshot)) => snapshot,
@@ -343,14 +555,62 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: Ref) -> Option<
                 }
                 Err(err) => {
                     log::error!("{:?}", err);
-                    return None;
+                    return report_diagnostic(diagnostics, &source_path, format!("{:#}", err));
                 }
             };
 
-            let patch_set = compute_patch_set(&snapshot, &tree, id);
-            apply_patch_set(tree, patch_set)
+            let patch_set = metrics.time(Phase::ComputePatch, || {
+                compute_patch_set(&snapshot, &tree, id)
+            });
+            let applied_patch_set =
+                metrics.time(Phase::ApplyPatch, || apply_patch_set(tree, patch_set));
+
+            (applied_patch_set, Some(snapshot))
         }
     };
 
+    // Everything that was read again to make the snapshot gets its diagnostics
+    // replaced, which clears the ones for files that were fixed.
+    let mut scope = vec![source_path.as_path()];
+    let mut new_diagnostics = Vec::new();
+
+    if let Some(snapshot) = &snapshot {
+        collect_relevant_paths(snapshot, &mut scope);
+        new_diagnostics = snapshot.all_diagnostics();
+    }
+
+    let (reported, cleared) = diagnostics.replace(&scope, new_diagnostics);
+    applied_patch_set.reported_diagnostics = reported;
+    applied_patch_set.cleared_diagnostics = cleared;
+
+    Some(applied_patch_set)
+}
+
+fn collect_relevant_paths<'a>(snapshot: &'a InstanceSnapshot, paths: &mut Vec<&'a Path>) {
+    paths.extend(
+        snapshot
+            .metadata
+            .relevant_paths
+            .iter()
+            .map(|path| path.as_path()),
+    );
+
+    for child in &snapshot.children {
+        collect_relevant_paths(child, paths);
+    }
+}
+
+/// Records a problem snapshotting the given path, returning a patch that tells
+/// clients about it if it's new.
+fn report_diagnostic(
+    diagnostics: &mut Diagnostics,
+    path: &Path,
+    message: String,
+) -> Option<AppliedPatchSet> {
+    let diagnostic = diagnostics.report(path, message)?;
+
+    let mut applied_patch_set = AppliedPatchSet::new();
+    applied_patch_set.reported_diagnostics.push(diagnostic);
+
     Some(applied_patch_set)
 }
@@ -1,17 +1,19 @@
 use std::{
+    collections::BTreeMap,
     io::{BufWriter, Write},
     path::{Path, PathBuf},
 };
 
 use anyhow::Context;
 use fs_err::File;
-use memofs::Vfs;
+use memofs::{
+    ArchiveBackend, GitBackend, InMemoryFs, OverlayBackend, Vfs, VfsBackend, VfsSnapshot,
+};
 use structopt::StructOpt;
-use tokio::runtime::Runtime;
 
-use crate::serve_session::ServeSession;
+use crate::{project::Project, serve_session::ServeSession};
 
-use super::resolve_path;
+use super::{resolve_path, WatcherOptions};
 
 const UNKNOWN_OUTPUT_KIND_ERR: &str = "Could not detect what kind of file to build. \
                                        Expected output file to end in .rbxl, .rbxlx, .rbxm, or .rbxmx.";
@@ -20,6 +22,8 @@ const UNKNOWN_OUTPUT_KIND_ERR: &str = "Could not detect what kind of file to bui
 #[derive(Debug, StructOpt)]
 pub struct BuildCommand {
     /// Path to the project to serve. Defaults to the current directory.
+    ///
+    /// Can also be a .zip, .tar, or .tar.gz archive containing the project.
     #[structopt(default_value = "")]
     pub project: PathBuf,
 
@@ -32,6 +36,23 @@ pub struct BuildCommand {
     /// Whether to automatically rebuild when any input files change.
     #[structopt(long)]
     pub watch: bool,
+
+    /// Build the project as it was at the given git revision, like a branch,
+    /// tag, or commit hash, instead of from the working copy. The working copy
+    /// is not modified.
+    #[structopt(long, conflicts_with = "watch")]
+    pub rev: Option<String>,
+
+    /// Extra files to layer over the project without writing them to disk.
+    ///
+    /// Either a directory, whose contents are placed relative to the project's
+    /// folder, or a JSON file mapping paths relative to the project's folder to
+    /// file contents. Can be passed more than once; later overlays win.
+    #[structopt(long, number_of_values = 1)]
+    pub overlay: Vec<PathBuf>,
+
+    #[structopt(flatten)]
+    pub watcher_options: WatcherOptions,
 }
 
 impl BuildCommand {
@@ -41,22 +62,68 @@ impl BuildCommand {
         let output_kind = detect_output_kind(&self.output).context(UNKNOWN_OUTPUT_KIND_ERR)?;
 
         log::trace!("Constructing in-memory filesystem");
-        let vfs = Vfs::new_default();
+        let backend: Box<dyn VfsBackend> = match &self.rev {
+            Some(rev) => {
+                let backend = GitBackend::new(&project_path, rev)
+                    .with_context(|| format!("Could not read git revision {}", rev))?;
+
+                Box::new(backend)
+            }
+            None if ArchiveBackend::is_archive(&project_path) && project_path.is_file() => {
+                let backend = ArchiveBackend::new(&project_path).with_context(|| {
+                    format!("Could not read archive {}", project_path.display())
+                })?;
+
+                Box::new(backend)
+            }
+            None => Box::new(self.watcher_options.std_backend(&project_path)),
+        };
+
+        let backend = if self.overlay.is_empty() {
+            backend
+        } else {
+            let project_folder = if Project::is_project_file(&project_path) {
+                project_path.parent().unwrap()
+            } else {
+                &project_path
+            };
+
+            let upper = load_overlays(&self.overlay, project_folder)?;
+            Box::new(OverlayBackend::new(upper, backend))
+        };
+
+        let vfs = Vfs::new(backend);
         vfs.set_watch_enabled(self.watch);
 
         let session = ServeSession::new(vfs, &project_path)?;
+        session.check_diagnostics()?;
         let mut cursor = session.message_queue().cursor();
 
         write_model(&session, &self.output, output_kind)?;
 
         if self.watch {
-            let mut rt = Runtime::new().unwrap();
-
             loop {
                 let receiver = session.message_queue().subscribe(cursor);
-                let (new_cursor, _patch_set) = rt.block_on(receiver).unwrap();
+                let (new_cursor, patch_sets) = futures::executor::block_on(receiver).unwrap();
                 cursor = new_cursor;
 
+                // Subscribers are also woken with no messages, like when the
+                // file watcher runs into an error, and nothing changed then.
+                if patch_sets.is_empty() {
+                    continue;
+                }
+
+                // A file that can't be read would be missing from the model,
+                // so the last good build is kept until it's fixed.
+                if let Err(err) = session.check_diagnostics() {
+                    log::error!(
+                        "{}\nNot writing {} until this is fixed.",
+                        err,
+                        self.output.display()
+                    );
+                    continue;
+                }
+
                 write_model(&session, &self.output, output_kind)?;
             }
         }
@@ -65,6 +132,55 @@ impl BuildCommand {
     }
 }
 
+/// Loads every overlay into a single in-memory filesystem mounted at the
+/// project's folder.
+fn load_overlays(overlays: &[PathBuf], project_folder: &Path) -> anyhow::Result<InMemoryFs> {
+    let mut root = VfsSnapshot::empty_dir();
+
+    for overlay in overlays {
+        let overlay = resolve_path(overlay);
+
+        if overlay.is_dir() {
+            add_overlay_dir(&mut root, &overlay, Path::new(""))
+        } else {
+            add_overlay_file(&mut root, &overlay)
+        }
+        .with_context(|| format!("Could not load overlay {}", overlay.display()))?;
+    }
+
+    let mut fs = InMemoryFs::new();
+    fs.load_snapshot(project_folder, root)?;
+
+    Ok(fs)
+}
+
+fn add_overlay_dir(root: &mut VfsSnapshot, base: &Path, relative: &Path) -> anyhow::Result<()> {
+    for entry in fs_err::read_dir(base.join(relative))? {
+        let entry = entry?;
+        let child = relative.join(entry.file_name());
+
+        if entry.path().is_dir() {
+            root.insert(&child, VfsSnapshot::empty_dir())?;
+            add_overlay_dir(root, base, &child)?;
+        } else {
+            root.insert(&child, VfsSnapshot::file(fs_err::read(entry.path())?))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn add_overlay_file(root: &mut VfsSnapshot, path: &Path) -> anyhow::Result<()> {
+    let contents = fs_err::read(path)?;
+    let files: BTreeMap<PathBuf, String> = serde_json::from_slice(&contents)?;
+
+    for (file_path, file_contents) in files {
+        root.insert(&file_path, VfsSnapshot::file(file_contents))?;
+    }
+
+    Ok(())
+}
+
 /// The different kinds of output that Rojo can build to.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum OutputKind {
@@ -8,11 +8,14 @@ mod plugin;
 mod serve;
 mod upload;
 
-use std::{borrow::Cow, env, path::Path, str::FromStr};
+use std::{borrow::Cow, env, path::Path, str::FromStr, time::Duration};
 
+use memofs::{StdBackend, DEFAULT_DEBOUNCE};
 use structopt::StructOpt;
 use thiserror::Error;
 
+use crate::project::Project;
+
 pub use self::build::BuildCommand;
 pub use self::doc::DocCommand;
 pub use self::fmt_project::FmtProjectCommand;
@@ -56,6 +59,11 @@ pub struct GlobalOptions {
     /// Set color behavior. Valid values are auto, always, and never.
     #[structopt(long("color"), global(true), default_value("auto"))]
     pub color: ColorChoice,
+
+    /// Log every filesystem event Rojo receives, with timestamps. Useful for
+    /// finding out why a change didn't sync.
+    #[structopt(long, global(true))]
+    pub trace_events: bool,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -106,6 +114,114 @@ pub struct ColorChoiceParseError {
     attempted: String,
 }
 
+/// Options for how Rojo finds changes to files, shared by every subcommand that
+/// watches the filesystem.
+#[derive(Debug, StructOpt)]
+pub struct WatcherOptions {
+    /// How to find changes to files. Valid values are native and poll.
+    ///
+    /// Use poll if changes aren't being picked up, which can happen in
+    /// containers and on network drives.
+    #[structopt(long, default_value("native"))]
+    pub watcher: WatcherKind,
+
+    /// How often to scan for changes when using the poll watcher, in
+    /// milliseconds. Must be at least 10.
+    #[structopt(long, default_value("1000"), parse(try_from_str = parse_poll_interval))]
+    pub poll_interval: u64,
+
+    /// How long to wait for changes to a file to settle before syncing them
+    /// when using the native watcher, in milliseconds. Defaults to the
+    /// project's `watchDebounce` setting, or 50.
+    #[structopt(long)]
+    pub watch_debounce: Option<u64>,
+}
+
+impl Default for WatcherOptions {
+    fn default() -> Self {
+        Self {
+            watcher: WatcherKind::Native,
+            poll_interval: 1000,
+            watch_debounce: None,
+        }
+    }
+}
+
+impl WatcherOptions {
+    /// Creates a filesystem backend that finds changes the way these options
+    /// and the project at the given path describe.
+    pub fn std_backend(&self, project_path: &Path) -> StdBackend {
+        match self.watcher {
+            WatcherKind::Native => StdBackend::new_with_debounce(self.debounce(project_path)),
+            WatcherKind::Poll => StdBackend::new_polling(Duration::from_millis(self.poll_interval)),
+        }
+    }
+
+    fn debounce(&self, project_path: &Path) -> Duration {
+        if let Some(debounce) = self.watch_debounce {
+            return Duration::from_millis(debounce);
+        }
+
+        // Problems with the project are reported when it's loaded for real, so
+        // they're ignored here.
+        Project::load_fuzzy(project_path)
+            .ok()
+            .flatten()
+            .and_then(|project| project.watch_debounce)
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_DEBOUNCE)
+    }
+}
+
+/// The shortest poll interval allowed, in milliseconds. Scanning any more often
+/// than this would keep the poll watcher busy all the time.
+const MIN_POLL_INTERVAL: u64 = 10;
+
+fn parse_poll_interval(source: &str) -> Result<u64, PollIntervalParseError> {
+    match source.parse() {
+        Ok(interval) if interval >= MIN_POLL_INTERVAL => Ok(interval),
+        _ => Err(PollIntervalParseError {
+            attempted: source.to_owned(),
+            minimum: MIN_POLL_INTERVAL,
+        }),
+    }
+}
+
+#[derive(Debug, Error)]
+#[error(
+    "Invalid poll interval '{attempted}'. It must be a number of milliseconds, at least {minimum}"
+)]
+pub struct PollIntervalParseError {
+    attempted: String,
+    minimum: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatcherKind {
+    Native,
+    Poll,
+}
+
+impl FromStr for WatcherKind {
+    type Err = WatcherKindParseError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        match source {
+            "native" => Ok(WatcherKind::Native),
+            "poll" => Ok(WatcherKind::Poll),
+            _ => Err(WatcherKindParseError {
+                attempted: source.to_owned(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Invalid watcher '{attempted}'. Valid values are: native, poll")]
+pub struct WatcherKindParseError {
+    attempted: String,
+}
+
 #[derive(Debug, StructOpt)]
 pub enum Subcommand {
     Init(InitCommand),
@@ -1,17 +1,23 @@
 use std::{
-    io::{self, Write},
-    net::{IpAddr, Ipv4Addr},
-    path::PathBuf,
-    sync::Arc,
+    io::{self, BufRead, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    thread,
 };
 
+use anyhow::bail;
 use memofs::Vfs;
 use structopt::StructOpt;
 use termcolor::{BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
+use uuid::Uuid;
 
-use crate::{serve_session::ServeSession, web::LiveServer};
+use crate::{
+    serve_session::ServeSession,
+    web::{LiveServer, TlsIdentity},
+};
 
-use super::{resolve_path, GlobalOptions};
+use super::{resolve_path, GlobalOptions, WatcherOptions};
 
 const DEFAULT_BIND_ADDRESS: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 1);
 const DEFAULT_PORT: u16 = 34872;
@@ -19,9 +25,12 @@ const DEFAULT_PORT: u16 = 34872;
 /// Expose a Rojo project to the Rojo Studio plugin.
 #[derive(Debug, StructOpt)]
 pub struct ServeCommand {
-    /// Path to the project to serve. Defaults to the current directory.
-    #[structopt(default_value = "")]
-    pub project: PathBuf,
+    /// Paths to the projects to serve. Defaults to the current directory.
+    ///
+    /// When more than one project is given, they're all served on the same
+    /// port. Each project's API is under `/api/<project name>/`, and the first
+    /// project is also served like it would be on its own.
+    pub projects: Vec<PathBuf>,
 
     /// The IP address to listen on. Defaults to `127.0.0.1`.
     #[structopt(long)]
@@ -31,33 +40,236 @@ pub struct ServeCommand {
     /// it has none.
     #[structopt(long)]
     pub port: Option<u16>,
+
+    /// Speak JSON-RPC over stdin and stdout instead of serving HTTP, for editor
+    /// integrations. Rojo stops when stdin is closed.
+    #[structopt(
+        long,
+        conflicts_with_all = &["address", "port", "token", "tls-cert", "tls-key"]
+    )]
+    pub stdio: bool,
+
+    /// Require clients to send a token with every API request, as an
+    /// `Authorization: Bearer <token>` header. Generates a random token unless
+    /// one is given with `--token=<token>`.
+    ///
+    /// Required when listening on an address other than localhost.
+    #[structopt(long, min_values = 0, require_equals = true)]
+    pub token: Option<Option<String>>,
+
+    /// Serve HTTPS using the PEM-encoded certificate at this path. Requires
+    /// `--tls-key`.
+    #[structopt(long, requires = "tls-key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// The PEM-encoded private key for the certificate given with `--tls-cert`.
+    #[structopt(long, requires = "tls-cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Generate a self-signed certificate and key at the `--tls-cert` and
+    /// `--tls-key` paths if they don't exist yet. Clients need to be told to
+    /// trust the certificate, so this is meant for local use.
+    #[structopt(long, requires_all = &["tls-cert", "tls-key"])]
+    pub tls_self_signed: bool,
+
+    /// Refuse all changes from clients, so that nothing served can be written
+    /// back to the project's files.
+    #[structopt(long)]
+    pub read_only: bool,
+
+    #[structopt(flatten)]
+    pub watcher_options: WatcherOptions,
 }
 
 impl ServeCommand {
     pub fn run(self, global: GlobalOptions) -> anyhow::Result<()> {
-        let project_path = resolve_path(&self.project);
+        let project_paths = if self.projects.is_empty() {
+            vec![resolve_path(Path::new(""))]
+        } else {
+            self.projects
+                .iter()
+                .map(|path| resolve_path(path))
+                .collect()
+        };
 
-        let vfs = Vfs::new_default();
+        let sessions = project_paths
+            .iter()
+            .map(|project_path| {
+                let vfs = Vfs::new(self.watcher_options.std_backend(project_path));
+                let session = ServeSession::new(vfs, project_path)?;
+                session.set_read_only(self.read_only);
 
-        let session = Arc::new(ServeSession::new(vfs, &project_path)?);
+                Ok(Arc::new(session))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if self.stdio {
+            LiveServer::new(sessions)?.start_stdio();
+            return Ok(());
+        }
 
         let ip = self.address.unwrap_or(DEFAULT_BIND_ADDRESS.into());
 
+        let token = match self.token {
+            Some(Some(token)) => Some(token),
+            Some(None) => Some(Uuid::new_v4().to_simple().to_string()),
+            None => None,
+        };
+
+        if token.is_none() && !ip.is_loopback() {
+            bail!(
+                "Refusing to listen on {} without a token, since anyone who can reach it \
+                 could change and delete files in the project.\n\
+                 Pass --token to require one.",
+                ip
+            );
+        }
+
         let port = self
             .port
-            .or_else(|| session.project_port())
+            .or_else(|| sessions[0].project_port())
             .unwrap_or(DEFAULT_PORT);
 
-        let server = LiveServer::new(session);
+        let mut server = LiveServer::new(sessions.clone())?;
+        if let Some(token) = &token {
+            server = server.with_token(token.clone());
+        }
+
+        let tls_identity = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert_path), Some(key_path)) => Some(load_tls_identity(
+                &resolve_path(cert_path),
+                &resolve_path(key_path),
+                self.tls_self_signed,
+                ip,
+            )?),
+            _ => None,
+        };
+
+        if let Some(identity) = &tls_identity {
+            server = server.with_tls(identity)?;
+        }
 
-        let _ = show_start_message(ip, port, global.color.into());
+        let _ = show_start_message(
+            &sessions,
+            ip,
+            port,
+            token.as_deref(),
+            tls_identity.is_some(),
+            self.read_only,
+            global.color.into(),
+        );
+        spawn_resync_listener(sessions.iter().map(Arc::downgrade).collect());
         server.start((ip, port).into());
 
+        // The server has shut down, so these are the last handles to the
+        // sessions. Dropping them stops each session's change processor first,
+        // letting it finish any change it's in the middle of.
+        drop(sessions);
+
         Ok(())
     }
 }
 
-fn show_start_message(bind_address: IpAddr, port: u16, color: ColorChoice) -> io::Result<()> {
+/// Reads the certificate and key to serve HTTPS with, first generating them if
+/// a self-signed certificate was asked for and they don't exist yet.
+fn load_tls_identity(
+    cert_path: &Path,
+    key_path: &Path,
+    self_signed: bool,
+    bind_address: IpAddr,
+) -> anyhow::Result<TlsIdentity> {
+    if self_signed {
+        match (cert_path.exists(), key_path.exists()) {
+            (false, false) => {
+                let mut addresses = vec![
+                    IpAddr::from(Ipv4Addr::LOCALHOST),
+                    Ipv6Addr::LOCALHOST.into(),
+                ];
+                if !bind_address.is_loopback() && !bind_address.is_unspecified() {
+                    addresses.push(bind_address);
+                }
+
+                let identity = TlsIdentity::self_signed(&addresses)?;
+                fs_err::write(cert_path, &identity.cert_pem)?;
+                write_private_key(key_path, &identity.key_pem)?;
+
+                log::info!(
+                    "Generated a self-signed certificate at {}",
+                    cert_path.display()
+                );
+
+                return Ok(identity);
+            }
+            (true, true) => {}
+            _ => bail!(
+                "Only one of {} and {} exists, so a self-signed certificate can't be generated \
+                 without overwriting the other.",
+                cert_path.display(),
+                key_path.display()
+            ),
+        }
+    }
+
+    Ok(TlsIdentity {
+        cert_pem: fs_err::read(cert_path)?,
+        key_pem: fs_err::read(key_path)?,
+    })
+}
+
+/// Writes a generated private key so that, on Unix, only the current user can
+/// read it.
+fn write_private_key(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs_err::OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use fs_err::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(contents)
+}
+
+/// Resyncs every session whenever the user types `r` and presses enter in the
+/// terminal. Stops if stdin is closed, like when Rojo isn't run interactively.
+fn spawn_resync_listener(sessions: Vec<Weak<ServeSession>>) {
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+
+            if !line.trim().eq_ignore_ascii_case("r") {
+                continue;
+            }
+
+            for session in sessions.iter().filter_map(Weak::upgrade) {
+                match session.resync() {
+                    Ok(patch_set) => println!(
+                        "Resynced {}: {} added, {} removed, {} updated",
+                        session.project_name(),
+                        patch_set.added.len(),
+                        patch_set.removed.len(),
+                        patch_set.updated.len()
+                    ),
+                    Err(err) => log::error!("Could not resync {}: {}", session.project_name(), err),
+                }
+            }
+        }
+    });
+}
+
+fn show_start_message(
+    sessions: &[Arc<ServeSession>],
+    bind_address: IpAddr,
+    port: u16,
+    token: Option<&str>,
+    tls: bool,
+    read_only: bool,
+    color: ColorChoice,
+) -> io::Result<()> {
     let mut green = ColorSpec::new();
     green.set_fg(Some(Color::Green)).set_bold(true);
 
@@ -79,17 +291,53 @@ fn show_start_message(bind_address: IpAddr, port: u16, color: ColorChoice) -> io
     buffer.set_color(&green)?;
     writeln!(&mut buffer, "{}", port)?;
 
+    if let Some(token) = token {
+        buffer.set_color(&ColorSpec::new())?;
+        write!(&mut buffer, "  Token:   ")?;
+        buffer.set_color(&green)?;
+        writeln!(&mut buffer, "{}", token)?;
+    }
+
+    if read_only {
+        buffer.set_color(&ColorSpec::new())?;
+        write!(&mut buffer, "  Mode:    ")?;
+        buffer.set_color(&green)?;
+        writeln!(&mut buffer, "read-only")?;
+    }
+
+    if sessions.len() > 1 {
+        buffer.set_color(&ColorSpec::new())?;
+        writeln!(&mut buffer, "  Projects:")?;
+
+        for session in sessions {
+            write!(&mut buffer, "    ")?;
+            buffer.set_color(&green)?;
+            writeln!(&mut buffer, "{}", session.project_name())?;
+            buffer.set_color(&ColorSpec::new())?;
+        }
+    }
+
     writeln!(&mut buffer)?;
 
     buffer.set_color(&ColorSpec::new())?;
     write!(&mut buffer, "Visit ")?;
 
     buffer.set_color(&green)?;
-    write!(&mut buffer, "http://localhost:{}/", port)?;
+    let scheme = if tls { "https" } else { "http" };
+    write!(&mut buffer, "{}://localhost:{}/", scheme, port)?;
 
     buffer.set_color(&ColorSpec::new())?;
     writeln!(&mut buffer, " in your browser for more information.")?;
 
+    write!(&mut buffer, "Type ")?;
+    buffer.set_color(&green)?;
+    write!(&mut buffer, "r")?;
+    buffer.set_color(&ColorSpec::new())?;
+    writeln!(
+        &mut buffer,
+        " and press enter to resync everything from disk."
+    )?;
+
     writer.print(&buffer)?;
 
     Ok(())
@@ -40,6 +40,7 @@ impl UploadCommand {
         let vfs = Vfs::new_default();
 
         let session = ServeSession::new(vfs, project_path)?;
+        session.check_diagnostics()?;
 
         let tree = session.tree();
         let inner_tree = tree.inner();
@@ -0,0 +1,165 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A problem with one of a project's files that kept Rojo from updating the
+/// tree, like a model file with invalid JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    /// The path Rojo was reading when it ran into the problem. The file at
+    /// fault is either this one or inside of it.
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+/// The diagnostics a session currently has, at most one per path. A diagnostic
+/// stays until the path it's for is read successfully again.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    by_path: BTreeMap<PathBuf, String>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a problem with the given path, replacing any earlier one.
+    /// Returns the diagnostic if it's different from what was there before.
+    pub fn report(&mut self, path: &Path, message: String) -> Option<Diagnostic> {
+        if self.by_path.get(path) == Some(&message) {
+            return None;
+        }
+
+        self.by_path.insert(path.to_owned(), message.clone());
+
+        Some(Diagnostic {
+            path: path.to_owned(),
+            message,
+        })
+    }
+
+    /// Replaces the diagnostics for everything inside the given paths with the
+    /// ones found after reading them again. Returns the diagnostics that are
+    /// new or changed, and the paths whose diagnostics went away.
+    pub fn replace(
+        &mut self,
+        scope: &[&Path],
+        diagnostics: Vec<Diagnostic>,
+    ) -> (Vec<Diagnostic>, Vec<PathBuf>) {
+        self.replace_where(
+            |path| scope.iter().any(|scope_path| path.starts_with(scope_path)),
+            diagnostics,
+        )
+    }
+
+    /// Replaces every diagnostic with the given ones, after the whole project
+    /// was read again. Returns the same as `replace`.
+    pub fn replace_all(&mut self, diagnostics: Vec<Diagnostic>) -> (Vec<Diagnostic>, Vec<PathBuf>) {
+        self.replace_where(|_| true, diagnostics)
+    }
+
+    fn replace_where(
+        &mut self,
+        in_scope: impl Fn(&Path) -> bool,
+        diagnostics: Vec<Diagnostic>,
+    ) -> (Vec<Diagnostic>, Vec<PathBuf>) {
+        let cleared: Vec<PathBuf> = self
+            .by_path
+            .keys()
+            .filter(|path| in_scope(path))
+            .filter(|path| {
+                !diagnostics
+                    .iter()
+                    .any(|diagnostic| &diagnostic.path == *path)
+            })
+            .cloned()
+            .collect();
+
+        for cleared_path in &cleared {
+            self.by_path.remove(cleared_path);
+        }
+
+        let reported = diagnostics
+            .into_iter()
+            .filter_map(|diagnostic| self.report(&diagnostic.path, diagnostic.message))
+            .collect();
+
+        (reported, cleared)
+    }
+
+    /// Lists every diagnostic, sorted by path.
+    pub fn to_vec(&self) -> Vec<Diagnostic> {
+        self.by_path
+            .iter()
+            .map(|(path, message)| Diagnostic {
+                path: path.clone(),
+                message: message.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn report_only_returns_changes() {
+        let mut diagnostics = Diagnostics::new();
+        let path = Path::new("/foo/bar.model.json");
+
+        assert!(diagnostics
+            .report(path, "Invalid JSON".to_owned())
+            .is_some());
+        assert!(diagnostics
+            .report(path, "Invalid JSON".to_owned())
+            .is_none());
+        assert!(diagnostics
+            .report(path, "Unknown class".to_owned())
+            .is_some());
+        assert_eq!(diagnostics.to_vec().len(), 1);
+    }
+
+    #[test]
+    fn replace_only_clears_inside_scope() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.report(Path::new("/foo/a.model.json"), "A".to_owned());
+        diagnostics.report(Path::new("/foo/bar/b.model.json"), "B".to_owned());
+        diagnostics.report(Path::new("/foobar.model.json"), "C".to_owned());
+
+        let (reported, cleared) = diagnostics.replace(
+            &[Path::new("/foo")],
+            vec![Diagnostic {
+                path: PathBuf::from("/foo/bar/b.model.json"),
+                message: "B".to_owned(),
+            }],
+        );
+        assert!(reported.is_empty());
+        assert_eq!(cleared, vec![PathBuf::from("/foo/a.model.json")]);
+
+        let remaining: Vec<PathBuf> = diagnostics
+            .to_vec()
+            .into_iter()
+            .map(|diagnostic| diagnostic.path)
+            .collect();
+        assert_eq!(
+            remaining,
+            vec![
+                PathBuf::from("/foo/bar/b.model.json"),
+                PathBuf::from("/foobar.model.json")
+            ]
+        );
+    }
+}
@@ -0,0 +1,520 @@
+//! Rojo's API for embedding live sync sessions in other Rust programs, like
+//! asset pipelines, without going through the HTTP server.
+//!
+//! ## Stability
+//! This module follows semantic versioning: nothing in it changes in a way
+//! that breaks code using it, except in a new major version of Rojo. Its types
+//! are copies of what's in the session rather than Rojo's internal types, so
+//! that Rojo can change how sessions work inside without breaking embedders.
+//!
+//! To leave room to grow, the structs and enums here are `#[non_exhaustive]`.
+//! Minor releases can add fields, variants, and methods to them, so structs
+//! are created with their `new` functions instead of literals, and matches on
+//! enums need a wildcard arm.
+//!
+//! The [`memofs`] and [`rbx_dom_weak`] crates are re-exported because their
+//! types, like `Vfs`, `Ref`, and `Variant`, are part of this API. Moving to a
+//! version of either that isn't compatible counts as a breaking change.
+//!
+//! Everything else that librojo exports, like `cli` and `web_api`, exists to
+//! build the `rojo` binary and its tests, and isn't covered by any of this. It
+//! can change in any release.
+//!
+//! ## Example
+//! ```no_run
+//! use librojo::embed::{memofs::{StdBackend, Vfs}, Session};
+//!
+//! let vfs = Vfs::new(StdBackend::new());
+//! let session = Session::start(vfs, "default.project.json")?;
+//!
+//! for change in session.subscribe() {
+//!     println!("{} instances added", change.added.len());
+//! }
+//! # Ok::<(), librojo::embed::SessionError>(())
+//! ```
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+    thread,
+};
+
+use crossbeam_channel::Receiver;
+use futures::{stream, Stream, StreamExt};
+use rbx_dom_weak::types::{Ref, Variant};
+use thiserror::Error;
+
+use crate::{
+    diagnostics,
+    message_queue::MessageQueue,
+    serve_session::{ServeSession, ServeSessionError},
+    snapshot::{AppliedPatchSet, AppliedPatchUpdate, PatchSet, PatchUpdate},
+};
+
+pub use crate::session_id::SessionId;
+
+// The versions of memofs and rbx_dom_weak that this API uses, so that embedders
+// don't need to match them by hand.
+pub use memofs;
+pub use rbx_dom_weak;
+
+/// A live sync session for one project, which keeps a tree of instances up to
+/// date with the files in a [`Vfs`](memofs::Vfs).
+///
+/// This is the same kind of session that `rojo serve` exposes over HTTP. The
+/// session stops when it's dropped or [`shutdown`](Session::shutdown) is
+/// called.
+pub struct Session {
+    inner: ServeSession,
+}
+
+impl Session {
+    /// Starts a session for the project at the given path, which can either be
+    /// a project file or a folder containing a `default.project.json` file.
+    ///
+    /// The project is read from the given VFS, which is watched for changes
+    /// unless watching has been turned off on it.
+    ///
+    /// Files that can't be read don't stop the session from starting. They're
+    /// left out of the tree and listed by [`diagnostics`](Session::diagnostics)
+    /// until they're fixed.
+    pub fn start<P: AsRef<Path>>(vfs: memofs::Vfs, project_path: P) -> Result<Self, SessionError> {
+        let inner = ServeSession::new(vfs, project_path).map_err(SessionError::from_internal)?;
+
+        Ok(Session { inner })
+    }
+
+    /// A random ID for this session. It's different for every session, even of
+    /// the same project.
+    pub fn session_id(&self) -> SessionId {
+        self.inner.session_id()
+    }
+
+    pub fn project_name(&self) -> &str {
+        self.inner.project_name()
+    }
+
+    /// The ID of the instance at the root of the tree.
+    pub fn root_id(&self) -> Ref {
+        self.inner.tree().get_root_id()
+    }
+
+    /// Copies an instance out of the tree, or returns `None` if there's no
+    /// instance with that ID.
+    pub fn instance(&self, id: Ref) -> Option<Instance> {
+        let tree = self.inner.tree();
+        let instance = tree.get_instance(id)?;
+
+        Some(Instance {
+            id,
+            parent: instance.parent(),
+            name: instance.name().to_owned(),
+            class_name: instance.class_name().to_owned(),
+            properties: instance.properties().clone(),
+            children: instance.children().to_vec(),
+        })
+    }
+
+    /// Lists the problems with the project's files that are keeping parts of
+    /// the tree from updating, sorted by path. Changes to these are also sent
+    /// to subscribers.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.inner
+            .diagnostics()
+            .into_iter()
+            .map(Diagnostic::from_internal)
+            .collect()
+    }
+
+    /// Lists the most recent errors from watching the project's files. Changes
+    /// may have been missed when there are any, which
+    /// [`resync`](Session::resync) catches up on.
+    pub fn watcher_errors(&self) -> Vec<String> {
+        self.inner.watcher_errors()
+    }
+
+    /// Receives every change made to the tree from now on, in order, on a
+    /// channel.
+    ///
+    /// The channel disconnects once the session stops. Changes are forwarded
+    /// by a background thread, which stops after the channel is dropped and the
+    /// next change is made.
+    pub fn subscribe(&self) -> Receiver<Change> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let changes = self.subscribe_async();
+
+        thread::Builder::new()
+            .name("Session subscription thread".to_owned())
+            .spawn(move || {
+                for change in futures::executor::block_on_stream(Box::pin(changes)) {
+                    if sender.send(change).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Could not start subscription thread");
+
+        receiver
+    }
+
+    /// Like [`subscribe`](Session::subscribe), but as a stream for use with
+    /// async code. It works with any executor, and ends once the session
+    /// stops.
+    pub fn subscribe_async(&self) -> impl Stream<Item = Change> + Send {
+        let message_queue = Arc::downgrade(&self.inner.message_queue_handle());
+        let cursor = self.inner.message_queue().cursor();
+
+        stream::unfold(cursor, move |cursor| {
+            let message_queue = message_queue.clone();
+
+            async move {
+                let (cursor, messages) = next_messages(&message_queue, cursor).await;
+
+                if messages.is_empty() {
+                    None
+                } else {
+                    Some((stream::iter(messages), cursor))
+                }
+            }
+        })
+        .flatten()
+        .filter_map(|patch_set| async move { Change::from_internal(patch_set) })
+    }
+
+    /// Requests changes to the tree, like the Studio plugin does when a user
+    /// edits a script. The changes are applied by the session's change
+    /// processor, which writes them back to the project's files where it can.
+    ///
+    /// Subscribers find out what actually changed once the edit is applied.
+    pub fn submit(&self, edit: Edit) -> Result<(), SessionError> {
+        if self.inner.is_read_only() {
+            return Err(SessionError::ReadOnly);
+        }
+
+        self.inner
+            .tree_mutation_sender()
+            .send(edit.into_internal())
+            .map_err(|_| SessionError::Stopped)
+    }
+
+    /// Turns read-only mode on or off. While it's on, `submit` fails and no
+    /// changes are written back to files.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.inner.set_read_only(read_only);
+    }
+
+    /// Rebuilds the whole tree from the VFS and sends any differences to
+    /// subscribers, for when the tree has drifted from the files. Returns
+    /// those differences, or `None` if there weren't any.
+    ///
+    /// If the project can't be read, the tree is left as it was.
+    pub fn resync(&self) -> Result<Option<Change>, SessionError> {
+        let patch_set = self.inner.resync().map_err(SessionError::from_internal)?;
+
+        Ok(Change::from_internal(patch_set))
+    }
+
+    /// Stops the session, waiting for any changes that are being processed to
+    /// finish. Subscriptions end once they've received every change.
+    pub fn shutdown(self) {
+        drop(self);
+    }
+}
+
+/// Waits for the messages after the given cursor, resolving to an empty list
+/// if the session stops first.
+///
+/// Only a weak handle to the message queue is kept between messages so that
+/// subscribers don't keep the queue alive after the session stops.
+async fn next_messages(
+    message_queue: &Weak<MessageQueue<AppliedPatchSet>>,
+    cursor: u32,
+) -> (u32, Vec<AppliedPatchSet>) {
+    loop {
+        let receiver = match message_queue.upgrade() {
+            Some(message_queue) => message_queue.subscribe(cursor),
+            None => return (cursor, Vec::new()),
+        };
+
+        match receiver.await {
+            // Subscribers are woken without any messages when the watcher
+            // reports an error, which the embedding program can read from the
+            // session itself.
+            Ok((_, messages)) if messages.is_empty() => continue,
+            Ok(result) => return result,
+            Err(_) => return (cursor, Vec::new()),
+        }
+    }
+}
+
+/// An instance from a session's tree, copied out of it by
+/// [`Session::instance`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Instance {
+    pub id: Ref,
+
+    /// The instance's parent, which is `Ref::none()` for the root instance.
+    pub parent: Ref,
+
+    pub name: String,
+    pub class_name: String,
+    pub properties: HashMap<String, Variant>,
+    pub children: Vec<Ref>,
+}
+
+/// A change that was made to a session's tree, which subscribers receive.
+///
+/// Instances that were added can be read with
+/// [`Session::instance`](Session::instance), as long as they haven't been
+/// removed since.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Change {
+    pub removed: Vec<Ref>,
+    pub added: Vec<Ref>,
+    pub updated: Vec<InstanceChange>,
+
+    /// Problems found while making this change, each replacing any earlier
+    /// diagnostic for the same path.
+    pub reported_diagnostics: Vec<Diagnostic>,
+
+    /// Paths whose diagnostics were resolved by this change.
+    pub cleared_diagnostics: Vec<PathBuf>,
+}
+
+impl Change {
+    /// Converts a message from the session, leaving out changes that only
+    /// touched Rojo's own metadata. Returns `None` if nothing is left.
+    fn from_internal(patch_set: AppliedPatchSet) -> Option<Self> {
+        let change = Change {
+            removed: patch_set.removed,
+            added: patch_set.added,
+            updated: patch_set
+                .updated
+                .into_iter()
+                .filter_map(InstanceChange::from_internal)
+                .collect(),
+            reported_diagnostics: patch_set
+                .reported_diagnostics
+                .into_iter()
+                .map(Diagnostic::from_internal)
+                .collect(),
+            cleared_diagnostics: patch_set.cleared_diagnostics,
+        };
+
+        if change == Change::default() {
+            None
+        } else {
+            Some(change)
+        }
+    }
+}
+
+/// Changes to one instance, either made to the tree or requested with an
+/// [`Edit`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct InstanceChange {
+    pub id: Ref,
+    pub changed_name: Option<String>,
+    pub changed_class_name: Option<String>,
+
+    /// Every changed property. Properties set to `None` were removed.
+    pub changed_properties: HashMap<String, Option<Variant>>,
+}
+
+impl InstanceChange {
+    /// Creates a change to the given instance that doesn't change anything
+    /// yet.
+    pub fn new(id: Ref) -> Self {
+        InstanceChange {
+            id,
+            changed_name: None,
+            changed_class_name: None,
+            changed_properties: HashMap::new(),
+        }
+    }
+
+    fn from_internal(update: AppliedPatchUpdate) -> Option<Self> {
+        if update.changed_name.is_none()
+            && update.changed_class_name.is_none()
+            && update.changed_properties.is_empty()
+        {
+            return None;
+        }
+
+        Some(InstanceChange {
+            id: update.id,
+            changed_name: update.changed_name,
+            changed_class_name: update.changed_class_name,
+            changed_properties: update.changed_properties,
+        })
+    }
+}
+
+/// Changes to request with [`Session::submit`]. Instances can be removed and
+/// updated, but not added.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Edit {
+    pub removed: Vec<Ref>,
+    pub updated: Vec<InstanceChange>,
+}
+
+impl Edit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn into_internal(self) -> PatchSet {
+        let mut patch_set = PatchSet::new();
+        patch_set.removed_instances = self.removed;
+        patch_set.updated_instances = self
+            .updated
+            .into_iter()
+            .map(|update| PatchUpdate {
+                id: update.id,
+                changed_name: update.changed_name,
+                changed_class_name: update.changed_class_name,
+                changed_properties: update.changed_properties,
+                changed_metadata: None,
+            })
+            .collect();
+
+        patch_set
+    }
+}
+
+/// A problem with one of a project's files that kept the session from
+/// updating the tree, like a model file with invalid JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// The path that was being read. The file at fault is either this one or
+    /// inside of it.
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn from_internal(diagnostic: diagnostics::Diagnostic) -> Self {
+        Diagnostic {
+            path: diagnostic.path,
+            message: diagnostic.message,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SessionError {
+    /// The project couldn't be read, like when its project file is missing or
+    /// invalid.
+    #[error(transparent)]
+    Project { source: anyhow::Error },
+
+    #[error("The session has stopped processing changes")]
+    Stopped,
+
+    #[error("The session is read-only")]
+    ReadOnly,
+}
+
+impl SessionError {
+    fn from_internal(error: ServeSessionError) -> Self {
+        match error {
+            ServeSessionError::Stopped => SessionError::Stopped,
+            error => SessionError::Project {
+                source: error.into(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use memofs::{InMemoryFs, Vfs, VfsBackend, VfsSnapshot};
+
+    fn project_fs() -> InMemoryFs {
+        let mut fs = InMemoryFs::new();
+        fs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(vec![
+                (
+                    "default.project.json",
+                    VfsSnapshot::file(r#"{ "name": "embedded", "tree": { "$path": "src" } }"#),
+                ),
+                (
+                    "src",
+                    VfsSnapshot::dir(vec![("foo.txt", VfsSnapshot::file("Hello"))]),
+                ),
+            ]),
+        )
+        .unwrap();
+
+        fs
+    }
+
+    #[test]
+    fn subscribe_to_changes() {
+        let mut fs = project_fs();
+        let session = Session::start(Vfs::new(fs.clone()), "/root").unwrap();
+        assert_eq!(session.project_name(), "embedded");
+
+        let receiver = session.subscribe();
+
+        fs.write(Path::new("/root/src/foo.txt"), b"World").unwrap();
+
+        let change = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(change.updated.len(), 1);
+
+        let foo = session.instance(change.updated[0].id).unwrap();
+        assert_eq!(foo.name, "foo");
+        assert_eq!(
+            foo.properties.get("Value"),
+            Some(&Variant::String("World".to_owned()))
+        );
+    }
+
+    #[test]
+    fn read_tree() {
+        let session = Session::start(Vfs::new(project_fs()), "/root").unwrap();
+
+        let root = session.instance(session.root_id()).unwrap();
+        assert_eq!(root.name, "embedded");
+        assert_eq!(root.parent, Ref::none());
+        assert_eq!(root.children.len(), 1);
+
+        let foo = session.instance(root.children[0]).unwrap();
+        assert_eq!(foo.class_name, "StringValue");
+        assert_eq!(foo.parent, root.id);
+    }
+
+    #[test]
+    fn read_only_refuses_patches() {
+        let session = Session::start(Vfs::new(project_fs()), "/root").unwrap();
+        session.set_read_only(true);
+
+        match session.submit(Edit::new()) {
+            Err(SessionError::ReadOnly) => {}
+            other => panic!("expected read-only error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn subscriptions_end_on_shutdown() {
+        let session = Session::start(Vfs::new(project_fs()), "/root").unwrap();
+        let receiver = session.subscribe();
+
+        session.shutdown();
+
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_err());
+        assert!(receiver.is_empty());
+    }
+}
@@ -1,17 +1,24 @@
+//! The library behind the `rojo` binary. Only the [`embed`] module is meant
+//! for use by other programs, and only it follows semantic versioning; see its
+//! documentation for details.
+
 // Recursion limit bump is to support Ritz, a JSX-like proc macro used for
 // Rojo's web UI currently.
 #![recursion_limit = "1024"]
 
 pub mod cli;
+pub mod embed;
 
 #[cfg(test)]
 mod tree_view;
 
 mod auth_cookie;
 mod change_processor;
+mod diagnostics;
 mod glob;
 mod lua_ast;
 mod message_queue;
+mod metrics;
 mod multimap;
 mod path_serializer;
 mod project;
@@ -1,6 +1,10 @@
-use std::sync::{Mutex, RwLock};
+use std::{
+    ops::Range,
+    sync::{Mutex, RwLock},
+    time::SystemTime,
+};
 
-use futures::sync::oneshot;
+use futures::channel::oneshot;
 
 /// A message queue with persistent history that can be subscribed to.
 ///
@@ -8,6 +12,9 @@ use futures::sync::oneshot;
 #[derive(Default)]
 pub struct MessageQueue<T> {
     messages: RwLock<Vec<T>>,
+
+    /// When each message in `messages` was pushed, at the same index.
+    pushed_at: RwLock<Vec<SystemTime>>,
     message_listeners: Mutex<Vec<Listener<T>>>,
 }
 
@@ -15,6 +22,7 @@ impl<T: Clone> MessageQueue<T> {
     pub fn new() -> MessageQueue<T> {
         MessageQueue {
             messages: RwLock::new(Vec::new()),
+            pushed_at: RwLock::new(Vec::new()),
             message_listeners: Mutex::new(Vec::new()),
         }
     }
@@ -24,6 +32,10 @@ impl<T: Clone> MessageQueue<T> {
         let mut messages = self.messages.write().unwrap();
         messages.extend_from_slice(new_messages);
 
+        let now = SystemTime::now();
+        let mut pushed_at = self.pushed_at.write().unwrap();
+        pushed_at.resize(messages.len(), now);
+
         let mut remaining_listeners = Vec::new();
 
         for listener in message_listeners.drain(..) {
@@ -38,6 +50,17 @@ impl<T: Clone> MessageQueue<T> {
         *message_listeners = remaining_listeners;
     }
 
+    /// Wakes every subscriber without pushing a message, so that they can
+    /// check on something outside of the queue, like the watcher's errors.
+    /// They're sent their own cursor and no messages.
+    pub fn wake_subscribers(&self) {
+        let mut message_listeners = self.message_listeners.lock().unwrap();
+
+        for listener in message_listeners.drain(..) {
+            let _ = listener.sender.send((listener.cursor, Vec::new()));
+        }
+    }
+
     /// Subscribe to any messages occurring after the given message cursor.
     pub fn subscribe(&self, cursor: u32) -> oneshot::Receiver<(u32, Vec<T>)> {
         let (sender, receiver) = oneshot::channel();
@@ -76,6 +99,22 @@ impl<T: Clone> MessageQueue<T> {
     pub fn cursor(&self) -> u32 {
         self.messages.read().unwrap().len() as u32
     }
+
+    /// Returns the messages between the given cursors along with when each one
+    /// was pushed. Cursors past the end of the queue are clamped to it.
+    pub fn history(&self, range: Range<u32>) -> Vec<(SystemTime, T)> {
+        let messages = self.messages.read().unwrap();
+        let pushed_at = self.pushed_at.read().unwrap();
+
+        let end = (range.end as usize).min(messages.len());
+        let start = (range.start as usize).min(end);
+
+        pushed_at[start..end]
+            .iter()
+            .copied()
+            .zip(messages[start..end].iter().cloned())
+            .collect()
+    }
 }
 
 struct Listener<T> {
@@ -97,3 +136,22 @@ fn fire_listener_if_ready<T: Clone>(
         Err(listener)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wake_without_messages() {
+        let queue = MessageQueue::<u32>::new();
+        let receiver = queue.subscribe(0);
+
+        queue.wake_subscribers();
+
+        let (cursor, messages) = futures::executor::block_on(receiver).unwrap();
+        assert_eq!(cursor, 0);
+        assert!(messages.is_empty());
+        assert_eq!(queue.cursor(), 0);
+        assert!(queue.history(0..1).is_empty());
+    }
+}
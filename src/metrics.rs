@@ -0,0 +1,112 @@
+//! Timings collected while serving a project, to help find out why syncing is
+//! slow. They're shown on the web UI and served in Prometheus' text format at
+//! `/metrics`.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// A step of turning a change into messages for clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Reading files into an `InstanceSnapshot`.
+    Snapshot,
+
+    /// Comparing a snapshot against the tree to find what changed.
+    ComputePatch,
+
+    /// Applying changes to the tree.
+    ApplyPatch,
+
+    /// Turning messages into JSON for a subscribed client.
+    Serialize,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [
+        Phase::Snapshot,
+        Phase::ComputePatch,
+        Phase::ApplyPatch,
+        Phase::Serialize,
+    ];
+
+    /// The name of this phase in Prometheus labels.
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Snapshot => "snapshot",
+            Phase::ComputePatch => "compute_patch",
+            Phase::ApplyPatch => "apply_patch",
+            Phase::Serialize => "serialize",
+        }
+    }
+
+    /// A short description of this phase for people.
+    pub fn description(self) -> &'static str {
+        match self {
+            Phase::Snapshot => "Snapshotting files",
+            Phase::ComputePatch => "Computing patches",
+            Phase::ApplyPatch => "Applying patches",
+            Phase::Serialize => "Serializing messages",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How long every run of a phase took, all added up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+    pub last: Duration,
+}
+
+impl Timing {
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::default()
+        } else {
+            Duration::from_secs_f64(self.total.as_secs_f64() / self.count as f64)
+        }
+    }
+
+    fn record(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+        self.last = duration;
+    }
+}
+
+/// The timings of each phase for one serve session.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    timings: Mutex<[Timing; 4]>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, phase: Phase, duration: Duration) {
+        self.timings.lock().unwrap()[phase.index()].record(duration);
+    }
+
+    /// Runs the given function, recording how long it took as a run of the
+    /// given phase.
+    pub fn time<T>(&self, phase: Phase, callback: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = callback();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    pub fn timing(&self, phase: Phase) -> Timing {
+        self.timings.lock().unwrap()[phase.index()]
+    }
+}
@@ -72,6 +72,20 @@ pub struct Project {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub glob_ignore_paths: Vec<Glob>,
 
+    /// Whether Rojo should follow symbolic links it finds inside directories.
+    /// Defaults to `true`.
+    ///
+    /// Symlinks that would cause a directory to contain itself are always
+    /// skipped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_symlinks: Option<bool>,
+
+    /// If specified, how long in milliseconds Rojo should wait for changes to a
+    /// file to settle before syncing them. Raising it can reduce flickering
+    /// with editors that save by replacing files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_debounce: Option<u64>,
+
     /// The path to the file that this project came from. Relative paths in the
     /// project should be considered relative to the parent of this field, also
     /// given by `Project::folder_location`.
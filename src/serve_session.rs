@@ -3,7 +3,10 @@ use std::{
     collections::HashSet,
     io,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
     time::Instant,
 };
 
@@ -13,8 +16,10 @@ use memofs::Vfs;
 use thiserror::Error;
 
 use crate::{
-    change_processor::ChangeProcessor,
+    change_processor::{ChangeProcessor, ResyncResult},
+    diagnostics::{Diagnostic, Diagnostics},
     message_queue::MessageQueue,
+    metrics::{Metrics, Phase},
     project::{Project, ProjectError},
     session_id::SessionId,
     snapshot::{
@@ -28,11 +33,9 @@ use crate::{
 /// when we need to build a Rojo tree and possibly rebuild it when input files
 /// change.
 ///
-/// Nothing here is specific to any Rojo interface. Though the primary way to
-/// interact with a serve session is Rojo's HTTP right now, there's no reason
-/// why Rojo couldn't expose an IPC or channels-based API for embedding in the
-/// future. `ServeSession` would be roughly the right interface to expose for
-/// those cases.
+/// Nothing here is specific to any Rojo interface. The primary way to interact
+/// with a serve session is Rojo's HTTP API, and it's exposed for embedding in
+/// other programs through [`embed::Session`](crate::embed::Session).
 pub struct ServeSession {
     /// The object responsible for listening to changes from the in-memory
     /// filesystem, applying them, updating the Roblox instance tree, and
@@ -86,6 +89,26 @@ pub struct ServeSession {
     /// A channel to send mutation requests on. These will be handled by the
     /// ChangeProcessor and trigger changes in the tree.
     tree_mutation_sender: Sender<PatchSet>,
+
+    /// A channel to ask the ChangeProcessor to resync the tree on. It sends
+    /// the result back through the given sender.
+    resync_sender: Sender<Sender<ResyncResult>>,
+
+    /// The most recent errors the VFS ran into while watching for changes,
+    /// oldest first. Changes may have been missed around them.
+    watcher_errors: Arc<Mutex<Vec<String>>>,
+
+    /// Problems with the project's files that kept the tree from being
+    /// updated, like invalid model files. Changes to these are sent to clients
+    /// along with changes to the tree.
+    diagnostics: Arc<Mutex<Diagnostics>>,
+
+    /// Whether changes sent through `tree_mutation_sender` are refused instead
+    /// of being written back to the filesystem.
+    read_only: Arc<AtomicBool>,
+
+    /// How long it's taken to turn changes into messages for clients.
+    metrics: Arc<Metrics>,
 }
 
 impl ServeSession {
@@ -124,16 +147,24 @@ impl ServeSession {
         let root_id = tree.get_root_id();
 
         let instance_context = InstanceContext::default();
+        let metrics = Arc::new(Metrics::new());
 
         log::trace!("Generating snapshot of instances from VFS");
-        let snapshot = snapshot_from_vfs(&instance_context, &vfs, &start_path)?
-            .expect("snapshot did not return an instance");
+        let snapshot = metrics
+            .time(Phase::Snapshot, || {
+                snapshot_from_vfs(&instance_context, &vfs, &start_path)
+            })?
+            .ok_or_else(|| ServeSessionError::EmptySnapshot {
+                path: start_path.to_path_buf(),
+            })?;
 
         log::trace!("Computing initial patch set");
-        let patch_set = compute_patch_set(&snapshot, &tree, root_id);
+        let patch_set = metrics.time(Phase::ComputePatch, || {
+            compute_patch_set(&snapshot, &tree, root_id)
+        });
 
         log::trace!("Applying initial patch set");
-        apply_patch_set(&mut tree, patch_set);
+        metrics.time(Phase::ApplyPatch, || apply_patch_set(&mut tree, patch_set));
 
         let session_id = SessionId::new();
         let message_queue = MessageQueue::new();
@@ -143,13 +174,28 @@ impl ServeSession {
         let vfs = Arc::new(vfs);
 
         let (tree_mutation_sender, tree_mutation_receiver) = crossbeam_channel::unbounded();
+        let (resync_sender, resync_receiver) = crossbeam_channel::unbounded();
+        let watcher_errors = Arc::new(Mutex::new(Vec::new()));
+
+        // Files that couldn't be read were left out of the snapshot, so the
+        // rest of the project can still be served until they're fixed.
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.replace_all(snapshot.all_diagnostics());
+        let diagnostics = Arc::new(Mutex::new(diagnostics));
+        let read_only = Arc::new(AtomicBool::new(false));
 
         log::trace!("Starting ChangeProcessor");
         let change_processor = ChangeProcessor::start(
             Arc::clone(&tree),
             Arc::clone(&vfs),
+            start_path.to_path_buf(),
             Arc::clone(&message_queue),
             tree_mutation_receiver,
+            resync_receiver,
+            Arc::clone(&watcher_errors),
+            Arc::clone(&diagnostics),
+            Arc::clone(&read_only),
+            Arc::clone(&metrics),
         );
 
         Ok(Self {
@@ -160,14 +206,15 @@ impl ServeSession {
             tree,
             message_queue,
             tree_mutation_sender,
+            resync_sender,
             vfs,
+            watcher_errors,
+            diagnostics,
+            read_only,
+            metrics,
         })
     }
 
-    pub fn tree_handle(&self) -> Arc<Mutex<RojoTree>> {
-        Arc::clone(&self.tree)
-    }
-
     pub fn tree(&self) -> MutexGuard<'_, RojoTree> {
         self.tree.lock().unwrap()
     }
@@ -185,6 +232,10 @@ impl ServeSession {
         &self.message_queue
     }
 
+    pub fn message_queue_handle(&self) -> Arc<MessageQueue<AppliedPatchSet>> {
+        Arc::clone(&self.message_queue)
+    }
+
     pub fn session_id(&self) -> SessionId {
         self.session_id
     }
@@ -212,6 +263,63 @@ impl ServeSession {
     pub fn serve_place_ids(&self) -> Option<&HashSet<u64>> {
         self.root_project.serve_place_ids.as_ref()
     }
+
+    /// Turns read-only mode on or off. While it's on, every change requested
+    /// through `tree_mutation_sender` is refused, so clients can't change any
+    /// files.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::SeqCst);
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn watcher_errors(&self) -> Vec<String> {
+        self.watcher_errors.lock().unwrap().clone()
+    }
+
+    /// Lists the session's current diagnostics, sorted by path.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.lock().unwrap().to_vec()
+    }
+
+    /// Fails if any of the project's files couldn't be read. Commands that
+    /// produce a file, like `rojo build`, use this so that a broken file isn't
+    /// silently left out of what they produce.
+    pub fn check_diagnostics(&self) -> Result<(), ServeSessionError> {
+        let diagnostics = self.diagnostics();
+
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(ServeSessionError::Diagnostics { diagnostics })
+        }
+    }
+
+    /// Rebuilds the whole tree from the filesystem and sends any differences
+    /// to clients, for when the tree has drifted from what's on disk, like
+    /// after the file watcher missed changes.
+    ///
+    /// Unlike restarting the session, the session ID and instance IDs are kept,
+    /// so connected clients stay connected. Watcher errors and diagnostics are
+    /// cleared since they no longer apply.
+    pub fn resync(&self) -> Result<AppliedPatchSet, ServeSessionError> {
+        // The change processor does the work so that the resync's message
+        // can't be pushed out of order with changes from the file watcher.
+        let (responder, receiver) = crossbeam_channel::bounded(1);
+
+        self.resync_sender
+            .send(responder)
+            .map_err(|_| ServeSessionError::Stopped)?;
+
+        let applied_patch_set = receiver.recv().map_err(|_| ServeSessionError::Stopped)??;
+        Ok(applied_patch_set)
+    }
 }
 
 #[derive(Debug, Error)]
@@ -223,6 +331,21 @@ pub enum ServeSessionError {
     )]
     NoProjectFound { path: PathBuf },
 
+    #[error(
+        "Some files in the project could not be read:\n{}",
+        format_diagnostics(.diagnostics)
+    )]
+    Diagnostics { diagnostics: Vec<Diagnostic> },
+
+    #[error(
+        "{} didn't produce an instance, so there's nothing to sync",
+        .path.display()
+    )]
+    EmptySnapshot { path: PathBuf },
+
+    #[error("The session has stopped processing changes")]
+    Stopped,
+
     #[error(transparent)]
     Io {
         #[from]
@@ -241,3 +364,11 @@ pub enum ServeSessionError {
         source: anyhow::Error,
     },
 }
+
+fn format_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
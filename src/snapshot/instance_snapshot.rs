@@ -8,6 +8,8 @@ use rbx_dom_weak::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::diagnostics::Diagnostic;
+
 use super::InstanceMetadata;
 
 /// A lightweight description of what an instance should look like.
@@ -37,6 +39,11 @@ pub struct InstanceSnapshot {
     ///
     /// Order is relevant for Roblox instances!
     pub children: Vec<InstanceSnapshot>,
+
+    /// Problems with files that were left out of this snapshot, or replaced
+    /// with placeholders, because they couldn't be read.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl InstanceSnapshot {
@@ -48,6 +55,7 @@ impl InstanceSnapshot {
             class_name: Cow::Borrowed("DEFAULT"),
             properties: HashMap::new(),
             children: Vec::new(),
+            diagnostics: Vec::new(),
         }
     }
 
@@ -102,6 +110,24 @@ impl InstanceSnapshot {
         }
     }
 
+    pub fn diagnostics(self, diagnostics: impl Into<Vec<Diagnostic>>) -> Self {
+        Self {
+            diagnostics: diagnostics.into(),
+            ..self
+        }
+    }
+
+    /// Gathers the diagnostics of this snapshot and all of its descendants.
+    pub fn all_diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.diagnostics.clone();
+
+        for child in &self.children {
+            diagnostics.extend(child.all_diagnostics());
+        }
+
+        diagnostics
+    }
+
     pub fn from_tree(tree: &WeakDom, id: Ref) -> Self {
         let instance = tree.get_by_ref(id).expect("instance did not exist in tree");
 
@@ -119,6 +145,7 @@ impl InstanceSnapshot {
             class_name: Cow::Owned(instance.class.clone()),
             properties: instance.properties.clone(),
             children,
+            diagnostics: Vec::new(),
         }
     }
 }
@@ -103,6 +103,11 @@ impl Default for InstanceMetadata {
 pub struct InstanceContext {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub path_ignore_rules: Arc<Vec<PathIgnoreRule>>,
+
+    /// Whether symbolic links encountered while reading directories should be
+    /// followed. If false, symlinked files and directories are skipped.
+    #[serde(default = "default_follow_symlinks", skip_serializing_if = "is_true")]
+    pub follow_symlinks: bool,
 }
 
 impl InstanceContext {
@@ -129,10 +134,19 @@ impl Default for InstanceContext {
     fn default() -> Self {
         InstanceContext {
             path_ignore_rules: Arc::new(Vec::new()),
+            follow_symlinks: default_follow_symlinks(),
         }
     }
 }
 
+fn default_follow_symlinks() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PathIgnoreRule {
     /// The path that this glob is relative to. Since ignore globs are defined
@@ -1,10 +1,12 @@
 //! Defines the data structures used for describing instance patches.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf};
 
 use rbx_dom_weak::types::{Ref, Variant};
 use serde::{Deserialize, Serialize};
 
+use crate::diagnostics::Diagnostic;
+
 use super::{InstanceMetadata, InstanceSnapshot};
 
 /// A set of different kinds of patches that can be applied to an WeakDom.
@@ -66,6 +68,20 @@ pub struct AppliedPatchSet {
     pub removed: Vec<Ref>,
     pub added: Vec<Ref>,
     pub updated: Vec<AppliedPatchUpdate>,
+
+    /// Problems found while making this change, each replacing any earlier
+    /// diagnostic for the same path.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reported_diagnostics: Vec<Diagnostic>,
+
+    /// Paths whose diagnostics were resolved by this change.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cleared_diagnostics: Vec<PathBuf>,
+
+    /// What led to this change, if it's known. Only used to show the history
+    /// of a session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cause: Option<PatchCause>,
 }
 
 impl AppliedPatchSet {
@@ -74,19 +90,43 @@ impl AppliedPatchSet {
             removed: Vec::new(),
             added: Vec::new(),
             updated: Vec::new(),
+            reported_diagnostics: Vec::new(),
+            cleared_diagnostics: Vec::new(),
+            cause: None,
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty()
+            && self.added.is_empty()
+            && self.updated.is_empty()
+            && self.reported_diagnostics.is_empty()
+            && self.cleared_diagnostics.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppliedPatchUpdate {
     pub id: Ref,
 
-    // TODO: Store previous values in order to detect application conflicts
     pub changed_name: Option<String>,
     pub changed_class_name: Option<String>,
     pub changed_properties: HashMap<String, Option<Variant>>,
     pub changed_metadata: Option<InstanceMetadata>,
+
+    // TODO: Use previous values to detect application conflicts
+    /// The name of the instance before it was changed, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_name: Option<String>,
+
+    /// The class name of the instance before it was changed, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_class_name: Option<String>,
+
+    /// The values that each changed property had before, or `None` if the
+    /// property didn't exist.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub previous_properties: HashMap<String, Option<Variant>>,
 }
 
 impl AppliedPatchUpdate {
@@ -97,6 +137,32 @@ impl AppliedPatchUpdate {
             changed_class_name: None,
             changed_properties: HashMap::new(),
             changed_metadata: None,
+            previous_name: None,
+            previous_class_name: None,
+            previous_properties: HashMap::new(),
         }
     }
 }
+
+/// What led to an `AppliedPatchSet`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PatchCause {
+    /// A file or directory was created.
+    FileCreated { path: PathBuf },
+
+    /// A file was changed.
+    FileChanged { path: PathBuf },
+
+    /// A file or directory was removed.
+    FileRemoved { path: PathBuf },
+
+    /// The watcher may have missed changes to this path, so it was read again.
+    FileRescanned { path: PathBuf },
+
+    /// A client asked for changes to be written back to files.
+    ClientWrite,
+
+    /// The whole project was read again.
+    Resync,
+}
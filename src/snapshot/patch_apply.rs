@@ -164,17 +164,19 @@ fn apply_update_child(context: &mut PatchApplyContext, tree: &mut RojoTree, patc
     };
 
     if let Some(name) = patch.changed_name {
-        *instance.name_mut() = name.clone();
+        let previous_name = std::mem::replace(instance.name_mut(), name.clone());
+        applied_patch.previous_name = Some(previous_name);
         applied_patch.changed_name = Some(name);
     }
 
     if let Some(class_name) = patch.changed_class_name {
-        *instance.class_name_mut() = class_name.clone();
+        let previous_class_name = std::mem::replace(instance.class_name_mut(), class_name.clone());
+        applied_patch.previous_class_name = Some(previous_class_name);
         applied_patch.changed_class_name = Some(class_name);
     }
 
     for (key, property_entry) in patch.changed_properties {
-        match property_entry {
+        let previous_value = match property_entry {
             // Ref values need to be potentially rewritten from snapshot IDs to
             // instance IDs if they referred to an instance that was created as
             // part of this patch.
@@ -195,16 +197,15 @@ fn apply_update_child(context: &mut PatchApplyContext, tree: &mut RojoTree, patc
 
                 instance
                     .properties_mut()
-                    .insert(key.clone(), Variant::Ref(new_referent));
+                    .insert(key.clone(), Variant::Ref(new_referent))
             }
-            Some(ref value) => {
-                instance.properties_mut().insert(key.clone(), value.clone());
-            }
-            None => {
-                instance.properties_mut().remove(&key);
-            }
-        }
+            Some(ref value) => instance.properties_mut().insert(key.clone(), value.clone()),
+            None => instance.properties_mut().remove(&key),
+        };
 
+        applied_patch
+            .previous_properties
+            .insert(key.clone(), previous_value);
         applied_patch.changed_properties.insert(key, property_entry);
     }
 
@@ -239,6 +240,7 @@ mod test {
                 "Baz".to_owned() => Variant::Int32(5),
             },
             children: Vec::new(),
+            diagnostics: Vec::new(),
         };
 
         let patch_set = PatchSet {
@@ -298,7 +300,7 @@ mod test {
             ..Default::default()
         };
 
-        apply_patch_set(&mut tree, patch_set);
+        let applied_patch_set = apply_patch_set(&mut tree, patch_set);
 
         let expected_properties = hashmap! {
             "Foo".to_owned() => Variant::Int32(8),
@@ -310,5 +312,22 @@ mod test {
         assert_eq!(root_instance.name(), "Foo");
         assert_eq!(root_instance.class_name(), "NewClassName");
         assert_eq!(root_instance.properties(), &expected_properties);
+
+        let expected_previous_properties = hashmap! {
+            "Foo".to_owned() => Some(Variant::Int32(7)),
+            "Bar".to_owned() => Some(Variant::Int32(3)),
+            "Baz".to_owned() => None,
+        };
+
+        let applied_update = &applied_patch_set.updated[0];
+        assert_eq!(applied_update.previous_name.as_deref(), Some("OldName"));
+        assert_eq!(
+            applied_update.previous_class_name.as_deref(),
+            Some("OldClassName")
+        );
+        assert_eq!(
+            applied_update.previous_properties,
+            expected_previous_properties
+        );
     }
 }
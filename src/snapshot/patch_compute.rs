@@ -244,6 +244,7 @@ mod test {
             name: Cow::Borrowed("foo"),
             class_name: Cow::Borrowed("foo"),
             children: Vec::new(),
+            diagnostics: Vec::new(),
         };
 
         let patch_set = compute_patch_set(&snapshot, &tree, root_id);
@@ -288,12 +289,14 @@ mod test {
                 name: Cow::Borrowed("child"),
                 class_name: Cow::Borrowed("child"),
                 children: Vec::new(),
+                diagnostics: Vec::new(),
             }],
 
             metadata: Default::default(),
             properties: HashMap::new(),
             name: Cow::Borrowed("foo"),
             class_name: Cow::Borrowed("foo"),
+            diagnostics: Vec::new(),
         };
 
         let patch_set = compute_patch_set(&snapshot, &tree, root_id);
@@ -310,6 +313,7 @@ mod test {
                     name: Cow::Borrowed("child"),
                     class_name: Cow::Borrowed("child"),
                     children: Vec::new(),
+                    diagnostics: Vec::new(),
                 },
             }],
             updated_instances: Vec::new(),
@@ -21,6 +21,7 @@ fn set_name_and_class_name() {
         class_name: Cow::Borrowed("Folder"),
         properties: Default::default(),
         children: Vec::new(),
+        diagnostics: Vec::new(),
     };
 
     let patch_set = compute_patch_set(&snapshot, &tree, tree.get_root_id());
@@ -45,6 +46,7 @@ fn set_property() {
             "PropertyName".to_owned() => "Hello, world!".into(),
         },
         children: Vec::new(),
+        diagnostics: Vec::new(),
     };
 
     let patch_set = compute_patch_set(&snapshot, &tree, tree.get_root_id());
@@ -76,6 +78,7 @@ fn remove_property() {
         class_name: Cow::Borrowed("ROOT"),
         properties: Default::default(),
         children: Vec::new(),
+        diagnostics: Vec::new(),
     };
 
     let patch_set = compute_patch_set(&snapshot, &tree, tree.get_root_id());
@@ -104,7 +107,9 @@ fn add_child() {
             class_name: Cow::Borrowed("Folder"),
             properties: Default::default(),
             children: Vec::new(),
+            diagnostics: Vec::new(),
         }],
+        diagnostics: Vec::new(),
     };
 
     let patch_set = compute_patch_set(&snapshot, &tree, tree.get_root_id());
@@ -137,6 +142,7 @@ fn remove_child() {
         class_name: Cow::Borrowed("ROOT"),
         properties: Default::default(),
         children: Vec::new(),
+        diagnostics: Vec::new(),
     };
 
     let patch_set = compute_patch_set(&snapshot, &tree, tree.get_root_id());
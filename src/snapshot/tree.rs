@@ -156,6 +156,11 @@ impl RojoTree {
         self.path_to_ids.get(path)
     }
 
+    /// How many instances are in the tree, including the root.
+    pub fn instance_count(&self) -> usize {
+        self.metadata_map.len()
+    }
+
     pub fn get_metadata(&self, id: Ref) -> Option<&InstanceMetadata> {
         self.metadata_map.get(&id)
     }
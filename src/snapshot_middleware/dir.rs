@@ -2,7 +2,10 @@ use std::path::Path;
 
 use memofs::{DirEntry, IoResultExt, Vfs};
 
-use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+use crate::{
+    diagnostics::Diagnostic,
+    snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot},
+};
 
 use super::{meta_file::DirectoryMetadata, middleware::SnapshotInstanceResult, snapshot_from_vfs};
 
@@ -15,6 +18,7 @@ pub fn snapshot_dir(context: &InstanceContext, vfs: &Vfs, path: &Path) -> Snapsh
     };
 
     let mut snapshot_children = Vec::new();
+    let mut diagnostics = Vec::new();
 
     for entry in vfs.read_dir(path)? {
         let entry = entry?;
@@ -23,8 +27,23 @@ pub fn snapshot_dir(context: &InstanceContext, vfs: &Vfs, path: &Path) -> Snapsh
             continue;
         }
 
-        if let Some(child_snapshot) = snapshot_from_vfs(context, vfs, entry.path())? {
-            snapshot_children.push(child_snapshot);
+        if !passes_symlink_rules(context, vfs, entry.path())? {
+            continue;
+        }
+
+        // A child that can't be read is left out instead of failing the whole
+        // directory. Changes to it will snapshot this directory again, which
+        // brings it back once it's fixed.
+        match snapshot_from_vfs(context, vfs, entry.path()) {
+            Ok(Some(child_snapshot)) => snapshot_children.push(child_snapshot),
+            Ok(None) => {}
+            Err(err) => {
+                log::warn!("{:?}", err);
+                diagnostics.push(Diagnostic {
+                    path: entry.path().to_path_buf(),
+                    message: format!("{:#}", err),
+                });
+            }
         }
     }
 
@@ -52,6 +71,7 @@ pub fn snapshot_dir(context: &InstanceContext, vfs: &Vfs, path: &Path) -> Snapsh
         .name(instance_name)
         .class_name("Folder")
         .children(snapshot_children)
+        .diagnostics(diagnostics)
         .metadata(
             InstanceMetadata::new()
                 .instigating_source(path)
@@ -67,6 +87,47 @@ pub fn snapshot_dir(context: &InstanceContext, vfs: &Vfs, path: &Path) -> Snapsh
     Ok(Some(snapshot))
 }
 
+/// Decides whether a directory entry should be snapshotted based on whether
+/// it's a symbolic link.
+///
+/// Symlinks are skipped entirely if the context says not to follow them.
+/// Symlinked directories that point back to one of their own ancestors are
+/// always skipped, since following them would recurse forever.
+fn passes_symlink_rules(context: &InstanceContext, vfs: &Vfs, path: &Path) -> anyhow::Result<bool> {
+    let meta = match vfs.metadata(path).with_not_found()? {
+        Some(meta) => meta,
+        None => return Ok(true),
+    };
+
+    if !meta.is_symlink() {
+        return Ok(true);
+    }
+
+    if !context.follow_symlinks {
+        log::trace!("Skipping symlink {}", path.display());
+        return Ok(false);
+    }
+
+    if meta.is_dir() {
+        let target = vfs.canonicalize(path)?;
+
+        for ancestor in path.ancestors().skip(1) {
+            if let Some(canonical_ancestor) = vfs.canonicalize(ancestor).with_not_found()? {
+                if canonical_ancestor == target {
+                    log::warn!(
+                        "Skipping symlink {} because it points to its ancestor {}",
+                        path.display(),
+                        ancestor.display()
+                    );
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    Ok(true)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -110,4 +171,82 @@ mod test {
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn symlinked_folder() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Child" => VfsSnapshot::symlink("/shared"),
+            }),
+        )
+        .unwrap();
+        imfs.load_snapshot(
+            "/shared",
+            VfsSnapshot::dir(hashmap! {
+                "Grandchild" => VfsSnapshot::empty_dir(),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let instance_snapshot =
+            snapshot_dir(&InstanceContext::default(), &mut vfs, Path::new("/foo"))
+                .unwrap()
+                .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn symlinked_folder_not_followed() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Child" => VfsSnapshot::symlink("/shared"),
+            }),
+        )
+        .unwrap();
+        imfs.load_snapshot("/shared", VfsSnapshot::empty_dir())
+            .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let context = InstanceContext {
+            follow_symlinks: false,
+            ..InstanceContext::default()
+        };
+
+        let instance_snapshot = snapshot_dir(&context, &mut vfs, Path::new("/foo"))
+            .unwrap()
+            .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn symlink_cycle() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Child" => VfsSnapshot::dir(hashmap! {
+                    "Loop" => VfsSnapshot::symlink(".."),
+                }),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let instance_snapshot =
+            snapshot_dir(&InstanceContext::default(), &mut vfs, Path::new("/foo"))
+                .unwrap()
+                .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
 }
@@ -94,6 +94,7 @@ impl JsonModelCore {
             class_name: Cow::Owned(class_name),
             properties,
             children,
+            diagnostics: Vec::new(),
         })
     }
 }
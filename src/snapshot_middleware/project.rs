@@ -5,6 +5,7 @@ use memofs::Vfs;
 use rbx_reflection::ClassTag;
 
 use crate::{
+    diagnostics::Diagnostic,
     project::{Project, ProjectNode},
     snapshot::{
         InstanceContext, InstanceMetadata, InstanceSnapshot, InstigatingSource, PathIgnoreRule,
@@ -30,6 +31,10 @@ pub fn snapshot_project(
 
     context.add_path_ignore_rules(rules);
 
+    if let Some(follow_symlinks) = project.follow_symlinks {
+        context.follow_symlinks = follow_symlinks;
+    }
+
     // TODO: If this project node is a path to an instance that Rojo doesn't
     // understand, this may panic!
     let mut snapshot =
@@ -76,6 +81,7 @@ pub fn snapshot_project_node(
     let mut properties = HashMap::new();
     let mut children = Vec::new();
     let mut metadata = InstanceMetadata::default();
+    let mut diagnostics = Vec::new();
 
     if let Some(path) = &node.path {
         // If the path specified in the project is relative, we assume it's
@@ -106,6 +112,7 @@ pub fn snapshot_project_node(
             // Take the snapshot's metadata as-is, which will be mutated later
             // on.
             metadata = snapshot.metadata;
+            diagnostics = snapshot.diagnostics;
         } else {
             // TODO: Should this issue an error instead?
             log::warn!(
@@ -176,15 +183,29 @@ pub fn snapshot_project_node(
     };
 
     for (child_name, child_project_node) in &node.children {
-        if let Some(child) = snapshot_project_node(
+        let child_result = snapshot_project_node(
             context,
             project_path,
             child_name,
             child_project_node,
             vfs,
             Some(&class_name),
-        )? {
-            children.push(child);
+        );
+
+        match child_result {
+            Ok(Some(child)) => children.push(child),
+            Ok(None) => {}
+            Err(err) => {
+                log::warn!("{:?}", err);
+                children.push(snapshot_placeholder(
+                    context,
+                    project_path,
+                    child_name,
+                    child_project_node,
+                    Some(&class_name),
+                    format!("{:#}", err),
+                ));
+            }
         }
     }
 
@@ -244,9 +265,58 @@ pub fn snapshot_project_node(
         properties,
         children,
         metadata,
+        diagnostics,
     }))
 }
 
+/// Stands in for a project node that couldn't be snapshotted, so that the rest
+/// of the project can still be served. The placeholder is tied to the node's
+/// `$path`, so changing the broken file snapshots the node again.
+fn snapshot_placeholder(
+    context: &InstanceContext,
+    project_path: &Path,
+    instance_name: &str,
+    node: &ProjectNode,
+    parent_class: Option<&str>,
+    message: String,
+) -> InstanceSnapshot {
+    let project_folder = project_path.parent().unwrap();
+
+    // Problems with nodes that don't have a $path can only come from the
+    // project file itself.
+    let path = match &node.path {
+        Some(path) => project_folder.join(path),
+        None => project_path.to_path_buf(),
+    };
+
+    let class_name = node
+        .class_name
+        .clone()
+        .or_else(|| infer_class_name(instance_name, parent_class).map(Cow::into_owned))
+        .unwrap_or_else(|| "Folder".to_owned());
+
+    let mut relevant_paths = Vec::new();
+    if node.path.is_some() {
+        relevant_paths.push(path.clone());
+    }
+
+    InstanceSnapshot::new()
+        .name(instance_name)
+        .class_name(class_name)
+        .diagnostics(vec![Diagnostic { path, message }])
+        .metadata(
+            InstanceMetadata::new()
+                .instigating_source(InstigatingSource::ProjectNode(
+                    project_path.to_path_buf(),
+                    instance_name.to_owned(),
+                    node.clone(),
+                    parent_class.map(|name| name.to_owned()),
+                ))
+                .relevant_paths(relevant_paths)
+                .context(context),
+        )
+}
+
 fn infer_class_name(name: &str, parent_class: Option<&str>) -> Option<Cow<'static, str>> {
     // If className wasn't defined from another source, we may be able
     // to infer one.
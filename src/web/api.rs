@@ -1,49 +1,91 @@
 //! Defines Rojo's HTTP API, all under /api. These endpoints generally return
 //! JSON.
 
-use std::{collections::HashMap, fs, path::PathBuf, str::FromStr, sync::Arc};
+use std::{collections::HashMap, fs, path::PathBuf, str::FromStr, sync::Arc, time::Instant};
 
-use futures::{Future, Stream};
-
-use hyper::{service::Service, Body, Method, Request, StatusCode};
+use hyper::{Body, Method, Request, Response, StatusCode};
 use rbx_dom_weak::types::Ref;
+use tokio::sync::watch;
 
 use crate::{
-    serve_session::ServeSession,
-    snapshot::{InstanceWithMeta, PatchSet, PatchUpdate},
+    metrics::Phase,
+    serve_session::{ServeSession, ServeSessionError},
+    snapshot::{InstanceWithMeta, PatchSet, PatchUpdate, RojoTree},
     web::{
         interface::{
-            ErrorResponse, Instance, InstanceMetadata as WebInstanceMetadata, InstanceUpdate,
-            OpenResponse, ReadResponse, ServerInfoResponse, SubscribeMessage, SubscribeResponse,
-            WriteRequest, WriteResponse, PROTOCOL_VERSION, SERVER_VERSION,
+            collect_shared_strings, DiagnosticsResponse, ErrorResponse, HistoryEntry,
+            HistoryResponse, Instance, InstanceMetadata as WebInstanceMetadata, InstanceUpdate,
+            OpenResponse, ProjectInfo, ReadResponse, ResyncResponse, SearchResponse,
+            ServerInfoResponse, SharedStrings, SubscribeMessage, SubscribeResponse, WriteRequest,
+            WriteResponse, PROTOCOL_VERSION, SERVER_VERSION,
         },
-        util::{json, json_ok},
+        util::{json, json_ok, percent_decode, query_param},
     },
 };
 
+/// The most matches `/api/search` sends back, so that searching for something
+/// common in a large place stays fast.
+const MAX_SEARCH_MATCHES: usize = 200;
+
+/// How many messages `/api/history` sends back at once.
+pub const HISTORY_PAGE_SIZE: u32 = 100;
+
+/// The first path segment after `/api/` of every route, which project names
+/// can't take over when serving several projects.
+pub const API_ROUTES: &[&str] = &[
+    "rojo",
+    "read",
+    "subscribe",
+    "open",
+    "write",
+    "resync",
+    "diagnostics",
+    "search",
+    "history",
+];
+
 pub struct ApiService {
     serve_session: Arc<ServeSession>,
+
+    /// Every session served alongside this one, including itself.
+    all_sessions: Arc<[Arc<ServeSession>]>,
+
+    /// Changes once the server starts shutting down, which tells any clients
+    /// waiting on new messages that the session is ending.
+    shutdown: watch::Receiver<bool>,
 }
 
-impl Service for ApiService {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = hyper::Error;
-    type Future =
-        Box<dyn Future<Item = hyper::Response<Self::ReqBody>, Error = Self::Error> + Send>;
+impl ApiService {
+    pub fn new(
+        serve_session: Arc<ServeSession>,
+        all_sessions: Arc<[Arc<ServeSession>]>,
+        shutdown: watch::Receiver<bool>,
+    ) -> Self {
+        ApiService {
+            serve_session,
+            all_sessions,
+            shutdown,
+        }
+    }
 
-    fn call(&mut self, request: hyper::Request<Self::ReqBody>) -> Self::Future {
+    pub async fn call(&self, request: Request<Body>) -> Response<Body> {
         match (request.method(), request.uri().path()) {
             (&Method::GET, "/api/rojo") => self.handle_api_rojo(),
             (&Method::GET, path) if path.starts_with("/api/read/") => self.handle_api_read(request),
+            (&Method::GET, path) if path.starts_with("/api/search/") => {
+                self.handle_api_search(request)
+            }
             (&Method::GET, path) if path.starts_with("/api/subscribe/") => {
-                self.handle_api_subscribe(request)
+                self.handle_api_subscribe(request).await
             }
             (&Method::POST, path) if path.starts_with("/api/open/") => {
                 self.handle_api_open(request)
             }
 
-            (&Method::POST, "/api/write") => self.handle_api_write(request),
+            (&Method::POST, "/api/write") => self.handle_api_write(request).await,
+            (&Method::POST, "/api/resync") => self.handle_api_resync().await,
+            (&Method::GET, "/api/diagnostics") => self.handle_api_diagnostics(),
+            (&Method::GET, "/api/history") => self.handle_api_history(request),
 
             (_method, path) => json(
                 ErrorResponse::not_found(format!("Route not found: {}", path)),
@@ -51,17 +93,10 @@ impl Service for ApiService {
             ),
         }
     }
-}
-
-impl ApiService {
-    pub fn new(serve_session: Arc<ServeSession>) -> Self {
-        ApiService { serve_session }
-    }
 
     /// Get a summary of information about the server
-    fn handle_api_rojo(&self) -> <Self as Service>::Future {
-        let tree = self.serve_session.tree();
-        let root_instance_id = tree.get_root_id();
+    fn handle_api_rojo(&self) -> Response<Body> {
+        let root_instance_id = self.serve_session.tree().get_root_id();
 
         json_ok(&ServerInfoResponse {
             server_version: SERVER_VERSION.to_owned(),
@@ -72,12 +107,34 @@ impl ApiService {
             place_id: self.serve_session.place_id(),
             game_id: self.serve_session.game_id(),
             root_instance_id,
+            watcher_errors: self.serve_session.watcher_errors(),
+            read_only: self.serve_session.is_read_only(),
+            projects: self.project_infos(),
         })
     }
 
+    /// Lists every project served by this server, if there's more than one.
+    fn project_infos(&self) -> Vec<ProjectInfo> {
+        if self.all_sessions.len() < 2 {
+            return Vec::new();
+        }
+
+        self.all_sessions
+            .iter()
+            .map(|session| ProjectInfo {
+                session_id: session.session_id(),
+                project_name: session.project_name().to_owned(),
+                expected_place_ids: session.serve_place_ids().cloned(),
+                game_id: session.game_id(),
+                place_id: session.place_id(),
+                root_instance_id: session.tree().get_root_id(),
+            })
+            .collect()
+    }
+
     /// Retrieve any messages past the given cursor index, and if
     /// there weren't any, subscribe to receive any new messages.
-    fn handle_api_subscribe(&self, request: Request<Body>) -> <Self as Service>::Future {
+    async fn handle_api_subscribe(&self, request: Request<Body>) -> Response<Body> {
         let argument = &request.uri().path()["/api/subscribe/".len()..];
         let input_cursor: u32 = match argument.parse() {
             Ok(v) => v,
@@ -92,12 +149,26 @@ impl ApiService {
         let session_id = self.serve_session.session_id();
 
         let receiver = self.serve_session.message_queue().subscribe(input_cursor);
+        let mut shutdown = self.shutdown.clone();
 
-        let tree_handle = self.serve_session.tree_handle();
+        let result = tokio::select! {
+            result = receiver => result,
+            _ = shutdown.changed() => {
+                return json_ok(SubscribeResponse {
+                    session_id,
+                    message_cursor: input_cursor,
+                    messages: Vec::new(),
+                    shared_strings: SharedStrings::new(),
+                    watcher_errors: Vec::new(),
+                    session_ending: true,
+                });
+            }
+        };
 
-        Box::new(receiver.then(move |result| match result {
+        match result {
             Ok((message_cursor, messages)) => {
-                let tree = tree_handle.lock().unwrap();
+                let start = Instant::now();
+                let tree = self.serve_session.tree();
 
                 let api_messages = messages
                     .into_iter()
@@ -123,11 +194,15 @@ impl ApiService {
                                     .as_ref()
                                     .map(WebInstanceMetadata::from_rojo_metadata);
 
+                                let (set_properties, removed_properties) =
+                                    InstanceUpdate::split_properties(update.changed_properties);
+
                                 InstanceUpdate {
                                     id: update.id,
                                     changed_name: update.changed_name,
                                     changed_class_name: update.changed_class_name,
-                                    changed_properties: update.changed_properties,
+                                    set_properties,
+                                    removed_properties,
                                     changed_metadata,
                                 }
                             })
@@ -137,70 +212,170 @@ impl ApiService {
                             removed,
                             added,
                             updated,
+                            reported_diagnostics: message.reported_diagnostics,
+                            cleared_diagnostics: message.cleared_diagnostics,
                         }
                     })
-                    .collect();
+                    .collect::<Vec<_>>();
 
-                json_ok(SubscribeResponse {
+                let mut shared_strings = SharedStrings::new();
+                for message in &api_messages {
+                    collect_instance_shared_strings(message.added.values(), &mut shared_strings);
+
+                    let changed_values = message
+                        .updated
+                        .iter()
+                        .flat_map(|update| update.set_properties.values());
+                    collect_shared_strings(changed_values, &mut shared_strings);
+                }
+
+                let response = json_ok(SubscribeResponse {
                     session_id,
                     message_cursor,
                     messages: api_messages,
-                })
+                    shared_strings,
+                    watcher_errors: self.serve_session.watcher_errors(),
+                    session_ending: false,
+                });
+
+                self.serve_session
+                    .metrics()
+                    .record(Phase::Serialize, start.elapsed());
+
+                response
             }
             Err(_) => json(
                 ErrorResponse::internal_error("Message queue disconnected sender"),
                 StatusCode::INTERNAL_SERVER_ERROR,
             ),
-        }))
+        }
     }
 
-    fn handle_api_write(&self, request: Request<Body>) -> <Self as Service>::Future {
+    /// List the problems with project files that are keeping parts of the tree
+    /// from syncing.
+    fn handle_api_diagnostics(&self) -> Response<Body> {
+        // The cursor is read first so that clients who subscribe from it won't
+        // miss changes made while the diagnostics are being listed.
+        let message_cursor = self.serve_session.message_queue().cursor();
+
+        json_ok(DiagnosticsResponse {
+            session_id: self.serve_session.session_id(),
+            message_cursor,
+            diagnostics: self.serve_session.diagnostics(),
+        })
+    }
+
+    /// List the changes made to the tree so far, newest first. The query string
+    /// can ask for older changes with `before`, a message cursor.
+    fn handle_api_history(&self, request: Request<Body>) -> Response<Body> {
+        let before = query_param(request.uri().query(), "before").map(|before| before.parse());
+        let before = match before {
+            None => None,
+            Some(Ok(before)) => Some(before),
+            Some(Err(err)) => {
+                return json(
+                    ErrorResponse::bad_request(format!("Malformed message cursor: {}", err)),
+                    StatusCode::BAD_REQUEST,
+                );
+            }
+        };
+
+        json_ok(read_history(&self.serve_session, before))
+    }
+
+    /// Rebuild the tree from the filesystem and send any differences to
+    /// subscribers, without restarting the session.
+    async fn handle_api_resync(&self) -> Response<Body> {
+        // Resyncing waits for the whole project to be snapshotted again, which
+        // would hold up other requests on this worker thread.
+        let serve_session = Arc::clone(&self.serve_session);
+        let result = tokio::task::spawn_blocking(move || serve_session.resync())
+            .await
+            .unwrap_or(Err(ServeSessionError::Stopped));
+
+        if let Err(err) = result {
+            return json(
+                ErrorResponse::internal_error(format!("Could not resync: {}", err)),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            );
+        }
+
+        json_ok(ResyncResponse {
+            session_id: self.serve_session.session_id(),
+            message_cursor: self.serve_session.message_queue().cursor(),
+        })
+    }
+
+    async fn handle_api_write(&self, request: Request<Body>) -> Response<Body> {
+        if self.serve_session.is_read_only() {
+            return json(
+                ErrorResponse::forbidden(
+                    "This server is read-only, so changes can't be written back to files",
+                ),
+                StatusCode::FORBIDDEN,
+            );
+        }
+
         let session_id = self.serve_session.session_id();
         let tree_mutation_sender = self.serve_session.tree_mutation_sender();
 
-        Box::new(request.into_body().concat2().and_then(move |body| {
-            let request: WriteRequest = match serde_json::from_slice(&body) {
-                Ok(request) => request,
-                Err(err) => {
-                    return json(
-                        ErrorResponse::bad_request(format!("Invalid body: {}", err)),
-                        StatusCode::BAD_REQUEST,
-                    );
-                }
-            };
+        let body = match hyper::body::to_bytes(request.into_body()).await {
+            Ok(body) => body,
+            Err(err) => {
+                return json(
+                    ErrorResponse::bad_request(format!("Could not read body: {}", err)),
+                    StatusCode::BAD_REQUEST,
+                );
+            }
+        };
 
-            if request.session_id != session_id {
+        let request: WriteRequest = match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(err) => {
                 return json(
-                    ErrorResponse::bad_request("Wrong session ID"),
+                    ErrorResponse::bad_request(format!("Invalid body: {}", err)),
                     StatusCode::BAD_REQUEST,
                 );
             }
+        };
+
+        if request.session_id != session_id {
+            return json(
+                ErrorResponse::bad_request("Wrong session ID"),
+                StatusCode::BAD_REQUEST,
+            );
+        }
 
-            let updated_instances = request
-                .updated
-                .into_iter()
-                .map(|update| PatchUpdate {
-                    id: update.id,
-                    changed_class_name: update.changed_class_name,
-                    changed_name: update.changed_name,
-                    changed_properties: update.changed_properties,
-                    changed_metadata: None,
-                })
-                .collect();
-
-            tree_mutation_sender
-                .send(PatchSet {
-                    removed_instances: Vec::new(),
-                    added_instances: Vec::new(),
-                    updated_instances,
-                })
-                .unwrap();
-
-            json_ok(&WriteResponse { session_id })
-        }))
+        let updated_instances = request
+            .updated
+            .into_iter()
+            .map(|update| PatchUpdate {
+                id: update.id,
+                changed_class_name: update.changed_class_name,
+                changed_name: update.changed_name,
+                changed_properties: InstanceUpdate::join_properties(
+                    update.set_properties,
+                    update.removed_properties,
+                ),
+                changed_metadata: None,
+            })
+            .collect();
+
+        tree_mutation_sender
+            .send(PatchSet {
+                removed_instances: Vec::new(),
+                added_instances: Vec::new(),
+                updated_instances,
+            })
+            .unwrap();
+
+        json_ok(&WriteResponse { session_id })
     }
 
-    fn handle_api_read(&self, request: Request<Body>) -> <Self as Service>::Future {
+    /// Read the given instances and their descendants. The query string can
+    /// limit how many levels of descendants are sent with `depth`, and ask for
+    /// each instance's relevant paths and instigating source with `sources`.
+    fn handle_api_read(&self, request: Request<Body>) -> Response<Body> {
         let argument = &request.uri().path()["/api/read/".len()..];
         let requested_ids: Result<Vec<Ref>, _> = argument.split(',').map(Ref::from_str).collect();
 
@@ -214,32 +389,100 @@ impl ApiService {
             }
         };
 
+        let query = request.uri().query();
+        let depth = match query_param(query, "depth").map(|depth| depth.parse::<usize>()) {
+            None => None,
+            Some(Ok(depth)) => Some(depth),
+            Some(Err(err)) => {
+                return json(
+                    ErrorResponse::bad_request(format!("Malformed depth: {}", err)),
+                    StatusCode::BAD_REQUEST,
+                );
+            }
+        };
+        let sources = query_param(query, "sources").as_deref() == Some("true");
+
         let message_queue = self.serve_session.message_queue();
         let message_cursor = message_queue.cursor();
 
+        // Serializing a large place takes a while, so the instances are copied
+        // out of the tree to let it go first. Otherwise, changes would wait on
+        // the read to finish.
+        let mut instances = HashMap::new();
+        {
+            let tree = self.serve_session.tree();
+
+            for id in requested_ids {
+                add_instances(&tree, id, depth, sources, &mut instances);
+            }
+        }
+
+        let mut shared_strings = SharedStrings::new();
+        collect_instance_shared_strings(instances.values(), &mut shared_strings);
+
+        json_ok(ReadResponse {
+            session_id: self.serve_session.session_id(),
+            message_cursor,
+            instances,
+            shared_strings,
+        })
+    }
+
+    /// Find instances whose name, class name, or relevant paths contain the
+    /// given text, ignoring case.
+    fn handle_api_search(&self, request: Request<Body>) -> Response<Body> {
+        let argument = &request.uri().path()["/api/search/".len()..];
+        let search = match percent_decode(argument) {
+            Some(search) if !search.is_empty() => search.to_lowercase(),
+            _ => {
+                return json(
+                    ErrorResponse::bad_request("Missing or malformed search"),
+                    StatusCode::BAD_REQUEST,
+                );
+            }
+        };
+
+        let message_cursor = self.serve_session.message_queue().cursor();
         let tree = self.serve_session.tree();
+        let root_id = tree.get_root_id();
 
-        let mut instances = HashMap::new();
+        let mut found = tree
+            .descendants(root_id)
+            .filter(|instance| instance_matches(instance, &search))
+            .map(|instance| instance.id());
 
-        for id in requested_ids {
-            if let Some(instance) = tree.get_instance(id) {
-                instances.insert(id, Instance::from_rojo_instance(instance));
+        let matches: Vec<Ref> = found.by_ref().take(MAX_SEARCH_MATCHES).collect();
+        let truncated = found.next().is_some();
 
-                for descendant in tree.descendants(id) {
-                    instances.insert(descendant.id(), Instance::from_rojo_instance(descendant));
+        let mut instances = HashMap::new();
+        for &id in &matches {
+            let mut current = id;
+
+            while let Some(instance) = tree.get_instance(current) {
+                if instances.contains_key(&current) {
+                    break;
                 }
+
+                instances.insert(current, web_instance(instance, true));
+                current = instance.parent();
             }
         }
 
-        json_ok(ReadResponse {
+        let mut shared_strings = SharedStrings::new();
+        collect_instance_shared_strings(instances.values(), &mut shared_strings);
+
+        json_ok(SearchResponse {
             session_id: self.serve_session.session_id(),
             message_cursor,
+            matches,
+            truncated,
             instances,
+            shared_strings,
         })
     }
 
     /// Open a script with the given ID in the user's default text editor.
-    fn handle_api_open(&self, request: Request<Body>) -> <Self as Service>::Future {
+    fn handle_api_open(&self, request: Request<Body>) -> Response<Body> {
         let argument = &request.uri().path()["/api/open/".len()..];
         let requested_id = match Ref::from_str(argument) {
             Ok(id) => id,
@@ -283,6 +526,108 @@ impl ApiService {
     }
 }
 
+/// Reads up to `HISTORY_PAGE_SIZE` messages from before the given cursor, or
+/// the most recent ones if there's no cursor.
+pub fn read_history(serve_session: &ServeSession, before: Option<u32>) -> HistoryResponse {
+    let message_queue = serve_session.message_queue();
+    let message_cursor = message_queue.cursor();
+
+    let end = before.unwrap_or(message_cursor).min(message_cursor);
+    let start = end.saturating_sub(HISTORY_PAGE_SIZE);
+    let messages = message_queue.history(start..end);
+
+    let tree = serve_session.tree();
+    let entries = (start..end)
+        .zip(messages)
+        .rev()
+        .map(|(index, (pushed_at, message))| HistoryEntry::new(index, pushed_at, message, &tree))
+        .collect::<Vec<_>>();
+
+    let mut shared_strings = SharedStrings::new();
+    for entry in &entries {
+        let changes = entry.updated.iter().flat_map(|update| &update.changes);
+        let values = changes.flat_map(|change| change.before.iter().chain(&change.after));
+        collect_shared_strings(values, &mut shared_strings);
+    }
+
+    HistoryResponse {
+        session_id: serve_session.session_id(),
+        message_cursor,
+        entries,
+        more: start > 0,
+        shared_strings,
+    }
+}
+
+fn collect_instance_shared_strings<'i, 'a: 'i>(
+    instances: impl IntoIterator<Item = &'i Instance<'a>>,
+    shared_strings: &mut SharedStrings,
+) {
+    for instance in instances {
+        let values = instance.properties.values().map(|value| value.as_ref());
+        collect_shared_strings(values, shared_strings);
+    }
+}
+
+/// Adds the given instance and up to `depth` levels of its descendants, or all
+/// of them if there's no depth.
+fn add_instances(
+    tree: &RojoTree,
+    id: Ref,
+    depth: Option<usize>,
+    sources: bool,
+    instances: &mut HashMap<Ref, Instance<'static>>,
+) {
+    let instance = match tree.get_instance(id) {
+        Some(instance) => instance,
+        None => return,
+    };
+
+    let depth = match depth {
+        Some(depth) => depth,
+        None => {
+            // Descendants include the instance itself.
+            for descendant in tree.descendants(id) {
+                instances.insert(
+                    descendant.id(),
+                    web_instance(descendant, sources).into_owned(),
+                );
+            }
+            return;
+        }
+    };
+
+    instances.insert(id, web_instance(instance, sources).into_owned());
+
+    if depth > 0 {
+        for &child_id in instance.children() {
+            add_instances(tree, child_id, Some(depth - 1), sources, instances);
+        }
+    }
+}
+
+fn web_instance(instance: InstanceWithMeta<'_>, sources: bool) -> Instance<'_> {
+    let mut web_instance = Instance::from_rojo_instance(instance);
+
+    if sources {
+        web_instance.metadata = Some(WebInstanceMetadata::from_rojo_metadata_with_sources(
+            instance.metadata(),
+        ));
+    }
+
+    web_instance
+}
+
+fn instance_matches(instance: &InstanceWithMeta<'_>, search: &str) -> bool {
+    instance.name().to_lowercase().contains(search)
+        || instance.class_name().to_lowercase().contains(search)
+        || instance
+            .metadata()
+            .relevant_paths
+            .iter()
+            .any(|path| path.to_string_lossy().to_lowercase().contains(search))
+}
+
 /// If this instance is represented by a script, try to find the correct .lua
 /// file to open to edit it.
 fn pick_script_path(instance: InstanceWithMeta<'_>) -> Option<PathBuf> {
@@ -27,6 +27,8 @@ macro_rules! declare_asset {
 }
 
 declare_asset!(css, "../../assets/index.css");
+declare_asset!(show_instances_js, "../../assets/show-instances.js");
+declare_asset!(history_js, "../../assets/history.js");
 
 pub fn logo() -> &'static [u8] {
     static LOGO: &[u8] = include_bytes!("../../assets/logo-512.png");
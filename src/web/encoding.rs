@@ -0,0 +1,197 @@
+//! Compresses responses with gzip or deflate if the client allows it, which
+//! matters most for the first read of a large place, since it can be hundreds
+//! of megabytes of JSON.
+
+use std::io::Write;
+
+use flate2::write::{GzEncoder, ZlibEncoder};
+use hyper::{
+    header::{
+        HeaderMap, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+        VARY,
+    },
+    Body, Response, StatusCode,
+};
+
+/// Responses smaller than this are sent uncompressed, since compressing them
+/// wouldn't save much.
+const MIN_COMPRESSED_SIZE: usize = 1024;
+
+/// A compression scheme from the `Accept-Encoding` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Deflate,
+}
+
+impl Compression {
+    /// Picks a compression scheme the request's `Accept-Encoding` header
+    /// allows, preferring gzip. Returns `None` if it allows neither.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let listed: Vec<(String, bool)> = listed_encodings(headers).collect();
+
+        // An encoding that isn't listed by name is allowed if `*` is.
+        let accepts = |name: &str| {
+            let listed_as = |wanted: &str| {
+                listed
+                    .iter()
+                    .find(|(value, _)| value == wanted)
+                    .map(|&(_, accepted)| accepted)
+            };
+
+            listed_as(name).or_else(|| listed_as("*")).unwrap_or(false)
+        };
+
+        if accepts("gzip") {
+            Some(Compression::Gzip)
+        } else if accepts("deflate") {
+            Some(Compression::Deflate)
+        } else {
+            None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Deflate => "deflate",
+        }
+    }
+
+    fn compress(self, input: &[u8]) -> std::io::Result<Vec<u8>> {
+        // The fastest level still shrinks JSON several times over, and large
+        // responses are slow enough to produce already.
+        let level = flate2::Compression::fast();
+
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), level);
+                encoder.write_all(input)?;
+                encoder.finish()
+            }
+            Compression::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), level);
+                encoder.write_all(input)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Compresses a response's body, unless it's too small to be worth it or is
+/// already encoded.
+pub async fn compress(response: Response<Body>, compression: Compression) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+
+    if parts.headers.contains_key(CONTENT_ENCODING) {
+        return Response::from_parts(parts, body);
+    }
+
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => {
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .header(CONTENT_TYPE, "text/plain")
+                .body(Body::from(err.to_string()))
+                .unwrap();
+        }
+    };
+
+    if body.len() < MIN_COMPRESSED_SIZE {
+        return Response::from_parts(parts, Body::from(body));
+    }
+
+    // Compressing hundreds of megabytes takes long enough that it'd hold up
+    // other requests if it ran on one of the runtime's worker threads.
+    let compressed = tokio::task::spawn_blocking({
+        let body = body.clone();
+        move || compression.compress(&body)
+    })
+    .await;
+
+    match compressed {
+        Ok(Ok(compressed)) => {
+            parts.headers.remove(CONTENT_LENGTH);
+            parts.headers.insert(
+                CONTENT_ENCODING,
+                HeaderValue::from_static(compression.name()),
+            );
+            Response::from_parts(parts, Body::from(compressed))
+        }
+        _ => Response::from_parts(parts, Body::from(body)),
+    }
+}
+
+/// The encodings listed in every `Accept-Encoding` header, lowercased and
+/// without parameters, along with whether they're accepted. Encodings given a
+/// quality of zero are explicitly not accepted.
+fn listed_encodings(headers: &HeaderMap) -> impl Iterator<Item = (String, bool)> + '_ {
+    headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let value = parts.next()?.to_lowercase();
+
+            let rejected = parts.any(|parameter| match parameter.strip_prefix("q=") {
+                Some(quality) => quality.parse::<f32>().is_ok_and(|q| q <= 0.0),
+                None => false,
+            });
+
+            if value.is_empty() {
+                None
+            } else {
+                Some((value, !rejected))
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn headers(name: &'static str, value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn compression() {
+        assert_eq!(Compression::from_headers(&HeaderMap::new()), None);
+        assert_eq!(
+            Compression::from_headers(&headers("accept-encoding", "gzip, deflate, br")),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_headers(&headers("accept-encoding", "deflate")),
+            Some(Compression::Deflate)
+        );
+        assert_eq!(
+            Compression::from_headers(&headers("accept-encoding", "GZIP;q=0, deflate;q=0.5")),
+            Some(Compression::Deflate)
+        );
+        assert_eq!(
+            Compression::from_headers(&headers("accept-encoding", "identity")),
+            None
+        );
+        assert_eq!(
+            Compression::from_headers(&headers("accept-encoding", "*")),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_headers(&headers("accept-encoding", "gzip;q=0, *")),
+            Some(Compression::Deflate)
+        );
+        assert_eq!(
+            Compression::from_headers(&headers("accept-encoding", "deflate, *;q=0")),
+            Some(Compression::Deflate)
+        );
+    }
+}
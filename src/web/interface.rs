@@ -5,21 +5,35 @@
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use rbx_dom_weak::types::{Ref, Variant};
+use rbx_dom_weak::types::{Ref, SharedString, Variant};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     session_id::SessionId,
-    snapshot::{InstanceMetadata as RojoInstanceMetadata, InstanceWithMeta},
+    snapshot::{
+        AppliedPatchSet, AppliedPatchUpdate, InstanceMetadata as RojoInstanceMetadata,
+        InstanceWithMeta, InstigatingSource as RojoInstigatingSource, RojoTree,
+    },
 };
 
+pub use crate::{diagnostics::Diagnostic, snapshot::PatchCause};
+
 /// Server version to report over the API, not exposed outside this crate.
 pub(crate) const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Current protocol version, which is required to match.
-pub const PROTOCOL_VERSION: u64 = 4;
+pub const PROTOCOL_VERSION: u64 = 5;
+
+/// The contents of the SharedString values in a response, base64 encoded and
+/// keyed by the hash that property values refer to them by.
+///
+/// SharedStrings are often repeated across many instances, like the physics
+/// data of identical unions, so each one is only sent once per response.
+pub type SharedStrings = HashMap<String, String>;
 
 /// Message returned by Rojo API when a change has occurred.
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +42,15 @@ pub struct SubscribeMessage<'a> {
     pub removed: Vec<Ref>,
     pub added: HashMap<Ref, Instance<'a>>,
     pub updated: Vec<InstanceUpdate>,
+
+    /// Problems with project files found with this change. Each one replaces
+    /// any earlier diagnostic for the same path.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reported_diagnostics: Vec<Diagnostic>,
+
+    /// Paths whose diagnostics were resolved by this change.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cleared_diagnostics: Vec<PathBuf>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,25 +60,117 @@ pub struct InstanceUpdate {
     pub changed_name: Option<String>,
     pub changed_class_name: Option<String>,
 
-    // TODO: Transform from HashMap<String, Option<_>> to something else, since
-    // null will get lost when decoding from JSON in some languages.
-    #[serde(default)]
-    pub changed_properties: HashMap<String, Option<Variant>>,
+    /// Properties that were given a new value.
+    #[serde(
+        default,
+        serialize_with = "property_values::serialize_map",
+        deserialize_with = "property_values::deserialize_map"
+    )]
+    pub set_properties: HashMap<String, Variant>,
+
+    /// Properties that were removed, which should go back to their defaults.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed_properties: Vec<String>,
     pub changed_metadata: Option<InstanceMetadata>,
 }
 
+impl InstanceUpdate {
+    /// Splits the changed properties of a patch into the ones that were set
+    /// and the ones that were removed. Removed properties are sorted so that
+    /// responses are stable.
+    pub(crate) fn split_properties(
+        changed_properties: HashMap<String, Option<Variant>>,
+    ) -> (HashMap<String, Variant>, Vec<String>) {
+        let mut set_properties = HashMap::new();
+        let mut removed_properties = Vec::new();
+
+        for (key, value) in changed_properties {
+            match value {
+                Some(value) => {
+                    set_properties.insert(key, value);
+                }
+                None => removed_properties.push(key),
+            }
+        }
+
+        removed_properties.sort();
+        (set_properties, removed_properties)
+    }
+
+    /// Joins set and removed properties back into the form patches use.
+    pub(crate) fn join_properties(
+        set_properties: HashMap<String, Variant>,
+        removed_properties: Vec<String>,
+    ) -> HashMap<String, Option<Variant>> {
+        let mut changed_properties: HashMap<_, _> = set_properties
+            .into_iter()
+            .map(|(key, value)| (key, Some(value)))
+            .collect();
+
+        for key in removed_properties {
+            changed_properties.insert(key, None);
+        }
+
+        changed_properties
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InstanceMetadata {
     pub ignore_unknown_instances: bool,
+
+    /// The paths that affect this instance. Only sent when asked for, like with
+    /// `/api/read/{id}?sources=true`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relevant_paths: Vec<PathBuf>,
+
+    /// What created this instance. Only sent along with `relevant_paths`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instigating_source: Option<InstigatingSource>,
 }
 
 impl InstanceMetadata {
     pub(crate) fn from_rojo_metadata(meta: &RojoInstanceMetadata) -> Self {
         Self {
             ignore_unknown_instances: meta.ignore_unknown_instances,
+            relevant_paths: Vec::new(),
+            instigating_source: None,
         }
     }
+
+    /// Like `from_rojo_metadata`, but also includes where the instance came
+    /// from on the filesystem.
+    pub(crate) fn from_rojo_metadata_with_sources(meta: &RojoInstanceMetadata) -> Self {
+        let instigating_source = meta.instigating_source.as_ref().map(|source| match source {
+            RojoInstigatingSource::Path(path) => InstigatingSource::Path(path.clone()),
+            RojoInstigatingSource::ProjectNode(project_path, name, _, _) => {
+                InstigatingSource::ProjectNode {
+                    project_path: project_path.clone(),
+                    name: name.clone(),
+                }
+            }
+        });
+
+        Self {
+            ignore_unknown_instances: meta.ignore_unknown_instances,
+            relevant_paths: meta.relevant_paths.clone(),
+            instigating_source,
+        }
+    }
+}
+
+/// The file or project node that an instance was created from.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InstigatingSource {
+    Path(PathBuf),
+
+    #[serde(rename_all = "camelCase")]
+    ProjectNode {
+        project_path: PathBuf,
+        name: String,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,6 +180,7 @@ pub struct Instance<'a> {
     pub parent: Ref,
     pub name: Cow<'a, str>,
     pub class_name: Cow<'a, str>,
+    #[serde(serialize_with = "property_values::serialize_map")]
     pub properties: HashMap<String, Cow<'a, Variant>>,
     pub children: Cow<'a, [Ref]>,
     pub metadata: Option<InstanceMetadata>,
@@ -75,14 +191,7 @@ impl<'a> Instance<'a> {
         let properties = source
             .properties()
             .iter()
-            .filter_map(|(key, value)| {
-                // SharedString values can't be serialized via Serde
-                if matches!(value, Variant::SharedString(_)) {
-                    return None;
-                }
-
-                Some((key.clone(), Cow::Borrowed(value)))
-            })
+            .map(|(key, value)| (key.clone(), Cow::Borrowed(value)))
             .collect();
 
         Instance {
@@ -95,6 +204,26 @@ impl<'a> Instance<'a> {
             metadata: Some(InstanceMetadata::from_rojo_metadata(source.metadata())),
         }
     }
+
+    /// Copies everything borrowed from the tree, so that the tree can be
+    /// released before the instance is serialized.
+    pub(crate) fn into_owned(self) -> Instance<'static> {
+        let properties = self
+            .properties
+            .into_iter()
+            .map(|(key, value)| (key, Cow::Owned(value.into_owned())))
+            .collect();
+
+        Instance {
+            id: self.id,
+            parent: self.parent,
+            name: Cow::Owned(self.name.into_owned()),
+            class_name: Cow::Owned(self.class_name.into_owned()),
+            properties,
+            children: Cow::Owned(self.children.into_owned()),
+            metadata: self.metadata,
+        }
+    }
 }
 
 /// Response body from /api/rojo
@@ -109,6 +238,39 @@ pub struct ServerInfoResponse {
     pub game_id: Option<u64>,
     pub place_id: Option<u64>,
     pub root_instance_id: Ref,
+
+    /// Recent errors the server ran into while watching for file changes.
+    /// Changes may have been missed around them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watcher_errors: Vec<String>,
+
+    /// Whether the server refuses writes, in which case clients shouldn't offer
+    /// to send changes back.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub read_only: bool,
+
+    /// Every project served by the server, when it serves more than one. Each
+    /// project's API is under `/api/{projectName}/`, with the name
+    /// percent-encoded, and the rest of this response describes the first one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub projects: Vec<ProjectInfo>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// A project served by a server that serves more than one, in
+/// `ServerInfoResponse`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInfo {
+    pub session_id: SessionId,
+    pub project_name: String,
+    pub expected_place_ids: Option<HashSet<u64>>,
+    pub game_id: Option<u64>,
+    pub place_id: Option<u64>,
+    pub root_instance_id: Ref,
 }
 
 /// Response body from /api/read/{id}
@@ -118,6 +280,201 @@ pub struct ReadResponse<'a> {
     pub session_id: SessionId,
     pub message_cursor: u32,
     pub instances: HashMap<Ref, Instance<'a>>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub shared_strings: SharedStrings,
+}
+
+/// Response body from /api/search/{query}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse<'a> {
+    pub session_id: SessionId,
+    pub message_cursor: u32,
+
+    /// The instances whose name, class name, or relevant paths contain the
+    /// query, closest to the root first.
+    pub matches: Vec<Ref>,
+
+    /// Whether there were more matches than could be sent.
+    pub truncated: bool,
+
+    /// Every match along with its ancestors, so that the matches can be shown
+    /// in place in the tree. Their children aren't included.
+    pub instances: HashMap<Ref, Instance<'a>>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub shared_strings: SharedStrings,
+}
+
+/// Response body from /api/history
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryResponse {
+    pub session_id: SessionId,
+    pub message_cursor: u32,
+
+    /// The most recent messages before the requested cursor, newest first.
+    pub entries: Vec<HistoryEntry>,
+
+    /// Whether there are older messages than these. They can be read by
+    /// passing the oldest entry's index as `before`.
+    pub more: bool,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub shared_strings: SharedStrings,
+}
+
+/// A message from the message queue, described for people rather than for
+/// keeping a tree in sync.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// The message's position in the queue. Subscribing from this cursor
+    /// returns this message first.
+    pub index: u32,
+
+    /// When the message was sent, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+
+    /// What led to the change, if it's known.
+    pub cause: Option<PatchCause>,
+
+    pub added: Vec<HistoryInstance>,
+    pub removed: Vec<Ref>,
+    pub updated: Vec<HistoryUpdate>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reported_diagnostics: Vec<Diagnostic>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cleared_diagnostics: Vec<PathBuf>,
+}
+
+/// An instance named in a history entry. The name and class name are the
+/// instance's current ones, which are missing if it's since been removed.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryInstance {
+    pub id: Ref,
+    pub name: Option<String>,
+    pub class_name: Option<String>,
+}
+
+/// An instance that was changed in a history entry.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryUpdate {
+    #[serde(flatten)]
+    pub instance: HistoryInstance,
+
+    /// Each changed property, sorted by name. Changes to `Name` and
+    /// `ClassName` are included as string properties.
+    pub changes: Vec<PropertyChange>,
+
+    /// Whether Rojo's metadata for the instance changed.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub metadata_changed: bool,
+}
+
+/// A property's value before and after a change. A value is missing when the
+/// property didn't exist.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertyChange {
+    pub property: String,
+    #[serde(serialize_with = "property_values::serialize_optional")]
+    pub before: Option<Variant>,
+    #[serde(serialize_with = "property_values::serialize_optional")]
+    pub after: Option<Variant>,
+}
+
+impl HistoryEntry {
+    pub(crate) fn new(
+        index: u32,
+        pushed_at: SystemTime,
+        message: AppliedPatchSet,
+        tree: &RojoTree,
+    ) -> Self {
+        let timestamp = pushed_at
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+
+        let added = message
+            .added
+            .into_iter()
+            .map(|id| HistoryInstance::new(id, tree))
+            .collect();
+
+        let updated = message
+            .updated
+            .into_iter()
+            .map(|update| HistoryUpdate::new(update, tree))
+            .collect();
+
+        Self {
+            index,
+            timestamp,
+            cause: message.cause,
+            added,
+            removed: message.removed,
+            updated,
+            reported_diagnostics: message.reported_diagnostics,
+            cleared_diagnostics: message.cleared_diagnostics,
+        }
+    }
+}
+
+impl HistoryInstance {
+    fn new(id: Ref, tree: &RojoTree) -> Self {
+        let instance = tree.get_instance(id);
+
+        Self {
+            id,
+            name: instance.map(|instance| instance.name().to_owned()),
+            class_name: instance.map(|instance| instance.class_name().to_owned()),
+        }
+    }
+}
+
+impl HistoryUpdate {
+    fn new(mut update: AppliedPatchUpdate, tree: &RojoTree) -> Self {
+        let mut changes = Vec::new();
+
+        if let Some(name) = update.changed_name {
+            changes.push(PropertyChange {
+                property: "Name".to_owned(),
+                before: update.previous_name.map(Variant::String),
+                after: Some(Variant::String(name)),
+            });
+        }
+
+        if let Some(class_name) = update.changed_class_name {
+            changes.push(PropertyChange {
+                property: "ClassName".to_owned(),
+                before: update.previous_class_name.map(Variant::String),
+                after: Some(Variant::String(class_name)),
+            });
+        }
+
+        for (property, after) in update.changed_properties {
+            let before = update.previous_properties.remove(&property).flatten();
+            changes.push(PropertyChange {
+                property,
+                before,
+                after,
+            });
+        }
+
+        changes.sort_by(|a, b| a.property.cmp(&b.property));
+
+        Self {
+            instance: HistoryInstance::new(update.id, tree),
+            changes,
+            metadata_changed: update.changed_metadata.is_some(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -144,6 +501,41 @@ pub struct SubscribeResponse<'a> {
     pub session_id: SessionId,
     pub message_cursor: u32,
     pub messages: Vec<SubscribeMessage<'a>>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub shared_strings: SharedStrings,
+
+    /// Same as `ServerInfoResponse::watcher_errors`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub watcher_errors: Vec<String>,
+
+    /// Set when the server is shutting down, in which case there are no
+    /// messages and this session won't send any more.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub session_ending: bool,
+}
+
+/// Response body from /api/diagnostics
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsResponse {
+    pub session_id: SessionId,
+
+    /// The message cursor these diagnostics are current as of. Later changes
+    /// arrive through /api/subscribe.
+    pub message_cursor: u32,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Response body from /api/resync
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResyncResponse {
+    pub session_id: SessionId,
+
+    /// The message cursor after the resync. Clients that have seen every
+    /// message up to it are in sync with the server again.
+    pub message_cursor: u32,
 }
 
 /// Response body from /api/open/{id}
@@ -182,6 +574,24 @@ impl ErrorResponse {
             details: details.into(),
         }
     }
+
+    pub fn forbidden<S: Into<String>>(details: S) -> Self {
+        Self {
+            kind: ErrorResponseKind::Forbidden,
+            details: details.into(),
+        }
+    }
+
+    pub fn unauthorized<S: Into<String>>(details: S) -> Self {
+        Self {
+            kind: ErrorResponseKind::Unauthorized,
+            details: details.into(),
+        }
+    }
+
+    pub fn details(&self) -> &str {
+        &self.details
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -189,4 +599,104 @@ pub enum ErrorResponseKind {
     NotFound,
     BadRequest,
     InternalError,
+    Unauthorized,
+    Forbidden,
+}
+
+/// Adds the contents of every SharedString among `values` to `shared_strings`.
+pub(crate) fn collect_shared_strings<'v>(
+    values: impl IntoIterator<Item = &'v Variant>,
+    shared_strings: &mut SharedStrings,
+) {
+    for value in values {
+        if let Variant::SharedString(value) = value {
+            shared_strings
+                .entry(shared_string_key(value))
+                .or_insert_with(|| base64::encode(value.data()));
+        }
+    }
+}
+
+/// The hash that a SharedString is referred to by, as hex.
+fn shared_string_key(value: &SharedString) -> String {
+    value
+        .hash()
+        .as_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Serializes property values like usual, except for SharedStrings, which
+/// rbx_types refuses to serialize. They're sent as `{"SharedString": "<hash>"}`
+/// instead, with their contents in the response's `SharedStrings`.
+///
+/// Clients write SharedStrings as `{"SharedString": "<base64 contents>"}`,
+/// which is how the plugin encodes them, so that's what they're deserialized
+/// from. Responses only carry a hash in their place, so their SharedStrings
+/// can't be read back.
+mod property_values {
+    use std::{borrow::Borrow, collections::HashMap};
+
+    use rbx_dom_weak::types::{SharedString, Variant};
+    use serde::{
+        de::Error as _, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    use super::shared_string_key;
+
+    struct ApiValue<'a>(&'a Variant);
+
+    impl Serialize for ApiValue<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self.0 {
+                Variant::SharedString(value) => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry("SharedString", &shared_string_key(value))?;
+                    map.end()
+                }
+                value => value.serialize(serializer),
+            }
+        }
+    }
+
+    pub fn serialize_map<S, V>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        V: Borrow<Variant>,
+    {
+        serializer.collect_map(
+            map.iter()
+                .map(|(key, value)| (key, ApiValue(value.borrow()))),
+        )
+    }
+
+    pub fn deserialize_map<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, Variant>, D::Error> {
+        let values = HashMap::<String, serde_json::Value>::deserialize(deserializer)?;
+
+        values
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value.get("SharedString").and_then(|value| value.as_str()) {
+                    Some(contents) => base64::decode(contents)
+                        .map(|contents| Variant::SharedString(SharedString::new(contents)))
+                        .map_err(|err| {
+                            D::Error::custom(format!("invalid SharedString in {}: {}", key, err))
+                        })?,
+                    None => Variant::deserialize(value).map_err(D::Error::custom)?,
+                };
+
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    pub fn serialize_optional<S: Serializer>(
+        value: &Option<Variant>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.as_ref().map(ApiValue).serialize(serializer)
+    }
 }
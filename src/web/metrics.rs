@@ -0,0 +1,157 @@
+//! Renders the metrics of every served project in Prometheus' text format, for
+//! `/metrics`.
+
+use std::{fmt::Write, sync::Arc};
+
+use crate::{metrics::Phase, serve_session::ServeSession};
+
+/// The content type of Prometheus' text format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub fn render(sessions: &[Arc<ServeSession>]) -> String {
+    let mut output = String::new();
+
+    metric(
+        &mut output,
+        "rojo_phase_seconds",
+        "summary",
+        "Time spent on each step of turning changes into messages for clients.",
+    );
+    for session in sessions {
+        for &phase in &Phase::ALL {
+            let timing = session.metrics().timing(phase);
+            let labels = phase_labels(session, phase);
+            sample(
+                &mut output,
+                "rojo_phase_seconds_sum",
+                &labels,
+                timing.total.as_secs_f64(),
+            );
+            sample(
+                &mut output,
+                "rojo_phase_seconds_count",
+                &labels,
+                timing.count as f64,
+            );
+        }
+    }
+
+    metric(
+        &mut output,
+        "rojo_phase_max_seconds",
+        "gauge",
+        "The longest a single run of each step has taken.",
+    );
+    for session in sessions {
+        for &phase in &Phase::ALL {
+            let timing = session.metrics().timing(phase);
+            let labels = phase_labels(session, phase);
+            sample(
+                &mut output,
+                "rojo_phase_max_seconds",
+                &labels,
+                timing.max.as_secs_f64(),
+            );
+        }
+    }
+
+    session_metric(
+        &mut output,
+        sessions,
+        "rojo_vfs_files_read_total",
+        "counter",
+        "How many files have been read.",
+        |session| session.vfs().stats().files_read as f64,
+    );
+    session_metric(
+        &mut output,
+        sessions,
+        "rojo_vfs_bytes_read_total",
+        "counter",
+        "The total size of every file read, in bytes.",
+        |session| session.vfs().stats().bytes_read as f64,
+    );
+    session_metric(
+        &mut output,
+        sessions,
+        "rojo_vfs_dirs_read_total",
+        "counter",
+        "How many directories have been listed.",
+        |session| session.vfs().stats().dirs_read as f64,
+    );
+    session_metric(
+        &mut output,
+        sessions,
+        "rojo_instances",
+        "gauge",
+        "How many instances are in the tree.",
+        |session| session.tree().instance_count() as f64,
+    );
+    session_metric(
+        &mut output,
+        sessions,
+        "rojo_messages_total",
+        "counter",
+        "How many messages have been sent to subscribed clients.",
+        |session| f64::from(session.message_queue().cursor()),
+    );
+    session_metric(
+        &mut output,
+        sessions,
+        "rojo_diagnostics",
+        "gauge",
+        "How many files currently have problems that keep them from syncing.",
+        |session| session.diagnostics().len() as f64,
+    );
+    session_metric(
+        &mut output,
+        sessions,
+        "rojo_uptime_seconds",
+        "gauge",
+        "How long the project has been served.",
+        |session| session.start_time().elapsed().as_secs_f64(),
+    );
+
+    output
+}
+
+fn metric(output: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(output, "# HELP {} {}", name, help).unwrap();
+    writeln!(output, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn sample(output: &mut String, name: &str, labels: &str, value: f64) {
+    writeln!(output, "{}{{{}}} {}", name, labels, value).unwrap();
+}
+
+/// Writes a metric with one sample for each project.
+fn session_metric(
+    output: &mut String,
+    sessions: &[Arc<ServeSession>],
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: impl Fn(&ServeSession) -> f64,
+) {
+    metric(output, name, kind, help);
+
+    for session in sessions {
+        sample(output, name, &project_label(session), value(session));
+    }
+}
+
+fn project_label(session: &ServeSession) -> String {
+    format!("project=\"{}\"", escape_label(session.project_name()))
+}
+
+fn phase_labels(session: &ServeSession, phase: Phase) -> String {
+    format!("{},phase=\"{}\"", project_label(session), phase.name())
+}
+
+/// Escapes a label value as Prometheus' text format requires.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
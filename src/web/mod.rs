@@ -4,72 +4,450 @@
 
 mod api;
 mod assets;
+mod encoding;
 pub mod interface;
+mod metrics;
+mod stdio;
+mod tls;
 mod ui;
 mod util;
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::HashSet, convert::Infallible, net::SocketAddr, pin::Pin, sync::Arc, time::Duration,
+};
 
-use futures::{
-    future::{self, FutureResult},
-    Future,
+use futures::{stream, Stream, StreamExt};
+use hyper::{
+    header,
+    server::accept,
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode, Uri,
 };
-use hyper::{service::Service, Body, Request, Response, Server};
 use log::trace;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::{mpsc, watch},
+};
 
 use crate::serve_session::ServeSession;
 
-use self::{api::ApiService, ui::UiService};
+use self::{
+    api::{ApiService, API_ROUTES},
+    encoding::{compress, Compression},
+    interface::ErrorResponse,
+    ui::UiService,
+    util::{json, percent_decode},
+};
+
+pub use self::tls::{TlsError, TlsIdentity};
+
+/// How long a client has to finish its TLS handshake before its connection is
+/// dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct RootService {
-    api: ApiService,
+    /// The API of each served project along with the project's name. The first
+    /// project's API is also served without a project name in the path.
+    apis: Vec<(String, ApiService)>,
     ui: UiService,
+
+    /// If set, every API request must carry this token in an `Authorization:
+    /// Bearer` header.
+    token: Option<Arc<str>>,
 }
 
-impl Service for RootService {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = hyper::Error;
-    type Future = Box<dyn Future<Item = Response<Self::ReqBody>, Error = Self::Error> + Send>;
+impl RootService {
+    pub fn new(
+        serve_sessions: Arc<[Arc<ServeSession>]>,
+        token: Option<Arc<str>>,
+        shutdown: watch::Receiver<bool>,
+    ) -> Self {
+        let apis = serve_sessions
+            .iter()
+            .map(|session| {
+                let api = ApiService::new(
+                    Arc::clone(session),
+                    Arc::clone(&serve_sessions),
+                    shutdown.clone(),
+                );
+                (session.project_name().to_owned(), api)
+            })
+            .collect();
+
+        RootService {
+            apis,
+            ui: UiService::new(Arc::clone(&serve_sessions[0]), Arc::clone(&serve_sessions)),
+            token,
+        }
+    }
 
-    fn call(&mut self, request: Request<Self::ReqBody>) -> Self::Future {
+    pub async fn call(&self, request: Request<Body>) -> Response<Body> {
         trace!("{} {}", request.method(), request.uri().path());
 
+        let compression = Compression::from_headers(request.headers());
+        let response = self.route(request).await;
+
+        match compression {
+            Some(compression) => compress(response, compression).await,
+            None => response,
+        }
+    }
+
+    async fn route(&self, mut request: Request<Body>) -> Response<Body> {
+        // Besides the API, `/metrics` needs the token since scrapers can send
+        // it. The other UI pages are public so that the plugin can check that
+        // the server is up. Without the token, the home page leaves out the
+        // project's problems, and the instance browser and change history
+        // read the project's contents from the API with the token.
+        let path = request.uri().path();
+        let needs_token = path.starts_with("/api") || path == "/metrics";
+        let authorized = self.is_authorized(&request);
+
+        if needs_token && !authorized {
+            return json(
+                ErrorResponse::unauthorized("Missing or incorrect token"),
+                StatusCode::UNAUTHORIZED,
+            );
+        }
+
         if request.uri().path().starts_with("/api") {
-            self.api.call(request)
+            match self.find_project_api(request.uri()) {
+                ApiTarget::Default => self.apis[0].1.call(request).await,
+                ApiTarget::Project(index, uri) => {
+                    *request.uri_mut() = uri;
+                    self.apis[index].1.call(request).await
+                }
+                ApiTarget::Unknown => json(
+                    ErrorResponse::not_found(format!(
+                        "No project or route found: {}",
+                        request.uri().path()
+                    )),
+                    StatusCode::NOT_FOUND,
+                ),
+            }
         } else {
-            self.ui.call(request)
+            self.ui.call(request, authorized).await
         }
     }
-}
 
-impl RootService {
-    pub fn new(serve_session: Arc<ServeSession>) -> Self {
-        RootService {
-            api: ApiService::new(Arc::clone(&serve_session)),
-            ui: UiService::new(Arc::clone(&serve_session)),
+    fn is_authorized(&self, request: &Request<Body>) -> bool {
+        let expected = match &self.token {
+            Some(token) => token,
+            None => return true,
+        };
+
+        let given = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match given {
+            Some(given) => constant_time_eq(given.as_bytes(), expected.as_bytes()),
+            None => false,
+        }
+    }
+
+    /// Finds the project that a request to `/api/<project name>/...` is for,
+    /// along with the URI with the project name taken out.
+    ///
+    /// Routes like `/api/rojo` always win over project names.
+    fn find_project_api(&self, uri: &Uri) -> ApiTarget {
+        let path = match uri.path().strip_prefix("/api/") {
+            Some(path) => path,
+            None => return ApiTarget::Default,
+        };
+
+        let (segment, rest) = match path.find('/') {
+            Some(index) => path.split_at(index),
+            None => (path, ""),
+        };
+
+        if API_ROUTES.contains(&segment) {
+            return ApiTarget::Default;
+        }
+
+        let index = percent_decode(segment)
+            .and_then(|project_name| self.apis.iter().position(|(name, _)| *name == project_name));
+
+        let new_uri = match uri.query() {
+            Some(query) => format!("/api{}?{}", rest, query),
+            None => format!("/api{}", rest),
+        };
+
+        match (index, new_uri.parse()) {
+            (Some(index), Ok(new_uri)) => ApiTarget::Project(index, new_uri),
+            _ => ApiTarget::Unknown,
         }
     }
 }
 
+/// Which project's API a request is for.
+enum ApiTarget {
+    /// A route like `/api/rojo`, which is served by the first project.
+    Default,
+
+    /// A route under `/api/<project name>/`, with the URI it has without the
+    /// project name.
+    Project(usize, Uri),
+
+    /// A path under `/api/` that doesn't start with a route or the name of a
+    /// served project.
+    Unknown,
+}
+
+/// Compares two byte strings in time that only depends on their lengths, so
+/// that tokens can't be guessed by timing how quickly they're rejected.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Serves the HTTP API and web UI for one or more serve sessions on one port.
 pub struct LiveServer {
-    serve_session: Arc<ServeSession>,
+    serve_sessions: Arc<[Arc<ServeSession>]>,
+    token: Option<Arc<str>>,
+    tls: Option<tokio_native_tls::TlsAcceptor>,
 }
 
 impl LiveServer {
-    pub fn new(serve_session: Arc<ServeSession>) -> Self {
-        LiveServer { serve_session }
+    /// Serves one or more sessions from one server. Each session's API is
+    /// served under `/api/<project name>/`, and the first session's is also
+    /// served under `/api/`. The web UI shows the first session.
+    pub fn new(serve_sessions: Vec<Arc<ServeSession>>) -> Result<Self, LiveServerError> {
+        if serve_sessions.is_empty() {
+            return Err(LiveServerError::NoSessions);
+        }
+
+        let mut names = HashSet::new();
+
+        for session in &serve_sessions {
+            let name = session.project_name();
+
+            if serve_sessions.len() > 1 && API_ROUTES.contains(&name) {
+                return Err(LiveServerError::ReservedProjectName {
+                    name: name.to_owned(),
+                });
+            }
+
+            if !names.insert(name) {
+                return Err(LiveServerError::DuplicateProjectName {
+                    name: name.to_owned(),
+                });
+            }
+        }
+
+        Ok(LiveServer {
+            serve_sessions: Arc::from(serve_sessions),
+            token: None,
+            tls: None,
+        })
     }
 
+    /// Requires every request to the HTTP API to carry the given token in an
+    /// `Authorization: Bearer` header. Doesn't apply to `start_stdio`, which is
+    /// only reachable by the process that started Rojo.
+    pub fn with_token(self, token: String) -> Self {
+        LiveServer {
+            token: Some(Arc::from(token)),
+            ..self
+        }
+    }
+
+    /// Serves HTTPS instead of HTTP, using the given certificate and key.
+    pub fn with_tls(self, identity: &TlsIdentity) -> Result<Self, TlsError> {
+        Ok(LiveServer {
+            tls: Some(identity.acceptor()?),
+            ..self
+        })
+    }
+
+    /// Serves the HTTP API and web UI on the given address until Rojo is
+    /// interrupted with Ctrl-C or, on Unix, SIGTERM.
+    ///
+    /// When interrupted, the server stops accepting connections and tells
+    /// clients waiting on new messages that the session is ending. It returns
+    /// once every connection has closed, and the sessions can then be dropped
+    /// to stop their change processors.
     pub fn start(self, address: SocketAddr) {
-        let server = Server::bind(&address)
-            .serve(move || {
-                let service: FutureResult<_, hyper::Error> =
-                    future::ok(RootService::new(Arc::clone(&self.serve_session)));
-                service
-            })
-            .map_err(|e| eprintln!("Server error: {}", e));
+        let runtime = tokio::runtime::Runtime::new().expect("Could not start async runtime");
+        runtime.block_on(self.serve(address));
+    }
+
+    async fn serve(self, address: SocketAddr) {
+        let listener = TcpListener::bind(address)
+            .await
+            .unwrap_or_else(|err| panic!("error binding to {}: {}", address, err));
+
+        let incoming = match self.tls {
+            Some(acceptor) => tls_connections(listener, acceptor),
+            None => tcp_connections(listener)
+                .map(|stream| Ok(Box::new(stream) as Box<dyn Connection>))
+                .boxed(),
+        };
+
+        let (shutdown_sender, shutdown) = watch::channel(false);
+        let root_service = Arc::new(RootService::new(
+            Arc::clone(&self.serve_sessions),
+            self.token.clone(),
+            shutdown,
+        ));
+
+        let make_service = make_service_fn(move |_| {
+            let root_service = Arc::clone(&root_service);
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let root_service = Arc::clone(&root_service);
+                    async move { Ok::<_, Infallible>(root_service.call(request).await) }
+                }))
+            }
+        });
+
+        let server = Server::builder(accept::from_stream(incoming))
+            .serve(make_service)
+            .with_graceful_shutdown(async move {
+                shutdown_signal().await;
+
+                log::info!("Shutting down... Press Ctrl-C again to stop immediately.");
+                let _ = shutdown_sender.send(true);
+
+                tokio::spawn(async {
+                    if tokio::signal::ctrl_c().await.is_ok() {
+                        std::process::exit(1);
+                    }
+                });
+            });
+
+        if let Err(err) = server.await {
+            eprintln!("Server error: {}", err);
+        }
+    }
+
+    /// Serves the API as JSON-RPC over stdin and stdout instead of HTTP, until
+    /// stdin is closed.
+    pub fn start_stdio(self) {
+        stdio::run(self.serve_sessions);
+    }
+}
+
+/// Resolves once Rojo is asked to stop, either with Ctrl-C or, on Unix, with
+/// SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            log::error!("Could not listen for Ctrl-C: {}", err);
+            futures::future::pending::<()>().await;
+        }
+    };
 
-        hyper::rt::run(server);
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                log::error!("Could not listen for SIGTERM: {}", err);
+                futures::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = futures::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
     }
 }
+
+/// A connection to the live server, which is either plain TCP or TLS.
+trait Connection: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Connection for T {}
+
+type Connections = Pin<Box<dyn Stream<Item = Result<Box<dyn Connection>, Infallible>> + Send>>;
+
+/// Accepts TCP connections forever. A connection that fails to be accepted, like
+/// when too many files are open, is skipped instead of stopping the server.
+fn tcp_connections(listener: TcpListener) -> impl Stream<Item = tokio::net::TcpStream> + Send {
+    stream::unfold(listener, |listener| async move {
+        let result = listener.accept().await;
+        Some((result, listener))
+    })
+    .filter_map(|result| async move {
+        match result {
+            Ok((stream, _)) => Some(stream),
+            Err(err) => {
+                log::debug!("Could not accept connection: {}", err);
+                None
+            }
+        }
+    })
+}
+
+/// Accepts TLS connections forever. Each handshake runs in its own task, so
+/// clients that never finish theirs don't hold up anyone else, and they're
+/// dropped after `HANDSHAKE_TIMEOUT`.
+fn tls_connections(listener: TcpListener, acceptor: tokio_native_tls::TlsAcceptor) -> Connections {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let mut incoming = Box::pin(tcp_connections(listener));
+
+        loop {
+            // Once the server stops taking connections, the listener is
+            // closed too.
+            let stream = tokio::select! {
+                Some(stream) = incoming.next() => stream,
+                _ = sender.closed() => break,
+            };
+
+            let acceptor = acceptor.clone();
+            let sender = sender.clone();
+
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = sender.send(stream);
+                    }
+
+                    // A client that fails its handshake, like a browser that
+                    // doesn't trust the certificate, only loses its own
+                    // connection.
+                    Ok(Err(err)) => log::debug!("TLS handshake failed: {}", err),
+                    Err(_) => log::debug!("TLS handshake timed out"),
+                }
+            });
+        }
+    });
+
+    stream::unfold(receiver, |mut receiver| async move {
+        let stream = receiver.recv().await?;
+        Some((Ok(Box::new(stream) as Box<dyn Connection>), receiver))
+    })
+    .boxed()
+}
+
+#[derive(Debug, Error)]
+pub enum LiveServerError {
+    #[error("At least one project must be served")]
+    NoSessions,
+
+    #[error(
+        "More than one project is named \"{name}\"\n\
+        Projects served together need different names so that they can be told apart."
+    )]
+    DuplicateProjectName { name: String },
+
+    #[error(
+        "The project \"{name}\" can't be served alongside other projects because its name is used by Rojo's API\n\
+        Rename the project to serve it with other projects."
+    )]
+    ReservedProjectName { name: String },
+}
@@ -0,0 +1,433 @@
+//! Serves Rojo's API as JSON-RPC 2.0 over stdin and stdout, for editor
+//! integrations that would rather not manage ports.
+//!
+//! Every message is a single line of JSON. Methods mirror the routes in
+//! `api.rs` and return the same types from `interface.rs`:
+//!
+//! * `rojo` returns a `ServerInfoResponse`.
+//! * `read`, with `{ "ids": [...] }`, returns a `ReadResponse`. It also takes
+//!   the same `depth` and `sources` options as `/api/read`.
+//! * `search`, with `{ "search": "..." }`, returns a `SearchResponse`.
+//! * `subscribe`, with `{ "cursor": 0 }`, returns `null`, then sends a
+//!   `messages` notification with a `SubscribeResponse` every time the tree
+//!   changes. Subscribing again replaces the previous subscription, and
+//!   `unsubscribe` ends it. The last notification has `sessionEnding` set if
+//!   the session ends first.
+//! * `write`, with a `WriteRequest`, returns a `WriteResponse`.
+//! * `open`, with `{ "id": "..." }`, returns an `OpenResponse`.
+//! * `resync` returns a `ResyncResponse`.
+//! * `diagnostics` returns a `DiagnosticsResponse`.
+//! * `history`, optionally with `{ "before": 0 }`, returns a `HistoryResponse`.
+//!
+//! When serving several projects, any method can be given a `project` param
+//! with the name of the project it's for.
+
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use hyper::{Body, Method, Request};
+use rbx_dom_weak::types::Ref;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{runtime::Runtime, sync::watch};
+
+use crate::serve_session::ServeSession;
+
+use super::{interface::ErrorResponse, util::percent_encode, RootService};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Returned for errors from Rojo's API, with the `ErrorResponse` as the error's
+/// data.
+const API_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum RpcMessage {
+    Response {
+        jsonrpc: &'static str,
+        id: Value,
+        #[serde(flatten)]
+        outcome: RpcOutcome,
+    },
+    Notification {
+        jsonrpc: &'static str,
+        method: &'static str,
+        params: Value,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum RpcOutcome {
+    Result(Value),
+    Error(RpcError),
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+/// The parts of a `SubscribeResponse` that the subscription needs to keep
+/// going.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubscribeProgress {
+    message_cursor: u32,
+    #[serde(default)]
+    session_ending: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectParams {
+    project: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadParams {
+    ids: Vec<Ref>,
+    #[serde(default)]
+    depth: Option<usize>,
+    #[serde(default)]
+    sources: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchParams {
+    search: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HistoryParams {
+    #[serde(default)]
+    before: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubscribeParams {
+    cursor: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenParams {
+    id: Ref,
+}
+
+/// Writes whole messages to stdout, one per line, from any thread.
+#[derive(Clone)]
+struct Output(Arc<Mutex<io::Stdout>>);
+
+impl Output {
+    fn send(&self, message: &RpcMessage) {
+        let mut stdout = self.0.lock().unwrap();
+
+        // If stdout is gone, so is whoever we were talking to; stdin will
+        // close soon too.
+        let _ = serde_json::to_writer(&mut *stdout, message);
+        let _ = writeln!(stdout);
+        let _ = stdout.flush();
+    }
+
+    fn respond(&self, id: Value, outcome: RpcOutcome) {
+        self.send(&RpcMessage::Response {
+            jsonrpc: "2.0",
+            id,
+            outcome,
+        });
+    }
+}
+
+/// Serves the API of the given sessions over stdin and stdout until stdin is
+/// closed.
+pub fn run(serve_sessions: Arc<[Arc<ServeSession>]>) {
+    let runtime = Runtime::new().expect("Could not start async runtime");
+    let output = Output(Arc::new(Mutex::new(io::stdout())));
+
+    // Requests waiting on new messages end when this is dropped, once stdin is
+    // closed.
+    let (_shutdown_sender, shutdown) = watch::channel(false);
+    let service = Arc::new(RootService::new(serve_sessions, None, shutdown));
+
+    // Bumped to end the current subscription, whose thread stops once it sees
+    // that its generation is out of date.
+    let subscription = Arc::new(AtomicU64::new(0));
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                log::error!("Could not read from stdin: {}", err);
+                break;
+            }
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: RpcRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                output.respond(
+                    Value::Null,
+                    RpcOutcome::Error(RpcError::new(PARSE_ERROR, err.to_string())),
+                );
+                continue;
+            }
+        };
+
+        // Requests without an ID are notifications, which get no response.
+        let id = request.id.clone();
+        let outcome = handle_request(request, &runtime, &service, &subscription, &output);
+
+        if let Some(id) = id {
+            output.respond(id, outcome);
+        }
+    }
+
+    subscription.fetch_add(1, Ordering::SeqCst);
+}
+
+fn handle_request(
+    request: RpcRequest,
+    runtime: &Runtime,
+    service: &Arc<RootService>,
+    subscription: &Arc<AtomicU64>,
+    output: &Output,
+) -> RpcOutcome {
+    if request.jsonrpc != "2.0" {
+        return RpcOutcome::Error(RpcError::new(
+            INVALID_REQUEST,
+            "Only JSON-RPC 2.0 is supported",
+        ));
+    }
+
+    let params = if request.params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        request.params
+    };
+
+    let api_root = match parse_params::<ProjectParams>(&params) {
+        Ok(ProjectParams {
+            project: Some(project),
+        }) => format!("/api/{}", percent_encode(&project)),
+        Ok(ProjectParams { project: None }) => "/api".to_owned(),
+        Err(err) => return RpcOutcome::Error(err),
+    };
+
+    let result = match request.method.as_str() {
+        "rojo" => runtime.block_on(call(
+            service,
+            Method::GET,
+            format!("{}/rojo", api_root),
+            None,
+        )),
+        "read" => parse_params::<ReadParams>(&params).and_then(|params| {
+            let ids: Vec<String> = params.ids.iter().map(|id| id.to_string()).collect();
+            let mut path = format!(
+                "{}/read/{}?sources={}",
+                api_root,
+                ids.join(","),
+                params.sources
+            );
+
+            if let Some(depth) = params.depth {
+                path.push_str(&format!("&depth={}", depth));
+            }
+
+            runtime.block_on(call(service, Method::GET, path, None))
+        }),
+        "search" => parse_params::<SearchParams>(&params).and_then(|params| {
+            let path = format!("{}/search/{}", api_root, percent_encode(&params.search));
+            runtime.block_on(call(service, Method::GET, path, None))
+        }),
+        "subscribe" => parse_params::<SubscribeParams>(&params).map(|params| {
+            let generation = subscription.fetch_add(1, Ordering::SeqCst) + 1;
+
+            runtime.spawn(subscribe(
+                Arc::clone(service),
+                format!("{}/subscribe/", api_root),
+                params.cursor,
+                Arc::clone(subscription),
+                generation,
+                output.clone(),
+            ));
+
+            Value::Null
+        }),
+        "unsubscribe" => {
+            subscription.fetch_add(1, Ordering::SeqCst);
+            Ok(Value::Null)
+        }
+        "write" => runtime.block_on(call(
+            service,
+            Method::POST,
+            format!("{}/write", api_root),
+            Some(params.to_string()),
+        )),
+        "open" => parse_params::<OpenParams>(&params).and_then(|params| {
+            let path = format!("{}/open/{}", api_root, params.id);
+            runtime.block_on(call(service, Method::POST, path, None))
+        }),
+        "resync" => runtime.block_on(call(
+            service,
+            Method::POST,
+            format!("{}/resync", api_root),
+            None,
+        )),
+        "diagnostics" => runtime.block_on(call(
+            service,
+            Method::GET,
+            format!("{}/diagnostics", api_root),
+            None,
+        )),
+        "history" => parse_params::<HistoryParams>(&params).and_then(|params| {
+            let path = match params.before {
+                Some(before) => format!("{}/history?before={}", api_root, before),
+                None => format!("{}/history", api_root),
+            };
+
+            runtime.block_on(call(service, Method::GET, path, None))
+        }),
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        )),
+    };
+
+    match result {
+        Ok(value) => RpcOutcome::Result(value),
+        Err(err) => RpcOutcome::Error(err),
+    }
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: &Value) -> Result<T, RpcError> {
+    T::deserialize(params).map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+/// Sends a request through the HTTP API's service, so that both transports
+/// always behave the same way.
+async fn call(
+    service: &RootService,
+    method: Method,
+    path: String,
+    body: Option<String>,
+) -> Result<Value, RpcError> {
+    let request = Request::builder()
+        .method(method)
+        .uri(path)
+        .body(body.map(Body::from).unwrap_or_else(Body::empty))
+        .map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))?;
+
+    let response = service.call(request).await;
+
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(|err| RpcError::new(API_ERROR, err.to_string()))?;
+
+    let value: Value = serde_json::from_slice(&body)
+        .map_err(|err| RpcError::new(API_ERROR, format!("Malformed API response: {}", err)))?;
+
+    if status.is_success() {
+        Ok(value)
+    } else {
+        let message = ErrorResponse::deserialize(&value)
+            .map(|error| error.details().to_owned())
+            .unwrap_or_else(|_| status.to_string());
+
+        Err(RpcError {
+            code: API_ERROR,
+            message,
+            data: Some(value),
+        })
+    }
+}
+
+async fn subscribe(
+    service: Arc<RootService>,
+    path_prefix: String,
+    mut cursor: u32,
+    subscription: Arc<AtomicU64>,
+    generation: u64,
+    output: Output,
+) {
+    loop {
+        let path = format!("{}{}", path_prefix, cursor);
+        let result = call(&service, Method::GET, path, None).await;
+
+        if subscription.load(Ordering::SeqCst) != generation {
+            break;
+        }
+
+        let value = match result {
+            Ok(value) => value,
+            Err(err) => {
+                log::error!("Subscription ended: {}", err.message);
+                break;
+            }
+        };
+
+        // Only the cursor is read back out, since messages with SharedStrings
+        // in them can't be deserialized into a `SubscribeResponse`.
+        let session_ending = match SubscribeProgress::deserialize(&value) {
+            Ok(response) => {
+                cursor = response.message_cursor;
+                response.session_ending
+            }
+            Err(err) => {
+                log::error!("Subscription ended: {}", err);
+                break;
+            }
+        };
+
+        output.send(&RpcMessage::Notification {
+            jsonrpc: "2.0",
+            method: "messages",
+            params: value,
+        });
+
+        if session_ending {
+            break;
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Lets the live server speak HTTPS, so that it can be reached across machines
+//! without a reverse proxy in front of it.
+
+use std::net::IpAddr;
+
+use rcgen::{Certificate, CertificateParams, DistinguishedName, DnType, SanType};
+use thiserror::Error;
+
+/// A certificate and its private key, both PEM-encoded, for the live server to
+/// serve HTTPS with.
+pub struct TlsIdentity {
+    pub cert_pem: Vec<u8>,
+    pub key_pem: Vec<u8>,
+}
+
+impl TlsIdentity {
+    /// Generates a self-signed certificate valid for `localhost` and the given
+    /// IP addresses. Clients need to be told to trust it, so it's only meant
+    /// for local use.
+    pub fn self_signed(addresses: &[IpAddr]) -> Result<Self, TlsError> {
+        let mut params = CertificateParams::new(vec!["localhost".to_owned()]);
+        params
+            .subject_alt_names
+            .extend(addresses.iter().map(|address| SanType::IpAddress(*address)));
+
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, "Rojo self-signed certificate");
+        params.distinguished_name = name;
+
+        let cert = Certificate::from_params(params)?;
+
+        Ok(TlsIdentity {
+            cert_pem: cert.serialize_pem()?.into_bytes(),
+            key_pem: cert.serialize_private_key_pem().into_bytes(),
+        })
+    }
+
+    pub(super) fn acceptor(&self) -> Result<tokio_native_tls::TlsAcceptor, TlsError> {
+        let identity = native_tls::Identity::from_pkcs8(&self.cert_pem, &self.key_pem)?;
+        let acceptor = native_tls::TlsAcceptor::new(identity)?;
+
+        Ok(acceptor.into())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("Could not generate a self-signed certificate")]
+    Generate {
+        #[from]
+        source: rcgen::RcgenError,
+    },
+
+    #[error("Invalid TLS certificate or key")]
+    Identity {
+        #[from]
+        source: native_tls::Error,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn self_signed_identity_is_usable() {
+        let identity = TlsIdentity::self_signed(&[Ipv4Addr::LOCALHOST.into()]).unwrap();
+
+        assert!(identity
+            .cert_pem
+            .starts_with(b"-----BEGIN CERTIFICATE-----"));
+        identity.acceptor().unwrap();
+    }
+
+    #[test]
+    fn invalid_key_is_rejected() {
+        let identity = TlsIdentity {
+            key_pem: b"not a key".to_vec(),
+            ..TlsIdentity::self_signed(&[]).unwrap()
+        };
+
+        assert!(identity.acceptor().is_err());
+    }
+}
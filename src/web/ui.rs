@@ -1,58 +1,57 @@
 //! Defines the HTTP-based Rojo UI. It uses ritz for templating, which is like
-//! JSX for Rust. Eventually we should probably replace this with a new
-//! framework, maybe using JS and client side rendering.
+//! JSX for Rust. Pages that need to stay live, like the instance browser, are
+//! rendered on the client by scripts in the assets folder instead.
 //!
 //! These endpoints generally return HTML and SVG.
 
-use std::{borrow::Cow, sync::Arc, time::Duration};
+use std::{sync::Arc, time::Duration};
 
-use futures::{future, Future};
-use hyper::{header, service::Service, Body, Method, Request, Response, StatusCode};
-use maplit::hashmap;
-use rbx_dom_weak::types::{Ref, Variant};
-use ritz::{html, Fragment, HtmlContent, HtmlSelfClosingTag};
+use hyper::{header, Body, Method, Request, Response, StatusCode};
+use ritz::{html, Fragment, HtmlContent};
 
 use crate::{
+    diagnostics::Diagnostic,
+    metrics::Phase,
     serve_session::ServeSession,
-    snapshot::RojoTree,
     web::{
         assets,
         interface::{ErrorResponse, SERVER_VERSION},
+        metrics,
         util::json,
     },
 };
 
 pub struct UiService {
     serve_session: Arc<ServeSession>,
+
+    /// Every session being served, including `serve_session`. Only used for
+    /// `/metrics`, which covers all of them.
+    all_sessions: Arc<[Arc<ServeSession>]>,
 }
 
-impl Service for UiService {
-    type ReqBody = Body;
-    type ResBody = Body;
-    type Error = hyper::Error;
-    type Future = Box<dyn Future<Item = Response<Self::ReqBody>, Error = Self::Error> + Send>;
+impl UiService {
+    pub fn new(serve_session: Arc<ServeSession>, all_sessions: Arc<[Arc<ServeSession>]>) -> Self {
+        UiService {
+            serve_session,
+            all_sessions,
+        }
+    }
 
-    fn call(&mut self, request: Request<Self::ReqBody>) -> Self::Future {
-        let response = match (request.method(), request.uri().path()) {
-            (&Method::GET, "/") => self.handle_home(),
+    /// Serves a UI page. `authorized` is whether the request carries the
+    /// server's token, or the server doesn't need one.
+    pub async fn call(&self, request: Request<Body>, authorized: bool) -> Response<Body> {
+        match (request.method(), request.uri().path()) {
+            (&Method::GET, "/") => self.handle_home(authorized),
             (&Method::GET, "/logo.png") => self.handle_logo(),
             (&Method::GET, "/icon.png") => self.handle_icon(),
             (&Method::GET, "/show-instances") => self.handle_show_instances(),
-            (_method, path) => {
-                return json(
-                    ErrorResponse::not_found(format!("Route not found: {}", path)),
-                    StatusCode::NOT_FOUND,
-                )
-            }
-        };
-
-        Box::new(future::ok(response))
-    }
-}
-
-impl UiService {
-    pub fn new(serve_session: Arc<ServeSession>) -> Self {
-        UiService { serve_session }
+            (&Method::GET, "/history") => self.handle_history(),
+            (&Method::GET, "/metrics") => self.handle_metrics(),
+            (_method, path) => json(
+                ErrorResponse::not_found(format!("Route not found: {}", path)),
+                StatusCode::NOT_FOUND,
+            ),
+        }
     }
 
     fn handle_logo(&self) -> Response<Body> {
@@ -69,12 +68,32 @@ impl UiService {
             .unwrap()
     }
 
-    fn handle_home(&self) -> Response<Body> {
+    fn handle_home(&self, authorized: bool) -> Response<Body> {
+        // Problems include full paths and parts of files, which anyone who can
+        // reach a server with a token shouldn't see.
+        let problems = if authorized {
+            html! {
+                <>
+                    { Self::watcher_errors(self.serve_session.watcher_errors()) }
+                    { Self::diagnostics(self.serve_session.diagnostics()) }
+                </>
+            }
+        } else {
+            html! {
+                <p class="history-note">"Problems with project files are only shown to clients that send this server's token."</p>
+            }
+        };
+
         let page = self.normal_page(html! {
-            <div class="button-list">
-                { Self::button("Rojo Documentation", "https://rojo.space/docs") }
-                { Self::button("View instance tree state", "/show-instances") }
-            </div>
+            <>
+                { problems }
+                <div class="button-list">
+                    { Self::button("Rojo Documentation", "https://rojo.space/docs") }
+                    { Self::button("View instance tree state", "/show-instances") }
+                    { Self::button("View change history", "/history") }
+                </div>
+                { self.performance() }
+            </>
         });
 
         Response::builder()
@@ -83,12 +102,27 @@ impl UiService {
             .unwrap()
     }
 
+    /// Shows the instance browser. The page itself is only a shell; the tree is
+    /// read from the API by `show-instances.js` as it's browsed.
     fn handle_show_instances(&self) -> Response<Body> {
-        let tree = self.serve_session.tree();
-        let root_id = tree.get_root_id();
-
         let page = self.normal_page(html! {
-            { Self::instance(&tree, root_id) }
+            <div class="instance-browser">
+                <div class="instance-toolbar">
+                    <input id="instance-search" class="instance-search" type="search" placeholder="Search by name, class, or path" />
+                    <div id="instance-status" class="instance-status">"Loading..."</div>
+                </div>
+                <div class="instance-panes">
+                    <div id="instance-tree" class="instance-tree"></div>
+                    <div id="instance-details" class="instance-details"></div>
+                </div>
+                <section class="recent-changes">
+                    <h1>"Recent changes"</h1>
+                    <ul id="recent-changes"></ul>
+                </section>
+                <script>
+                    { ritz::UnescapedText::new(assets::show_instances_js()) }
+                </script>
+            </div>
         });
 
         Response::builder()
@@ -97,127 +131,142 @@ impl UiService {
             .unwrap()
     }
 
-    fn instance(tree: &RojoTree, id: Ref) -> HtmlContent<'_> {
-        let instance = tree.get_instance(id).unwrap();
-        let children_list: Vec<_> = instance
-            .children()
-            .iter()
-            .copied()
-            .map(|id| Self::instance(tree, id))
-            .collect();
-
-        let children_container = if children_list.is_empty() {
-            HtmlContent::None
-        } else {
-            let section = ExpandableSection {
-                title: "Children",
-                class_name: "instance-children",
-                id,
-                expanded: true,
-                content: html! {
-                    { Fragment::new(children_list) }
-                },
-            };
-
-            section.render()
-        };
+    fn handle_metrics(&self) -> Response<Body> {
+        Response::builder()
+            .header(header::CONTENT_TYPE, metrics::CONTENT_TYPE)
+            .body(Body::from(metrics::render(&self.all_sessions)))
+            .unwrap()
+    }
 
-        let mut properties: Vec<_> = instance.properties().iter().collect();
-        properties.sort_by_key(|pair| pair.0);
+    /// Shows how long syncing changes has taken and how much has been read, to
+    /// help find out why syncing is slow.
+    fn performance(&self) -> HtmlContent<'static> {
+        let session_metrics = self.serve_session.metrics();
+        let timings = Phase::ALL.iter().map(|&phase| {
+            let timing = session_metrics.timing(phase);
 
-        let property_list: Vec<_> = properties
-            .into_iter()
-            .map(|(key, value)| {
-                html! {
-                    <div class="instance-property" title={ Self::display_value(value) }>
-                        { key.clone() } ": " { format!("{:?}", value.ty()) }
-                    </div>
-                }
-            })
-            .collect();
+            html! {
+                <tr>
+                    <td>{ phase.description() }</td>
+                    <td>{ timing.count.to_string() }</td>
+                    <td>{ Self::format_duration(timing.average()) }</td>
+                    <td>{ Self::format_duration(timing.max) }</td>
+                    <td>{ Self::format_duration(timing.last) }</td>
+                </tr>
+            }
+        });
 
-        let property_container = if property_list.is_empty() {
-            HtmlContent::None
-        } else {
-            let section = ExpandableSection {
-                title: "Properties",
-                class_name: "instance-properties",
-                id,
-                expanded: false,
-                content: html! {
-                    { Fragment::new(property_list) }
-                },
-            };
-
-            section.render()
-        };
+        let vfs_stats = self.serve_session.vfs().stats();
+        let instance_count = self.serve_session.tree().instance_count();
+        let message_count = self.serve_session.message_queue().cursor();
 
-        let metadata_container = {
-            let metadata = instance.metadata();
-
-            let relevant_paths = if metadata.relevant_paths.is_empty() {
-                HtmlContent::None
-            } else {
-                let list = metadata
-                    .relevant_paths
-                    .iter()
-                    .map(|path| html! { <li>{ format!("{}", path.display()) }</li> });
-
-                html! {
-                    <div>
-                        "relevant_paths: "
-                        <ul class="path-list">{ Fragment::new(list) }</ul>
-                    </div>
-                }
-            };
+        html! {
+            <section class="performance">
+                <h1 class="section-title">"Performance"</h1>
+                <table class="property-table">
+                    <tr>
+                        <td><strong>"Step"</strong></td>
+                        <td><strong>"Runs"</strong></td>
+                        <td><strong>"Average"</strong></td>
+                        <td><strong>"Longest"</strong></td>
+                        <td><strong>"Last"</strong></td>
+                    </tr>
+                    { Fragment::new(timings) }
+                </table>
+                <div class="performance-stats">
+                    { Self::stat_item("Instances", instance_count.to_string()) }
+                    { Self::stat_item("Messages sent", message_count.to_string()) }
+                    { Self::stat_item("Files read", vfs_stats.files_read.to_string()) }
+                    { Self::stat_item("Data read", Self::format_bytes(vfs_stats.bytes_read)) }
+                    { Self::stat_item("Directories listed", vfs_stats.dirs_read.to_string()) }
+                </div>
+                <p>"These are also available for Prometheus at " <a href="/metrics">"/metrics"</a> "."</p>
+            </section>
+        }
+    }
 
-            let content = html! {
-                <>
-                    <div>"ignore_unknown_instances: " { metadata.ignore_unknown_instances.to_string() }</div>
-                    <div>"instigating source: " { format!("{:?}", metadata.instigating_source) }</div>
-                    { relevant_paths }
-                </>
-            };
+    fn format_duration(duration: Duration) -> String {
+        format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+    }
 
-            let section = ExpandableSection {
-                title: "Metadata",
-                class_name: "instance-metadata",
-                id,
-                expanded: false,
-                content,
-            };
+    fn format_bytes(bytes: u64) -> String {
+        const UNITS: &[&str] = &["KiB", "MiB", "GiB"];
 
-            section.render()
-        };
+        if bytes < 1024 {
+            return format!("{} B", bytes);
+        }
 
-        let class_name_specifier = if instance.name() == instance.class_name() {
-            HtmlContent::None
-        } else {
-            html! {
-                <span>
-                    " (" { instance.class_name().to_owned() } ")"
-                </span>
-            }
-        };
+        let mut value = bytes as f64 / 1024.0;
+        let mut unit = 0;
+
+        while value >= 1024.0 && unit + 1 < UNITS.len() {
+            value /= 1024.0;
+            unit += 1;
+        }
+
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+
+    /// Lists the changes made to the tree, newest first. Like the instance
+    /// browser, the page is only a shell; `history.js` reads the entries from
+    /// the API, which needs the server's token if it has one.
+    fn handle_history(&self) -> Response<Body> {
+        let page = self.normal_page(html! {
+            <div class="history">
+                <h1 class="section-title">"Change history"</h1>
+                <div id="history-status" class="history-status">"Loading..."</div>
+                <ol id="history-list" class="history-list"></ol>
+                <div class="button-list">
+                    <button id="history-older" class="button" hidden="">"Older changes"</button>
+                </div>
+                <script>
+                    { ritz::UnescapedText::new(assets::history_js()) }
+                </script>
+            </div>
+        });
+
+        Response::builder()
+            .header(header::CONTENT_TYPE, "text/html")
+            .body(Body::from(format!("<!DOCTYPE html>{}", page)))
+            .unwrap()
+    }
+
+    fn watcher_errors(errors: Vec<String>) -> HtmlContent<'static> {
+        if errors.is_empty() {
+            return HtmlContent::None;
+        }
+
+        let list = errors.into_iter().map(|error| html! { <li>{ error }</li> });
 
         html! {
-            <div class="instance">
-                <label class="instance-title" for={ format!("instance-{:?}", id) }>
-                    { instance.name().to_owned() }
-                    { class_name_specifier }
-                </label>
-                { metadata_container }
-                { property_container }
-                { children_container }
+            <div class="watcher-errors">
+                <h1>"File watcher errors"</h1>
+                <p>"Some changes may not have been synced. Resyncing or restarting the server will pick them up."</p>
+                <ul>{ Fragment::new(list) }</ul>
             </div>
         }
     }
 
-    fn display_value(value: &Variant) -> String {
-        match value {
-            Variant::String(value) => value.clone(),
-            Variant::Bool(value) => value.to_string(),
-            _ => format!("{:?}", value),
+    fn diagnostics(diagnostics: Vec<Diagnostic>) -> HtmlContent<'static> {
+        if diagnostics.is_empty() {
+            return HtmlContent::None;
+        }
+
+        let list = diagnostics.into_iter().map(|diagnostic| {
+            html! {
+                <li>
+                    <code>{ diagnostic.path.display().to_string() }</code>
+                    <pre>{ diagnostic.message }</pre>
+                </li>
+            }
+        });
+
+        html! {
+            <div class="diagnostics">
+                <h1>"Problems with project files"</h1>
+                <p>"These files couldn't be synced. Rojo will try again when they change."</p>
+                <ul>{ Fragment::new(list) }</ul>
+            </div>
         }
     }
 
@@ -288,50 +337,3 @@ impl UiService {
         }
     }
 }
-
-struct ExpandableSection<'a> {
-    title: &'a str,
-    class_name: &'a str,
-    id: Ref,
-    expanded: bool,
-    content: HtmlContent<'a>,
-}
-
-impl<'a> ExpandableSection<'a> {
-    fn render(self) -> HtmlContent<'a> {
-        let input_id = format!("{}-{:?}", self.class_name, self.id);
-
-        // We need to specify this input manually because Ritz doesn't have
-        // support for conditional attributes like `checked`.
-        let mut input = HtmlSelfClosingTag {
-            name: Cow::Borrowed("input"),
-            attributes: hashmap! {
-                Cow::Borrowed("class") => Cow::Borrowed("expandable-input"),
-                Cow::Borrowed("id") => Cow::Owned(input_id.clone()),
-                Cow::Borrowed("type") => Cow::Borrowed("checkbox"),
-            },
-        };
-
-        if self.expanded {
-            input
-                .attributes
-                .insert(Cow::Borrowed("checked"), Cow::Borrowed("checked"));
-        }
-
-        html! {
-            <section class="expandable-section">
-                { input }
-
-                <h1 class="expandable-label">
-                    <label for={ input_id.clone() }>
-                        <span class="expandable-visualizer"></span>
-                        { self.title }
-                    </label>
-                </h1>
-                <div class={ format!("expandable-items {}", self.class_name) }>
-                    { self.content }
-                </div>
-            </section>
-        }
-    }
-}
@@ -1,23 +1,62 @@
-use futures::{future, Future};
 use hyper::{header::CONTENT_TYPE, Body, Response, StatusCode};
 use serde::Serialize;
 
-/// Respond to a request with JSON and the given status code.
-pub fn json<T: Serialize>(
-    value: T,
-    code: StatusCode,
-) -> Box<dyn Future<Item = hyper::Response<hyper::Body>, Error = hyper::Error> + Send> {
-    Box::new(future::ok(response_json(value, code)))
-}
-
 /// Respond to a request with a 200 OK response containing JSON.
-pub fn json_ok<T: Serialize>(
-    value: T,
-) -> Box<dyn Future<Item = hyper::Response<hyper::Body>, Error = hyper::Error> + Send> {
+pub fn json_ok<T: Serialize>(value: T) -> Response<Body> {
     json(value, StatusCode::OK)
 }
 
-fn response_json<T: Serialize>(value: T, code: StatusCode) -> Response<Body> {
+/// Percent-encodes a string to use as a URL path segment, like a project name
+/// in an API route.
+pub fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+/// Decodes a percent-encoded URL path segment, like a project name in an API
+/// route. Returns `None` if the segment isn't valid.
+pub fn percent_decode(input: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut input = input.bytes();
+
+    while let Some(byte) = input.next() {
+        if byte == b'%' {
+            let hex = [input.next()?, input.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Finds the value of a parameter in a URL's query string, like `depth` in
+/// `?depth=1`. Returns `None` if the parameter isn't there or can't be decoded.
+pub fn query_param(query: Option<&str>, name: &str) -> Option<String> {
+    query?
+        .split('&')
+        .map(|pair| match pair.find('=') {
+            Some(index) => (&pair[..index], &pair[index + 1..]),
+            None => (pair, ""),
+        })
+        .find(|(key, _)| *key == name)
+        .and_then(|(_, value)| percent_decode(value))
+}
+
+/// Respond to a request with JSON and the given status code.
+pub fn json<T: Serialize>(value: T, code: StatusCode) -> Response<Body> {
     let serialized = match serde_json::to_string(&value) {
         Ok(v) => v,
         Err(err) => {
@@ -35,3 +74,35 @@ fn response_json<T: Serialize>(value: T, code: StatusCode) -> Response<Body> {
         .body(Body::from(serialized))
         .unwrap()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(percent_decode("Lobby").as_deref(), Some("Lobby"));
+        assert_eq!(percent_decode("My%20Game").as_deref(), Some("My Game"));
+        assert_eq!(percent_decode("%E2%9C%A8").as_deref(), Some("\u{2728}"));
+        assert_eq!(percent_decode("bad%2"), None);
+        assert_eq!(percent_decode("bad%zz"), None);
+    }
+
+    #[test]
+    fn query() {
+        let query = Some("depth=1&sources=true&q=My%20Game");
+        assert_eq!(query_param(query, "depth").as_deref(), Some("1"));
+        assert_eq!(query_param(query, "q").as_deref(), Some("My Game"));
+        assert_eq!(query_param(query, "missing"), None);
+        assert_eq!(query_param(None, "depth"), None);
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        for input in &["Lobby", "My Game", "a/b%c", "\u{2728}"] {
+            let encoded = percent_encode(input);
+            assert!(!encoded.contains('/') && !encoded.contains(' '));
+            assert_eq!(percent_decode(&encoded).as_deref(), Some(*input));
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::{
     fs,
     path::{Path, PathBuf},
-    process::Command,
+    process::{Command, ExitStatus},
     sync::atomic::{AtomicUsize, Ordering},
     thread,
     time::Duration,
@@ -11,7 +11,10 @@ use rbx_dom_weak::types::Ref;
 
 use tempfile::{tempdir, TempDir};
 
-use librojo::web_api::{ReadResponse, ServerInfoResponse, SubscribeResponse};
+use librojo::web_api::{
+    DiagnosticsResponse, HistoryResponse, ReadResponse, ResyncResponse, SearchResponse,
+    ServerInfoResponse, SubscribeResponse,
+};
 use rojo_insta_ext::RedactionMap;
 
 use crate::rojo_test::io_util::{
@@ -26,11 +29,20 @@ use crate::rojo_test::io_util::{
 /// The passed in callback is where the actual test body should go. Setup and
 /// cleanup happens automatically.
 pub fn run_serve_test(test_name: &str, callback: impl FnOnce(TestServeSession, RedactionMap)) {
+    run_multi_serve_test(&[test_name], callback)
+}
+
+/// Like `run_serve_test`, but serves several projects from the `serve-tests`
+/// folder with one `rojo serve` process.
+pub fn run_multi_serve_test(
+    test_names: &[&str],
+    callback: impl FnOnce(TestServeSession, RedactionMap),
+) {
     let _ = env_logger::try_init();
 
     let mut redactions = RedactionMap::new();
 
-    let mut session = TestServeSession::new(test_name);
+    let mut session = TestServeSession::new(test_names);
     let info = session.wait_to_come_online();
 
     redactions.intern(info.session_id);
@@ -57,38 +69,60 @@ pub struct TestServeSession {
     _dir: TempDir,
 
     port: usize,
-    project_path: PathBuf,
+    project_paths: Vec<PathBuf>,
+    token: Option<String>,
+    tls_cert: Option<PathBuf>,
 }
 
 impl TestServeSession {
-    pub fn new(name: &str) -> Self {
-        let working_dir = get_working_dir_path();
+    pub fn new(names: &[&str]) -> Self {
+        Self::with_options(names, &[], None)
+    }
 
-        let source_path = Path::new(SERVE_TESTS_PATH).join(name);
+    /// Starts a session with extra arguments passed to `rojo serve`. If a token
+    /// is given, the server requires it and it's sent with every request made
+    /// through the session.
+    pub fn with_options(names: &[&str], extra_args: &[&str], token: Option<&str>) -> Self {
+        let working_dir = get_working_dir_path();
         let dir = tempdir().expect("Couldn't create temporary directory");
-        let project_path = dir.path().join(name);
 
-        let source_is_file = fs::metadata(&source_path).unwrap().is_file();
+        let project_paths: Vec<PathBuf> = names
+            .iter()
+            .map(|name| {
+                let source_path = Path::new(SERVE_TESTS_PATH).join(name);
+                let project_path = dir.path().join(name);
 
-        if source_is_file {
-            fs::copy(&source_path, &project_path).expect("couldn't copy project file");
-        } else {
-            fs::create_dir(&project_path).expect("Couldn't create temporary project subdirectory");
+                let source_is_file = fs::metadata(&source_path).unwrap().is_file();
 
-            copy_recursive(&source_path, &project_path)
-                .expect("Couldn't copy project to temporary directory");
-        };
+                if source_is_file {
+                    fs::copy(&source_path, &project_path).expect("couldn't copy project file");
+                } else {
+                    fs::create_dir(&project_path)
+                        .expect("Couldn't create temporary project subdirectory");
+
+                    copy_recursive(&source_path, &project_path)
+                        .expect("Couldn't copy project to temporary directory");
+                };
+
+                project_path
+            })
+            .collect();
 
         let port = get_port_number();
         let port_string = port.to_string();
 
-        let rojo_process = Command::new(ROJO_PATH)
-            .args(&[
-                "serve",
-                project_path.to_str().unwrap(),
-                "--port",
-                port_string.as_str(),
-            ])
+        let mut command = Command::new(ROJO_PATH);
+        command
+            .arg("serve")
+            .args(&project_paths)
+            .args(&["--port", port_string.as_str()])
+            .args(extra_args);
+
+        if let Some(token) = token {
+            command.arg(format!("--token={}", token));
+        }
+
+        let rojo_process = command
             .current_dir(working_dir)
             .spawn()
             .expect("Couldn't start Rojo");
@@ -97,12 +131,85 @@ impl TestServeSession {
             rojo_process: KillOnDrop(rojo_process),
             _dir: dir,
             port,
-            project_path,
+            project_paths,
+            token: token.map(str::to_owned),
+            tls_cert: None,
+        }
+    }
+
+    /// Talks to the server over HTTPS from now on, trusting the certificate at
+    /// the given path. The certificate is read for every request, so it can be
+    /// one that Rojo hasn't generated yet.
+    pub fn with_tls(self, cert_path: PathBuf) -> Self {
+        TestServeSession {
+            tls_cert: Some(cert_path),
+            ..self
+        }
+    }
+
+    pub fn port(&self) -> usize {
+        self.port
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        let scheme = if self.tls_cert.is_some() {
+            "https"
+        } else {
+            "http"
+        };
+
+        format!("{}://localhost:{}{}", scheme, self.port, path)
+    }
+
+    /// A client that trusts the session's certificate, if it's using one. Until
+    /// the certificate exists, requests fail like the server is still starting.
+    pub fn client(&self) -> reqwest::Client {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(cert) = self.tls_cert.as_ref().and_then(|path| fs::read(path).ok()) {
+            let cert = reqwest::Certificate::from_pem(&cert).expect("Invalid certificate");
+            builder = builder.add_root_certificate(cert);
         }
+
+        builder.build().expect("Couldn't create HTTP client")
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.client().request(method, &self.url(path));
+
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends a signal like `INT` or `TERM` to `rojo serve`, then waits for it
+    /// to exit.
+    #[cfg(unix)]
+    pub fn stop_with_signal(&mut self, signal: &str) -> ExitStatus {
+        const MAX_WAIT: Duration = Duration::from_secs(10);
+
+        let pid = self.rojo_process.0.id().to_string();
+        let status = Command::new("kill")
+            .args(&[format!("-{}", signal).as_str(), pid.as_str()])
+            .status()
+            .expect("Couldn't run kill");
+        assert!(status.success(), "Couldn't signal Rojo");
+
+        let start = std::time::Instant::now();
+        while start.elapsed() < MAX_WAIT {
+            if let Some(status) = self.rojo_process.0.try_wait().unwrap() {
+                return status;
+            }
+
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        panic!("Rojo did not exit within {:?} of SIG{}", MAX_WAIT, signal);
     }
 
     pub fn path(&self) -> &Path {
-        &self.project_path
+        &self.project_paths[0]
     }
 
     /// Waits for the `rojo serve` server to come online with expontential
@@ -140,26 +247,72 @@ impl TestServeSession {
     }
 
     pub fn get_api_rojo(&self) -> Result<ServerInfoResponse, reqwest::Error> {
-        let url = format!("http://localhost:{}/api/rojo", self.port);
-        let body = reqwest::get(&url)?.text()?;
+        self.get_api_rojo_at("/api")
+    }
+
+    /// Like `get_api_rojo`, but for the API rooted at the given path, like
+    /// `/api/<project name>` when serving several projects.
+    pub fn get_api_rojo_at(&self, api_root: &str) -> Result<ServerInfoResponse, reqwest::Error> {
+        let path = format!("{}/rojo", api_root);
+        let body = self.request(reqwest::Method::GET, &path).send()?.text()?;
 
         Ok(serde_json::from_str(&body).expect("Server returned malformed response"))
     }
 
     pub fn get_api_read(&self, id: Ref) -> Result<ReadResponse, reqwest::Error> {
-        let url = format!("http://localhost:{}/api/read/{}", self.port, id);
-        let body = reqwest::get(&url)?.text()?;
+        self.get_api_read_at("/api", id)
+    }
+
+    pub fn get_api_read_at(&self, api_root: &str, id: Ref) -> Result<ReadResponse, reqwest::Error> {
+        let path = format!("{}/read/{}", api_root, id);
+        let body = self.request(reqwest::Method::GET, &path).send()?.text()?;
 
         Ok(serde_json::from_str(&body).expect("Server returned malformed response"))
     }
 
+    /// Like `get_api_read`, but with options for the query string, like
+    /// `depth=1`.
+    pub fn get_api_read_with(
+        &self,
+        id: Ref,
+        options: &str,
+    ) -> Result<ReadResponse<'static>, reqwest::Error> {
+        let path = format!("/api/read/{}?{}", id, options);
+
+        self.request(reqwest::Method::GET, &path).send()?.json()
+    }
+
+    pub fn get_api_search(&self, search: &str) -> Result<SearchResponse<'static>, reqwest::Error> {
+        let path = format!("/api/search/{}", search);
+
+        self.request(reqwest::Method::GET, &path).send()?.json()
+    }
+
     pub fn get_api_subscribe(
         &self,
         cursor: u32,
     ) -> Result<SubscribeResponse<'static>, reqwest::Error> {
-        let url = format!("http://localhost:{}/api/subscribe/{}", self.port, cursor);
+        let path = format!("/api/subscribe/{}", cursor);
+
+        self.request(reqwest::Method::GET, &path).send()?.json()
+    }
+
+    pub fn get_api_diagnostics(&self) -> Result<DiagnosticsResponse, reqwest::Error> {
+        self.request(reqwest::Method::GET, "/api/diagnostics")
+            .send()?
+            .json()
+    }
+
+    pub fn get_api_history(&self) -> Result<HistoryResponse, reqwest::Error> {
+        self.request(reqwest::Method::GET, "/api/history")
+            .send()?
+            .json()
+    }
 
-        reqwest::get(&url)?.json()
+    pub fn post_api_resync(&self) -> Result<ResyncResponse, reqwest::Error> {
+        self.request(reqwest::Method::POST, "/api/resync")
+            .send()?
+            .json()
     }
 }
 
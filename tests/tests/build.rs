@@ -3,7 +3,9 @@ use std::{fs, path::Path, process::Command};
 use insta::assert_snapshot;
 use tempfile::tempdir;
 
-use crate::rojo_test::io_util::{get_working_dir_path, BUILD_TESTS_PATH, ROJO_PATH};
+use crate::rojo_test::io_util::{
+    copy_recursive, get_working_dir_path, BUILD_TESTS_PATH, ROJO_PATH,
+};
 
 macro_rules! gen_build_tests {
     ( $($test_name: ident,)* ) => {
@@ -89,3 +91,138 @@ fn run_build_test(test_name: &str) {
         assert_snapshot!(test_name, contents);
     });
 }
+
+#[test]
+fn build_from_git_revision() {
+    let _ = env_logger::try_init();
+
+    let repo_dir = tempdir().expect("couldn't create temporary directory");
+    let repo_path = repo_dir.path();
+    copy_recursive(&Path::new(BUILD_TESTS_PATH).join("txt"), repo_path)
+        .expect("couldn't copy test project");
+
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(repo_path)
+            .env("GIT_AUTHOR_NAME", "rojo")
+            .env("GIT_AUTHOR_EMAIL", "rojo@example.com")
+            .env("GIT_COMMITTER_NAME", "rojo")
+            .env("GIT_COMMITTER_EMAIL", "rojo@example.com")
+            .status()
+            .expect("Couldn't start git");
+
+        assert!(status.success(), "git {:?} failed", args);
+    };
+
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "Initial"]);
+
+    // Only the committed contents should end up in the build.
+    fs::write(repo_path.join("foo.txt"), "uncommitted").unwrap();
+
+    let output_dir = tempdir().expect("couldn't create temporary directory");
+    let output_path = output_dir.path().join("txt.rbxmx");
+
+    let status = Command::new(ROJO_PATH)
+        .args(&[
+            "build",
+            repo_path.to_str().unwrap(),
+            "--rev",
+            "HEAD",
+            "-o",
+            output_path.to_str().unwrap(),
+        ])
+        .env("RUST_LOG", "error")
+        .current_dir(get_working_dir_path())
+        .status()
+        .expect("Couldn't start Rojo");
+
+    assert!(status.success(), "Rojo did not exit successfully");
+
+    let contents = fs::read_to_string(&output_path).expect("Couldn't read output file");
+    let committed = fs::read_to_string(Path::new(BUILD_TESTS_PATH).join("txt/foo.txt")).unwrap();
+
+    assert!(contents.contains(committed.trim()));
+    assert!(!contents.contains("uncommitted"));
+}
+
+#[test]
+fn build_from_archive() {
+    let _ = env_logger::try_init();
+
+    let archive_dir = tempdir().expect("couldn't create temporary directory");
+    let archive_path = archive_dir.path().join("txt.tar.gz");
+
+    let status = Command::new("tar")
+        .args(&["-czf", archive_path.to_str().unwrap(), "-C"])
+        .arg(Path::new(BUILD_TESTS_PATH).join("txt"))
+        .arg(".")
+        .status()
+        .expect("Couldn't start tar");
+
+    assert!(status.success(), "tar did not exit successfully");
+
+    let output_dir = tempdir().expect("couldn't create temporary directory");
+    let output_path = output_dir.path().join("txt.rbxmx");
+
+    let status = Command::new(ROJO_PATH)
+        .args(&[
+            "build",
+            archive_path.to_str().unwrap(),
+            "-o",
+            output_path.to_str().unwrap(),
+        ])
+        .env("RUST_LOG", "error")
+        .current_dir(get_working_dir_path())
+        .status()
+        .expect("Couldn't start Rojo");
+
+    assert!(status.success(), "Rojo did not exit successfully");
+
+    let contents = fs::read_to_string(&output_path).expect("Couldn't read output file");
+    let expected = fs::read_to_string(Path::new(BUILD_TESTS_PATH).join("txt/foo.txt")).unwrap();
+
+    assert!(contents.contains(expected.trim()));
+}
+
+#[test]
+fn build_with_overlays() {
+    let _ = env_logger::try_init();
+
+    let overlay_dir = tempdir().expect("couldn't create temporary directory");
+    let overlay_folder = overlay_dir.path().join("folder");
+    fs::create_dir(&overlay_folder).unwrap();
+    fs::write(overlay_folder.join("foo.txt"), "from folder overlay").unwrap();
+
+    let overlay_json = overlay_dir.path().join("overlay.json");
+    fs::write(&overlay_json, r#"{ "foo.txt": "from JSON overlay" }"#).unwrap();
+
+    let output_dir = tempdir().expect("couldn't create temporary directory");
+    let output_path = output_dir.path().join("txt.rbxmx");
+
+    let status = Command::new(ROJO_PATH)
+        .args(&[
+            "build",
+            Path::new(BUILD_TESTS_PATH).join("txt").to_str().unwrap(),
+            "--overlay",
+            overlay_folder.to_str().unwrap(),
+            "--overlay",
+            overlay_json.to_str().unwrap(),
+            "-o",
+            output_path.to_str().unwrap(),
+        ])
+        .env("RUST_LOG", "error")
+        .current_dir(get_working_dir_path())
+        .status()
+        .expect("Couldn't start Rojo");
+
+    assert!(status.success(), "Rojo did not exit successfully");
+
+    // Later overlays win over earlier ones, which win over the real files.
+    let contents = fs::read_to_string(&output_path).expect("Couldn't read output file");
+    assert!(contents.contains("from JSON overlay"));
+    assert!(!contents.contains("from folder overlay"));
+    assert!(!contents.contains("This is a txt file in a project."));
+}
@@ -1,9 +1,27 @@
-use std::fs;
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    process::{ChildStdin, Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
 
+use flate2::read::{GzDecoder, ZlibDecoder};
 use insta::assert_yaml_snapshot;
-use tempfile::tempdir;
+use rbx_dom_weak::types::Variant;
+use serde_json::{json, Value};
+use tempfile::{tempdir, TempDir};
 
-use crate::rojo_test::{internable::InternAndRedact, serve_util::run_serve_test};
+use librojo::web_api::{InstigatingSource, PatchCause, ReadResponse, SubscribeResponse};
+
+use crate::rojo_test::{
+    internable::InternAndRedact,
+    io_util::{copy_recursive, KillOnDrop, ROJO_PATH, SERVE_TESTS_PATH},
+    serve_util::{run_multi_serve_test, run_serve_test, TestServeSession},
+};
 
 #[test]
 fn empty() {
@@ -220,3 +238,951 @@ fn empty_json_model() {
         );
     });
 }
+
+#[test]
+fn resync() {
+    run_serve_test("scripts", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "resync_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        // Nothing drifted, so resyncing shouldn't send any changes or change
+        // the session.
+        let resync_response = session.post_api_resync().unwrap();
+        assert_eq!(resync_response.session_id, info.session_id);
+        assert_eq!(resync_response.message_cursor, 0);
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "resync_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+    });
+}
+
+#[test]
+fn resync_drift() {
+    // The poll watcher won't scan again during the test, so edits go unnoticed
+    // until the project is resynced.
+    let mut session = TestServeSession::with_options(
+        &["scripts"],
+        &["--watcher", "poll", "--poll-interval", "600000"],
+        None,
+    );
+    let info = session.wait_to_come_online();
+
+    fs::write(session.path().join("src/foo.lua"), "-- Changed on disk").unwrap();
+
+    let resync_response = session.post_api_resync().unwrap();
+    assert_eq!(resync_response.session_id, info.session_id);
+    assert_eq!(resync_response.message_cursor, 1);
+
+    let subscribe_response = session.get_api_subscribe(0).unwrap();
+    assert_eq!(subscribe_response.message_cursor, 1);
+
+    let update = &subscribe_response.messages[0].updated[0];
+    assert_eq!(
+        update.set_properties.get("Source"),
+        Some(&Variant::String("-- Changed on disk".to_owned()))
+    );
+
+    let history = session.get_api_history().unwrap();
+    assert!(matches!(history.entries[0].cause, Some(PatchCause::Resync)));
+}
+
+#[test]
+fn resync_missing_project() {
+    let mut session = TestServeSession::with_options(
+        &["scripts"],
+        &["--watcher", "poll", "--poll-interval", "600000"],
+        None,
+    );
+    let info = session.wait_to_come_online();
+
+    let project_path = session.path().to_path_buf();
+    fs::remove_dir_all(&project_path).unwrap();
+
+    let response = session
+        .client()
+        .post(&session.url("/api/resync"))
+        .send()
+        .unwrap();
+    assert_eq!(
+        response.status(),
+        reqwest::StatusCode::INTERNAL_SERVER_ERROR
+    );
+
+    // The session keeps its tree and resyncs fine once the project is back.
+    copy_recursive(&Path::new(SERVE_TESTS_PATH).join("scripts"), &project_path).unwrap();
+
+    let resync_response = session.post_api_resync().unwrap();
+    assert_eq!(resync_response.session_id, info.session_id);
+    assert_eq!(resync_response.message_cursor, 0);
+}
+
+#[test]
+fn multiple_projects() {
+    run_multi_serve_test(&["empty", "scripts"], |session, _redactions| {
+        // The first project is served like it would be on its own, and every
+        // project is listed.
+        let info = session.get_api_rojo().unwrap();
+        assert_eq!(info.project_name, "empty");
+
+        let project_names: Vec<_> = info
+            .projects
+            .iter()
+            .map(|project| project.project_name.as_str())
+            .collect();
+        assert_eq!(project_names, vec!["empty", "scripts"]);
+
+        let scripts_info = session.get_api_rojo_at("/api/scripts").unwrap();
+        assert_eq!(scripts_info.project_name, "scripts");
+        assert_eq!(scripts_info.session_id, info.projects[1].session_id);
+        assert_ne!(scripts_info.session_id, info.session_id);
+
+        let read_response = session
+            .get_api_read_at("/api/scripts", scripts_info.root_instance_id)
+            .unwrap();
+        let root = &read_response.instances[&scripts_info.root_instance_id];
+        assert_eq!(root.name, "scripts");
+        assert_eq!(root.children.len(), 2);
+
+        // Names that aren't served aren't sent to the first project instead.
+        for path in &["/api/missing", "/api/missing/rojo", "/api/scripts"] {
+            let response = session.client().get(&session.url(path)).send().unwrap();
+            assert_eq!(
+                response.status(),
+                reqwest::StatusCode::NOT_FOUND,
+                "{}",
+                path
+            );
+        }
+    });
+}
+
+/// A `rojo serve --stdio` process serving a copy of one of the serve tests.
+struct StdioSession {
+    _process: KillOnDrop,
+    _dir: TempDir,
+    project_path: PathBuf,
+    stdin: ChildStdin,
+    receiver: mpsc::Receiver<Value>,
+}
+
+impl StdioSession {
+    fn new(name: &str) -> Self {
+        let dir = tempdir().unwrap();
+        let project_path = dir.path().join(name);
+        fs::create_dir(&project_path).unwrap();
+        copy_recursive(&Path::new(SERVE_TESTS_PATH).join(name), &project_path).unwrap();
+
+        let mut process = KillOnDrop(
+            Command::new(ROJO_PATH)
+                .args(&["serve", "--stdio"])
+                .arg(&project_path)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("Couldn't start Rojo"),
+        );
+
+        let stdin = process.0.stdin.take().unwrap();
+        let stdout = BufReader::new(process.0.stdout.take().unwrap());
+
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                let message: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        StdioSession {
+            _process: process,
+            _dir: dir,
+            project_path,
+            stdin,
+            receiver,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        writeln!(self.stdin, "{}", message).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn recv(&self) -> Value {
+        self.receiver.recv_timeout(Duration::from_secs(10)).unwrap()
+    }
+}
+
+#[test]
+fn stdio() {
+    let mut session = StdioSession::new("scripts");
+
+    session.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "rojo" }));
+    let response = session.recv();
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["result"]["projectName"], "scripts");
+
+    session.send(
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "subscribe", "params": { "cursor": 0 } }),
+    );
+    let response = session.recv();
+    assert_eq!(response["id"], 2);
+    assert_eq!(response["result"], Value::Null);
+
+    fs::write(session.project_path.join("src/foo.lua"), "Updated foo!").unwrap();
+
+    let notification = session.recv();
+    assert_eq!(notification["method"], "messages");
+    assert_eq!(notification["params"]["messageCursor"], 1);
+    assert_eq!(
+        notification["params"]["messages"][0]["updated"][0]["setProperties"]["Source"]["String"],
+        "Updated foo!"
+    );
+
+    session.send(json!({ "jsonrpc": "2.0", "id": 3, "method": "nope" }));
+    let response = session.recv();
+    assert_eq!(response["id"], 3);
+    assert_eq!(response["error"]["code"], -32601);
+}
+
+#[test]
+fn stdio_shared_strings() {
+    let mut session = StdioSession::new("shared_strings");
+
+    session.send(
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "subscribe", "params": { "cursor": 0 } }),
+    );
+    let response = session.recv();
+    assert_eq!(response["id"], 1);
+
+    let model_path = session.project_path.join("src/unions.rbxmx");
+    let model = fs::read_to_string(&model_path).unwrap();
+
+    // "Goodbye, world!" in place of "Hello, world!"
+    fs::write(
+        &model_path,
+        model.replace("SGVsbG8sIHdvcmxkIQ==", "R29vZGJ5ZSwgd29ybGQh"),
+    )
+    .unwrap();
+
+    let notification = session.recv();
+    assert_eq!(notification["method"], "messages");
+    assert_eq!(notification["params"]["messageCursor"], 1);
+
+    let shared_strings = notification["params"]["sharedStrings"].as_object().unwrap();
+    assert_eq!(shared_strings.len(), 1);
+    assert!(shared_strings
+        .values()
+        .any(|contents| contents == "R29vZGJ5ZSwgd29ybGQh"));
+
+    // Messages with SharedStrings in them used to end the subscription.
+    fs::write(&model_path, model.replace("Second", "Third")).unwrap();
+
+    let notification = session.recv();
+    assert_eq!(notification["method"], "messages");
+    assert_eq!(notification["params"]["messageCursor"], 2);
+}
+
+#[test]
+fn token() {
+    let mut session = TestServeSession::with_options(&["empty"], &[], Some("secret"));
+    let info = session.wait_to_come_online();
+    assert_eq!(info.project_name, "empty");
+
+    let client = reqwest::Client::new();
+    let url = session.url("/api/rojo");
+
+    let response = client.get(&url).send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client.get(&url).bearer_auth("wrong").send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // The web UI doesn't need the token, except for metrics.
+    let response = client.get(&session.url("/")).send().unwrap();
+    assert!(response.status().is_success());
+
+    let response = client.get(&session.url("/history")).send().unwrap();
+    assert!(response.status().is_success());
+
+    let metrics_url = session.url("/metrics");
+    let response = client.get(&metrics_url).send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .get(&metrics_url)
+        .bearer_auth("secret")
+        .send()
+        .unwrap();
+    assert!(response.status().is_success());
+}
+
+#[test]
+fn token_hides_problems_on_home_page() {
+    let mut session = TestServeSession::with_options(&["broken_files"], &[], Some("secret"));
+    session.wait_to_come_online();
+
+    let client = reqwest::Client::new();
+    let url = session.url("/");
+
+    let home = client.get(&url).send().unwrap().text().unwrap();
+    assert!(!home.contains("bad.model.json"));
+    assert!(home.contains("Problems with project files are only shown"));
+
+    let home = client
+        .get(&url)
+        .bearer_auth("secret")
+        .send()
+        .unwrap()
+        .text()
+        .unwrap();
+    assert!(home.contains("bad.model.json"));
+}
+
+#[test]
+fn read_only() {
+    let mut session = TestServeSession::with_options(&["scripts"], &["--read-only"], None);
+    let info = session.wait_to_come_online();
+    assert!(info.read_only);
+
+    let response = reqwest::Client::new()
+        .post(&session.url("/api/write"))
+        .json(&json!({
+            "sessionId": info.session_id,
+            "removed": [],
+            "updated": [],
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn read_depth_and_sources() {
+    run_serve_test("scripts", |session, _redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+
+        let count = |options: &str| {
+            session
+                .get_api_read_with(root_id, options)
+                .unwrap()
+                .instances
+                .len()
+        };
+        assert_eq!(count("depth=0"), 1);
+        assert_eq!(count("depth=1"), 3);
+        assert_eq!(count(""), 3);
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        let root = &read_response.instances[&root_id];
+        let metadata = root.metadata.as_ref().unwrap();
+        assert!(metadata.relevant_paths.is_empty());
+        assert!(metadata.instigating_source.is_none());
+
+        let read_response = session
+            .get_api_read_with(root_id, "depth=1&sources=true")
+            .unwrap();
+        let foo = read_response
+            .instances
+            .values()
+            .find(|instance| instance.name == "foo")
+            .unwrap();
+        let metadata = foo.metadata.as_ref().unwrap();
+        let foo_path = session.path().join("src/foo.lua");
+        assert!(metadata.relevant_paths.contains(&foo_path));
+        assert!(matches!(
+            &metadata.instigating_source,
+            Some(InstigatingSource::Path(path)) if *path == foo_path
+        ));
+
+        let response = session
+            .client()
+            .get(&session.url(&format!("/api/read/{}?depth=deep", root_id)))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    });
+}
+
+#[test]
+fn search() {
+    run_serve_test("scripts", |session, _redactions| {
+        let info = session.get_api_rojo().unwrap();
+
+        let match_names = |search: &str| {
+            let response = session.get_api_search(search).unwrap();
+            assert!(!response.truncated);
+
+            let mut names: Vec<String> = response
+                .matches
+                .iter()
+                .map(|id| response.instances[id].name.to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Names and class names match without regard to case.
+        assert_eq!(match_names("FOO"), vec!["foo"]);
+        assert_eq!(match_names("script"), vec!["bar", "foo", "scripts"]);
+        assert_eq!(match_names("bar.server.lua"), vec!["bar"]);
+        assert!(match_names("nothing%20like%20this").is_empty());
+
+        // Matches come with their ancestors, so they can be shown in place.
+        let response = session.get_api_search("foo").unwrap();
+        assert!(response.instances.contains_key(&info.root_instance_id));
+
+        let response = session
+            .client()
+            .get(&session.url("/api/search/"))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    });
+}
+
+#[test]
+fn shared_strings() {
+    run_serve_test("shared_strings", |session, _redactions| {
+        let info = session.get_api_rojo().unwrap();
+
+        // SharedStrings can't be read back into `ReadResponse`, since they're
+        // sent by hash, so this looks at the JSON directly.
+        let response: Value = session
+            .client()
+            .get(&session.url(&format!("/api/read/{}", info.root_instance_id)))
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+
+        let instances = response["instances"].as_object().unwrap();
+        let hashes: Vec<&Value> = instances
+            .values()
+            .filter(|instance| instance["ClassName"] == "UnionOperation")
+            .map(|instance| &instance["Properties"]["PhysicalConfigData"]["SharedString"])
+            .collect();
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[0], hashes[1]);
+
+        // Both unions refer to the same contents, which are only sent once.
+        let shared_strings = response["sharedStrings"].as_object().unwrap();
+        assert_eq!(shared_strings.len(), 1);
+
+        let hash = hashes[0].as_str().unwrap();
+        assert_eq!(shared_strings[hash], "SGVsbG8sIHdvcmxkIQ==");
+    });
+}
+
+#[test]
+fn write_shared_string() {
+    run_serve_test("shared_strings", |session, _redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let read_url = session.url(&format!("/api/read/{}", info.root_instance_id));
+        let read = || -> Value {
+            session
+                .client()
+                .get(&read_url)
+                .send()
+                .unwrap()
+                .json()
+                .unwrap()
+        };
+
+        let response = read();
+        let union_id = response["instances"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .find(|(_, instance)| instance["ClassName"] == "UnionOperation")
+            .map(|(id, _)| id.clone())
+            .unwrap();
+
+        // Clients send the contents of SharedStrings as base64, like the
+        // plugin does.
+        let response = session
+            .client()
+            .post(&session.url("/api/write"))
+            .json(&json!({
+                "sessionId": info.session_id,
+                "removed": [],
+                "updated": [{
+                    "id": union_id,
+                    "setProperties": {
+                        "PhysicalConfigData": { "SharedString": "R29vZGJ5ZSE=" },
+                    },
+                }],
+            }))
+            .send()
+            .unwrap();
+        assert!(response.status().is_success());
+
+        // Writes are applied in the background, so wait for this one to land.
+        for _ in 0..50 {
+            let response = read();
+            let hash = response["instances"][&union_id]["Properties"]["PhysicalConfigData"]
+                ["SharedString"]
+                .as_str()
+                .unwrap();
+
+            if response["sharedStrings"][hash] == "R29vZGJ5ZSE=" {
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        panic!("The written SharedString never showed up");
+    });
+}
+
+#[test]
+fn compressed_read() {
+    run_serve_test("scripts", |session, _redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let url = session.url(&format!("/api/read/{}?sources=true", info.root_instance_id));
+
+        // Clients decompress responses themselves by default, which would hide
+        // whether the server compressed them.
+        let client = reqwest::Client::builder().gzip(false).build().unwrap();
+
+        for &encoding in &["gzip", "deflate"] {
+            let mut response = client
+                .get(&url)
+                .header("Accept-Encoding", encoding)
+                .send()
+                .unwrap();
+            assert_eq!(response.headers()["Content-Encoding"], encoding);
+
+            let mut compressed = Vec::new();
+            response.copy_to(&mut compressed).unwrap();
+
+            let mut body = Vec::new();
+            if encoding == "gzip" {
+                GzDecoder::new(compressed.as_slice())
+                    .read_to_end(&mut body)
+                    .unwrap();
+            } else {
+                ZlibDecoder::new(compressed.as_slice())
+                    .read_to_end(&mut body)
+                    .unwrap();
+            }
+
+            let read_response: ReadResponse = serde_json::from_slice(&body).unwrap();
+            assert_eq!(read_response.session_id, info.session_id);
+        }
+
+        let response = client.get(&url).send().unwrap();
+        assert!(!response.headers().contains_key("Content-Encoding"));
+    });
+}
+
+#[test]
+fn remove_property() {
+    run_serve_test("remove_property", |session, _redactions| {
+        fs::write(
+            session.path().join("src/value.model.json"),
+            r#"{ "ClassName": "StringValue" }"#,
+        )
+        .unwrap();
+
+        let mut cursor = 0;
+        let update = loop {
+            let response = session.get_api_subscribe(cursor).unwrap();
+            cursor = response.message_cursor;
+
+            let update = response
+                .messages
+                .into_iter()
+                .flat_map(|message| message.updated)
+                .find(|update| !update.removed_properties.is_empty());
+
+            if let Some(update) = update {
+                break update;
+            }
+        };
+
+        assert_eq!(update.removed_properties, vec!["Value".to_owned()]);
+        assert!(update.set_properties.is_empty());
+    });
+}
+
+#[test]
+fn history() {
+    run_serve_test("scripts", |session, _redactions| {
+        let info = session.get_api_rojo().unwrap();
+
+        let history = session.get_api_history().unwrap();
+        assert_eq!(history.session_id, info.session_id);
+        assert!(history.entries.is_empty());
+        assert!(!history.more);
+
+        let foo_path = session.path().join("src/foo.lua");
+        fs::write(&foo_path, "-- Goodbye, from foo!").unwrap();
+
+        // Writing a file can show up as more than one change, so keep reading
+        // messages until the new source shows up.
+        let mut cursor = 0;
+        loop {
+            let response = session.get_api_subscribe(cursor).unwrap();
+            cursor = response.message_cursor;
+
+            let changed = response.messages.iter().any(|message| {
+                message
+                    .updated
+                    .iter()
+                    .any(|update| update.set_properties.contains_key("Source"))
+            });
+
+            if changed {
+                break;
+            }
+        }
+
+        let history = session.get_api_history().unwrap();
+        assert_eq!(history.message_cursor, cursor);
+        assert_eq!(
+            history.entries.first().map(|entry| entry.index + 1),
+            Some(cursor)
+        );
+
+        let update = history
+            .entries
+            .iter()
+            .filter(|entry| {
+                matches!(
+                    &entry.cause,
+                    Some(PatchCause::FileChanged { path }) if *path == foo_path
+                )
+            })
+            .flat_map(|entry| &entry.updated)
+            .find(|update| update.instance.name.as_deref() == Some("foo"))
+            .unwrap();
+
+        let change = update
+            .changes
+            .iter()
+            .find(|change| change.property == "Source")
+            .unwrap();
+        assert_eq!(
+            change.before,
+            Some(Variant::String("-- Hello, from foo!".to_owned()))
+        );
+        assert_eq!(
+            change.after,
+            Some(Variant::String("-- Goodbye, from foo!".to_owned()))
+        );
+
+        // The page reads the history from the API, so that it needs the token
+        // when the server has one.
+        let page = session
+            .client()
+            .get(&session.url("/history"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert!(page.contains("history-list"));
+        assert!(!page.contains("Goodbye, from foo!"));
+    });
+}
+
+#[test]
+fn metrics() {
+    run_serve_test("scripts", |session, _redactions| {
+        let metrics = session
+            .client()
+            .get(&session.url("/metrics"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+
+        let value = |sample: &str| -> f64 {
+            let line = metrics
+                .lines()
+                .find(|line| line.starts_with(sample))
+                .unwrap_or_else(|| panic!("{} is missing from:\n{}", sample, metrics));
+
+            line[sample.len()..].trim().parse().unwrap()
+        };
+
+        assert_eq!(value(r#"rojo_instances{project="scripts"}"#), 3.0);
+        assert_eq!(
+            value(r#"rojo_phase_seconds_count{project="scripts",phase="snapshot"}"#),
+            1.0
+        );
+        assert!(value(r#"rojo_vfs_files_read_total{project="scripts"}"#) >= 3.0);
+        assert!(value(r#"rojo_vfs_bytes_read_total{project="scripts"}"#) > 0.0);
+        assert!(value(r#"rojo_vfs_dirs_read_total{project="scripts"}"#) >= 1.0);
+
+        let home = session
+            .client()
+            .get(&session.url("/"))
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert!(home.contains("Performance"));
+    });
+}
+
+#[test]
+fn diagnostics() {
+    run_serve_test("empty_json_model", |session, _redactions| {
+        let info = session.get_api_rojo().unwrap();
+        assert!(session
+            .get_api_diagnostics()
+            .unwrap()
+            .diagnostics
+            .is_empty());
+
+        let model_path = session.path().join("src/test.model.json");
+        fs::write(&model_path, "{\"ClassName\": ").unwrap();
+
+        // Writing a file can show up as more than one change, so keep reading
+        // messages until the diagnostic we're after shows up.
+        let mut cursor = 0;
+        let reported = loop {
+            let response = session.get_api_subscribe(cursor).unwrap();
+            cursor = response.message_cursor;
+
+            let reported = response
+                .messages
+                .into_iter()
+                .flat_map(|message| message.reported_diagnostics)
+                .last();
+
+            if let Some(diagnostic) = reported {
+                break diagnostic;
+            }
+        };
+        assert!(reported.message.contains("test.model.json"));
+
+        let diagnostics_response = session.get_api_diagnostics().unwrap();
+        assert_eq!(diagnostics_response.session_id, info.session_id);
+        assert_eq!(diagnostics_response.diagnostics.len(), 1);
+        assert_eq!(diagnostics_response.diagnostics[0].path, reported.path);
+
+        fs::write(&model_path, r#"{"ClassName": "Model"}"#).unwrap();
+
+        let mut cursor = diagnostics_response.message_cursor;
+        loop {
+            let response = session.get_api_subscribe(cursor).unwrap();
+            cursor = response.message_cursor;
+
+            let cleared = response
+                .messages
+                .iter()
+                .any(|message| message.cleared_diagnostics.contains(&reported.path));
+
+            if cleared {
+                break;
+            }
+        }
+
+        assert!(session
+            .get_api_diagnostics()
+            .unwrap()
+            .diagnostics
+            .is_empty());
+    });
+}
+
+#[test]
+fn broken_files_at_startup() {
+    run_serve_test("broken_files", |session, _redactions| {
+        let info = session.get_api_rojo().unwrap();
+
+        let names = |session: &TestServeSession| -> Vec<(String, String)> {
+            let read_response = session.get_api_read(info.root_instance_id).unwrap();
+            let mut names: Vec<_> = read_response
+                .instances
+                .values()
+                .map(|instance| (instance.name.to_string(), instance.class_name.to_string()))
+                .collect();
+            names.sort();
+            names
+        };
+
+        // The broken directory entry is left out, and the broken project node
+        // is replaced with a placeholder.
+        let names_before = names(&session);
+        assert!(names_before.contains(&("good".to_owned(), "Model".to_owned())));
+        assert!(names_before.contains(&("broken".to_owned(), "Folder".to_owned())));
+        assert!(!names_before.iter().any(|(name, _)| name == "bad"));
+
+        let diagnostics_response = session.get_api_diagnostics().unwrap();
+        let diagnostic_paths: Vec<_> = diagnostics_response
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.path.clone())
+            .collect();
+        assert_eq!(
+            diagnostic_paths,
+            vec![
+                session.path().join("broken.model.json"),
+                session.path().join("src/bad.model.json"),
+            ]
+        );
+
+        for path in &diagnostic_paths {
+            fs::write(path, r#"{"ClassName": "Model"}"#).unwrap();
+        }
+
+        let mut cursor = diagnostics_response.message_cursor;
+        let mut cleared = Vec::new();
+        while cleared.len() < diagnostic_paths.len() {
+            let response = session.get_api_subscribe(cursor).unwrap();
+            cursor = response.message_cursor;
+
+            for message in response.messages {
+                cleared.extend(message.cleared_diagnostics);
+            }
+        }
+
+        let names_after = names(&session);
+        assert!(names_after.contains(&("bad".to_owned(), "Model".to_owned())));
+        assert!(names_after.contains(&("broken".to_owned(), "Model".to_owned())));
+        assert!(session
+            .get_api_diagnostics()
+            .unwrap()
+            .diagnostics
+            .is_empty());
+    });
+}
+
+#[test]
+fn tls_self_signed() {
+    let cert_dir = tempdir().unwrap();
+    let cert_path = cert_dir.path().join("cert.pem");
+    let key_path = cert_dir.path().join("key.pem");
+
+    let mut session = TestServeSession::with_options(
+        &["empty"],
+        &[
+            "--tls-cert",
+            cert_path.to_str().unwrap(),
+            "--tls-key",
+            key_path.to_str().unwrap(),
+            "--tls-self-signed",
+        ],
+        None,
+    )
+    .with_tls(cert_path.clone());
+
+    let info = session.wait_to_come_online();
+    assert_eq!(info.project_name, "empty");
+    assert!(key_path.exists());
+
+    // Clients that don't trust the certificate can't connect.
+    let result = reqwest::Client::new().get(&session.url("/api/rojo")).send();
+    assert!(result.is_err());
+
+    let response = session.client().get(&session.url("/")).send().unwrap();
+    assert!(response.status().is_success());
+}
+
+#[test]
+fn tls_stalled_handshakes() {
+    let cert_dir = tempdir().unwrap();
+    let cert_path = cert_dir.path().join("cert.pem");
+    let key_path = cert_dir.path().join("key.pem");
+
+    let mut session = TestServeSession::with_options(
+        &["empty"],
+        &[
+            "--tls-cert",
+            cert_path.to_str().unwrap(),
+            "--tls-key",
+            key_path.to_str().unwrap(),
+            "--tls-self-signed",
+        ],
+        None,
+    )
+    .with_tls(cert_path.clone());
+    session.wait_to_come_online();
+
+    // These connections never start their handshakes, which shouldn't keep
+    // anyone else from connecting.
+    let stalled: Vec<_> = (0..32)
+        .map(|_| TcpStream::connect(("localhost", session.port() as u16)).unwrap())
+        .collect();
+
+    let response = session.client().get(&session.url("/")).send().unwrap();
+    assert!(response.status().is_success());
+
+    drop(stalled);
+}
+
+#[test]
+#[cfg(unix)]
+fn graceful_shutdown() {
+    let mut session = TestServeSession::new(&["empty"]);
+    let info = session.wait_to_come_online();
+    assert_eq!(info.project_name, "empty");
+
+    // Nothing changes in this project, so this request waits until the server
+    // ends it.
+    let subscribe_url = session.url("/api/subscribe/0");
+    let subscriber = thread::spawn(move || {
+        reqwest::get(&subscribe_url)
+            .and_then(|mut response| response.json::<SubscribeResponse<'static>>())
+    });
+    thread::sleep(Duration::from_millis(300));
+
+    let status = session.stop_with_signal("INT");
+    assert!(status.success(), "Rojo exited with {}", status);
+
+    let response = subscriber.join().unwrap().unwrap();
+    assert!(response.session_ending);
+    assert!(response.messages.is_empty());
+    assert_eq!(response.session_id, info.session_id);
+}
+
+#[test]
+#[cfg(unix)]
+fn shutdown_finishes_writes() {
+    let mut session = TestServeSession::new(&["scripts"]);
+    let info = session.wait_to_come_online();
+
+    let read_response = session.get_api_read(info.root_instance_id).unwrap();
+    let (foo_id, _) = read_response
+        .instances
+        .iter()
+        .find(|(_, instance)| instance.name == "foo")
+        .unwrap();
+
+    let response = reqwest::Client::new()
+        .post(&session.url("/api/write"))
+        .json(&json!({
+            "sessionId": info.session_id,
+            "removed": [],
+            "updated": [{
+                "id": foo_id,
+                "setProperties": {
+                    "Source": { "String": "Written during shutdown" },
+                },
+            }],
+        }))
+        .send()
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let status = session.stop_with_signal("TERM");
+    assert!(status.success(), "Rojo exited with {}", status);
+
+    let source = fs::read_to_string(session.path().join("src/foo.lua")).unwrap();
+    assert_eq!(source, "Written during shutdown");
+}